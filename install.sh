@@ -6,21 +6,12 @@ else
     echo "Installing Lua.."
     if which apt &> /dev/null; then
     sudo apt-get install lua
-    MNGR=apt-get
 else
-    echo "Assuming DNF/YUM.. if not, end the program now and install lua and luarocks manually."
+    echo "Assuming DNF/YUM.. if not, end the program now and install lua manually."
     sleep 5
-    MNGR=dnf
     sudo dnf install lua
 fi
 fi
-if which luarocks &> /dev/null; then
-    echo "Luarocks is installed. Skipping.."
-else
-    echo "Installing luarocks.."
-    $MNGR install luarocks
-fi
-sudo luarocks install luafilesystem
 # pretend that it copies the files omg omg (it doesn't)
 echo "done"
 exit 0
\ No newline at end of file
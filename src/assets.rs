@@ -0,0 +1,99 @@
+use eframe::egui;
+use mlua::prelude::*;
+use orbit::vfs::{self, Vfs};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use crate::sandbox::PluginContext;
+
+/// Textures and fonts loaded by plugins, kept in the Lua state's app data. Entries are
+/// keyed by file so they can be dropped and reloaded when the file changes on disk.
+/// Files inside archives are keyed by the archive's path followed by the entry's.
+#[derive(Default)]
+pub struct AssetCache {
+    textures: HashMap<PathBuf, egui::TextureHandle>,
+    /// Font family name -> font file.
+    fonts: BTreeMap<String, PathBuf>,
+}
+
+/// An image file a plugin asked for with `orbit.assets.image(path)`; drawn with `ui:image`.
+pub struct LuaImage {
+    pub path: PathBuf,
+}
+
+impl LuaUserData for LuaImage {}
+
+impl AssetCache {
+    pub fn texture(&mut self, ctx: &egui::Context, path: &Path) -> Result<egui::TextureHandle, String> {
+        if let Some(texture) = self.textures.get(path) {
+            return Ok(texture.clone());
+        }
+        let data = vfs::read(path)?;
+        let image = image::load_from_memory(&data).map_err(|e| format!("Failed to decode {:?}: {}", path, e))?;
+        let size = [image.width() as _, image.height() as _];
+        let image_buffer = image.to_rgba8();
+        let pixels = image_buffer.as_flat_samples();
+        let color_image = egui::ColorImage::from_rgba_unmultiplied(size, pixels.as_slice());
+        let texture = ctx.load_texture(path.to_string_lossy(), color_image, egui::TextureOptions::default());
+        self.textures.insert(path.to_path_buf(), texture.clone());
+        Ok(texture)
+    }
+
+    pub fn has_font(&self, family: &str) -> bool {
+        self.fonts.contains_key(family)
+    }
+
+    pub fn add_font(&mut self, ctx: &egui::Context, family: &str, path: &Path) -> Result<(), String> {
+        if self.fonts.get(family).is_some_and(|loaded| loaded == path) {
+            return Ok(());
+        }
+        let previous = self.fonts.insert(family.to_string(), path.to_path_buf());
+        let applied = self.apply_fonts(ctx);
+        if applied.is_err() {
+            match previous {
+                Some(previous) => self.fonts.insert(family.to_string(), previous),
+                None => self.fonts.remove(family),
+            };
+        }
+        applied
+    }
+
+    /// Installs egui's default fonts plus every plugin font, read fresh from disk.
+    fn apply_fonts(&self, ctx: &egui::Context) -> Result<(), String> {
+        let mut definitions = egui::FontDefinitions::default();
+        for (family, path) in &self.fonts {
+            let data = vfs::read(path)?;
+            definitions.font_data.insert(family.clone(), egui::FontData::from_owned(data).into());
+            definitions.families.insert(egui::FontFamily::Name(family.as_str().into()), vec![family.clone()]);
+        }
+        ctx.set_fonts(definitions);
+        Ok(())
+    }
+
+    /// Forgets textures loaded from `changed` files, or from archives among them, and
+    /// reloads fonts if one of them changed.
+    pub fn invalidate(&mut self, ctx: &egui::Context, changed: &[PathBuf]) -> Result<(), String> {
+        let is_changed = |path: &PathBuf| changed.iter().any(|changed| path.starts_with(changed));
+        self.textures.retain(|path, _| !is_changed(path));
+        if self.fonts.values().any(is_changed) {
+            self.apply_fonts(ctx)?;
+        }
+        Ok(())
+    }
+}
+
+/// Finds the file behind an asset path a plugin asked for. The game looks relative
+/// paths up in the virtual filesystem first, so mods can replace its files. Other
+/// plugins get their own file first and only fall back to the layered one.
+pub fn locate(lua: &Lua, plugin: &PluginContext, path: &str) -> LuaResult<PathBuf> {
+    let layered = || lua.app_data_ref::<Vfs>().and_then(|vfs| vfs.locate(path).map(Path::to_path_buf));
+    if plugin.name == "game" {
+        if let Some(location) = layered() {
+            return Ok(location);
+        }
+    }
+    let resolved = plugin.resolve_path(path)?;
+    if resolved.is_file() {
+        return Ok(resolved);
+    }
+    layered().ok_or_else(|| LuaError::RuntimeError(format!("{:?} does not exist", resolved)))
+}
@@ -0,0 +1,248 @@
+use mlua::prelude::*;
+use mlua::{Debug, HookTriggers};
+use std::collections::HashMap;
+
+/// Lua instructions one plugin may run per frame before it is stopped. Far more than
+/// any plugin needs to draw a frame, but small enough that a stuck loop only freezes
+/// the window for a moment.
+pub const INSTRUCTIONS_PER_FRAME: u64 = 20_000_000;
+/// Bytes one call into a plugin may allocate beyond what the Lua state already uses.
+pub const MEMORY_CAP: usize = 256 * 1024 * 1024;
+/// Bytes one plugin may keep allocated across all of its calls, e.g. in tables it
+/// holds on to between frames.
+pub const RETAINED_CAP: usize = 512 * 1024 * 1024;
+/// How often the hook counts; lower is more precise and slower.
+const HOOK_INTERVAL: u32 = 10_000;
+
+/// Per-frame instruction counts and memory caps for plugin code, kept in the Lua
+/// state's app data. The host wraps every call into a plugin in [`run`], so the hook
+/// knows whose code is running.
+pub struct Budget {
+    /// Plugins whose code is running, innermost last: plugins call each other through events.
+    running: Vec<String>,
+    /// For each entry of `running`: memory in use when it started, and how much the
+    /// calls it made into other plugins changed that.
+    memory: Vec<(usize, isize)>,
+    used: HashMap<String, u64>,
+    /// Bytes each plugin's calls left allocated, added up over every call.
+    retained: HashMap<String, usize>,
+    /// Instructions between hook calls. Drops to 1 once a plugin is over budget, so
+    /// the stop cannot be swallowed by a `pcall` in a loop.
+    interval: u32,
+    /// Plugins stopped since the host last asked, with the reason.
+    stopped: Vec<(String, String)>,
+}
+
+impl Default for Budget {
+    fn default() -> Self {
+        Self {
+            running: Vec::new(),
+            memory: Vec::new(),
+            used: HashMap::new(),
+            retained: HashMap::new(),
+            interval: HOOK_INTERVAL,
+            stopped: Vec::new(),
+        }
+    }
+}
+
+fn budget(lua: &Lua) -> LuaResult<mlua::AppDataRefMut<'_, Budget>> {
+    lua.app_data_mut::<Budget>()
+        .ok_or_else(|| LuaError::RuntimeError("execution budgets are not available".to_string()))
+}
+
+fn triggers(interval: u32) -> HookTriggers {
+    HookTriggers::new().every_nth_instruction(interval)
+}
+
+fn hook(lua: &Lua, _: Debug) -> LuaResult<()> {
+    let Some(mut budget) = lua.app_data_mut::<Budget>() else {
+        return Ok(());
+    };
+    let Some(plugin) = budget.running.last().cloned() else {
+        return Ok(());
+    };
+    let interval = budget.interval;
+    let used = budget.used.entry(plugin.clone()).or_default();
+    *used += interval as u64;
+    if *used <= INSTRUCTIONS_PER_FRAME {
+        return Ok(());
+    }
+    let reason = format!(
+        "plugin '{}' ran more than {} Lua instructions in one frame and was stopped; it may be stuck in a loop",
+        plugin, INSTRUCTIONS_PER_FRAME
+    );
+    budget.stop(&plugin, &reason);
+    if interval != 1 {
+        budget.interval = 1;
+        drop(budget);
+        lua.set_hook(triggers(1), hook);
+    }
+    Err(LuaError::RuntimeError(reason))
+}
+
+/// Sets up the budget and the instruction hook on a Lua state.
+pub fn install(lua: &Lua) {
+    lua.set_app_data(Budget::default());
+    lua.set_hook(triggers(HOOK_INTERVAL), hook);
+}
+
+/// Starts a new frame: every plugin gets its full instruction budget back.
+pub fn new_frame(lua: &Lua) {
+    if let Ok(mut budget) = budget(lua) {
+        budget.used.clear();
+        budget.interval = HOOK_INTERVAL;
+    }
+    lua.set_hook(triggers(HOOK_INTERVAL), hook);
+}
+
+fn is_memory_error(error: &LuaError) -> bool {
+    match error {
+        LuaError::MemoryError(_) => true,
+        LuaError::CallbackError { cause, .. } => is_memory_error(cause),
+        _ => false,
+    }
+}
+
+impl Budget {
+    fn stop(&mut self, plugin: &str, reason: &str) {
+        if !self.stopped.iter().any(|(stopped, _)| stopped == plugin) {
+            self.stopped.push((plugin.to_string(), reason.to_string()));
+        }
+    }
+}
+
+/// Runs `call` as `plugin`'s code: its instructions count against the plugin's budget,
+/// it may allocate at most [`MEMORY_CAP`] more bytes, and what it leaves allocated
+/// counts towards the plugin's [`RETAINED_CAP`].
+pub fn run<R>(lua: &Lua, plugin: &str, call: impl FnOnce() -> LuaResult<R>) -> LuaResult<R> {
+    let start = lua.used_memory();
+    {
+        let mut budget = budget(lua)?;
+        budget.running.push(plugin.to_string());
+        budget.memory.push((start, 0));
+    }
+    let cap = start.saturating_add(MEMORY_CAP);
+    let previous = lua.set_memory_limit(cap).unwrap_or(0);
+    if previous != 0 && previous < cap {
+        // a plugin called from another one stays inside the caller's cap
+        let _ = lua.set_memory_limit(previous);
+    }
+    let result = call();
+    let _ = lua.set_memory_limit(previous);
+    let mut budget = budget(lua)?;
+    budget.running.pop();
+    // only what this call kept itself; plugins it called were charged on their own
+    let (_, nested) = budget.memory.pop().unwrap_or((start, 0));
+    let change = lua.used_memory() as isize - start as isize;
+    if let Some((_, caller_nested)) = budget.memory.last_mut() {
+        *caller_nested += change;
+    }
+    let retained = budget.retained.entry(plugin.to_string()).or_default();
+    *retained = retained.saturating_add_signed(change - nested);
+    let over_cap = *retained > RETAINED_CAP;
+    if let Err(e) = &result {
+        if is_memory_error(e) {
+            let reason = format!(
+                "plugin '{}' tried to use more than {} MB of memory in one call and was stopped",
+                plugin, MEMORY_CAP / 1024 / 1024
+            );
+            budget.stop(plugin, &reason);
+            return Err(LuaError::RuntimeError(reason));
+        }
+    }
+    if over_cap {
+        // the count includes garbage; everything still in use after a collection
+        // is an upper bound for it
+        drop(budget);
+        let _ = lua.gc_collect();
+        let in_use = lua.used_memory();
+        let mut budget = self::budget(lua)?;
+        let retained = budget.retained.entry(plugin.to_string()).or_default();
+        *retained = (*retained).min(in_use);
+        if *retained > RETAINED_CAP {
+            let reason = format!(
+                "plugin '{}' kept more than {} MB of memory allocated and was stopped",
+                plugin, RETAINED_CAP / 1024 / 1024
+            );
+            budget.stop(plugin, &reason);
+            return Err(LuaError::RuntimeError(reason));
+        }
+    }
+    result
+}
+
+/// Forgets the memory a plugin kept, for when it is loaded again from scratch.
+pub fn forget_plugin(lua: &Lua, plugin: &str) {
+    if let Ok(mut budget) = budget(lua) {
+        budget.retained.remove(plugin);
+    }
+}
+
+/// Bytes each plugin is counted as keeping, for the debug view.
+pub fn retained(lua: &Lua) -> Vec<(String, usize)> {
+    let mut retained: Vec<_> = budget(lua)
+        .map(|budget| budget.retained.iter().map(|(plugin, bytes)| (plugin.clone(), *bytes)).collect())
+        .unwrap_or_default();
+    retained.sort();
+    retained
+}
+
+/// Plugins stopped for going over budget since the last call, with the reason.
+pub fn take_stopped(lua: &Lua) -> Vec<(String, String)> {
+    budget(lua).map(|mut budget| std::mem::take(&mut budget.stopped)).unwrap_or_default()
+}
+
+/// Resumes a coroutine with the hook moved onto it: the hook only follows one thread,
+/// so code in coroutines would otherwise run uncounted.
+fn resume<'lua>(lua: &'lua Lua, thread: &LuaThread<'lua>, args: LuaMultiValue<'lua>) -> LuaResult<LuaMultiValue<'lua>> {
+    let interval = budget(lua)?.interval;
+    thread.set_hook(triggers(interval), hook);
+    let result = thread.resume(args);
+    // back onto the thread that called resume
+    let interval = budget(lua)?.interval;
+    lua.set_hook(triggers(interval), hook);
+    result
+}
+
+/// Replaces `resume` and `wrap` in a plugin's `coroutine` table with versions that keep
+/// counting instructions inside the coroutine.
+pub fn hook_coroutines(lua: &Lua, coroutine: &LuaTable) -> LuaResult<()> {
+    coroutine.set("resume", lua.create_function(|lua, (thread, args): (LuaThread, LuaMultiValue)| {
+        match resume(lua, &thread, args) {
+            Ok(values) => Ok((true, values)),
+            Err(e) => Ok((false, LuaMultiValue::from_vec(vec![LuaValue::String(lua.create_string(e.to_string())?)]))),
+        }
+    })?)?;
+    coroutine.set("wrap", lua.create_function(|lua, function: LuaFunction| {
+        let thread = lua.create_registry_value(lua.create_thread(function)?)?;
+        lua.create_function(move |lua, args: LuaMultiValue| resume(lua, &lua.registry_value(&thread)?, args))
+    })?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn retained_by(lua: &Lua, plugin: &str) -> usize {
+        retained(lua).into_iter().find(|(name, _)| name == plugin).map_or(0, |(_, bytes)| bytes)
+    }
+
+    #[test]
+    fn charges_kept_memory_to_the_plugin_that_allocated_it() {
+        let lua = Lua::new();
+        install(&lua);
+        let keep = lua.load("return function(n) kept = {} for i = 1, n do kept[i] = i end end").eval::<LuaFunction>().unwrap();
+        // `a` calls into `b`, which keeps a large table; `a` only keeps a small one
+        run(&lua, "a", || {
+            run(&lua, "b", || keep.call::<_, ()>(100_000))?;
+            lua.load("small = { 1, 2, 3 }").exec()
+        }).unwrap();
+        assert!(retained_by(&lua, "b") > 1024 * 1024);
+        assert!(retained_by(&lua, "a") < 64 * 1024);
+
+        forget_plugin(&lua, "b");
+        assert_eq!(retained_by(&lua, "b"), 0);
+    }
+}
@@ -0,0 +1,570 @@
+use mlua::{ChunkMode, Lua};
+use orbit::manifest::Manifest;
+use orbit::modules;
+use orbit::permissions::Permission;
+use quick_xml::events::Event;
+use quick_xml::reader::Reader;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use crate::sandbox;
+
+/// Elements `index.xml` may contain.
+const MANIFEST_ELEMENTS: [&str; 16] = [
+    "plugin", "name", "description", "version", "author", "homepage", "tags", "tag", "logo",
+    "update", "permissions", "permission", "dependencies", "depends", "settings", "setting",
+];
+/// Stdlib and `orbit` functions that only work with a permission.
+const GATED: [(&str, &str, Permission); 5] = [
+    ("os", "execute", Permission::Process),
+    ("os", "getenv", Permission::Process),
+    ("io", "popen", Permission::Process),
+    ("orbit", "clipboard", Permission::Clipboard),
+    ("orbit", "window", Permission::Window),
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// One problem `check` found, optionally tied to a file inside the plugin and a line.
+#[derive(Clone, Debug)]
+pub struct Finding {
+    pub severity: Severity,
+    pub file: Option<PathBuf>,
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.severity {
+            Severity::Error => write!(f, "error: ")?,
+            Severity::Warning => write!(f, "warning: ")?,
+        }
+        if let Some(file) = &self.file {
+            write!(f, "{}:", file.display())?;
+            if let Some(line) = self.line {
+                write!(f, "{}:", line)?;
+            }
+            write!(f, " ")?;
+        }
+        write!(f, "{}", self.message)
+    }
+}
+
+fn error(file: Option<&Path>, line: Option<usize>, message: String) -> Finding {
+    Finding { severity: Severity::Error, file: file.map(Path::to_path_buf), line, message }
+}
+
+fn warning(file: Option<&Path>, line: Option<usize>, message: String) -> Finding {
+    Finding { severity: Severity::Warning, file: file.map(Path::to_path_buf), line, message }
+}
+
+/// Checks a plugin folder the way Orbit will load it: `index.xml` against the manifest
+/// format, the entrypoint and logo, every Lua file for syntax errors, and the globals
+/// and modules the Lua code uses. Modules may come from the plugin's dependencies
+/// installed in `config_dir`.
+pub fn check(dir: &Path, config_dir: &Path) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    let index = Path::new("index.xml");
+    let manifest = match fs::read_to_string(dir.join(index)) {
+        Ok(xml_content) => {
+            findings.extend(check_elements(&xml_content));
+            match Manifest::parse(&xml_content) {
+                Ok(manifest) => Some(manifest),
+                Err(e) => {
+                    findings.push(error(Some(index), None, e));
+                    None
+                },
+            }
+        },
+        Err(e) => {
+            findings.push(error(Some(index), None, format!("cannot be read: {}", e)));
+            None
+        },
+    };
+    if let Some(manifest) = &manifest {
+        for (element, value) in [("name", &manifest.name), ("version", &manifest.version)] {
+            if value.is_empty() {
+                findings.push(error(Some(index), None, format!("<{}> is missing or empty", element)));
+            }
+        }
+        if manifest.description.is_empty() {
+            findings.push(warning(Some(index), None, "<description> is missing or empty".to_string()));
+        }
+        for message in &manifest.warnings {
+            findings.push(warning(Some(index), None, message.clone()));
+        }
+        let mut keys = BTreeSet::new();
+        for spec in &manifest.settings {
+            if !keys.insert(&spec.key) {
+                findings.push(error(Some(index), None, format!("setting '{}' is declared twice", spec.key)));
+            }
+        }
+        if let Some(logo) = &manifest.logo {
+            match fs::read(dir.join(logo)) {
+                Ok(data) => {
+                    if let Err(e) = image::load_from_memory(&data) {
+                        findings.push(error(Some(logo), None, format!("logo cannot be decoded: {}", e)));
+                    }
+                },
+                Err(e) => findings.push(error(Some(logo), None, format!("logo cannot be read: {}", e))),
+            }
+        }
+    }
+
+    if !dir.join("index.lua").is_file() {
+        if dir.file_name().is_some_and(|name| name == "game") {
+            findings.push(error(None, None, "the game plugin has no index.lua entrypoint".to_string()));
+        } else {
+            findings.push(warning(None, None, "no index.lua entrypoint; the plugin can only provide modules".to_string()));
+        }
+    }
+
+    let mut files = Vec::new();
+    lua_files(dir, dir, &mut files);
+    let lua = Lua::new();
+    let mut scans = Vec::new();
+    for relative in files {
+        let code = match fs::read_to_string(dir.join(&relative)) {
+            Ok(code) => code,
+            Err(e) => {
+                findings.push(error(Some(&relative), None, format!("cannot be read: {}", e)));
+                continue;
+            },
+        };
+        let name = relative.to_string_lossy().replace('\\', "/");
+        let compiled = lua.load(&code)
+            .set_name(format!("@{}", name))
+            .set_mode(ChunkMode::Text)
+            .into_function();
+        match compiled {
+            Ok(_) => scans.push((relative, scan(&tokenize(&code)))),
+            Err(mlua::Error::SyntaxError { message, .. }) => {
+                // "<name>:<line>: <message>"
+                let rest = message.strip_prefix(&format!("{}:", name)).unwrap_or(&message);
+                let (line, text) = rest.split_once(": ")
+                    .and_then(|(line, text)| Some((line.parse().ok()?, text)))
+                    .map_or((None, rest), |(line, text)| (Some(line), text));
+                findings.push(error(Some(&relative), line, format!("syntax error: {}", text)));
+            },
+            Err(e) => findings.push(error(Some(&relative), None, e.to_string())),
+        }
+    }
+
+    let defined: BTreeSet<&str> = scans.iter().flat_map(|(_, scan)| scan.defined.iter().map(String::as_str)).collect();
+    let known: BTreeSet<&str> = sandbox::SAFE_BASICS.iter()
+        .chain(&sandbox::SAFE_LIBS)
+        .chain(&sandbox::ENV_GLOBALS)
+        .chain(&sandbox::SHARED_GLOBALS)
+        .copied()
+        .chain(defined)
+        .collect();
+    let name = dir.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+    let search_path = modules::search_path(config_dir, &name, dir);
+    let declared = manifest.as_ref().map(|m| m.permissions.clone()).unwrap_or_default();
+    for (relative, scan) in &scans {
+        for (name, line) in &scan.globals {
+            if !known.contains(name.as_str()) {
+                findings.push(warning(Some(relative), Some(*line), format!(
+                    "global '{}' is neither defined by the plugin nor provided by Orbit", name
+                )));
+            }
+        }
+        for (module, line) in &scan.requires {
+            if let Err(e) = modules::find(&search_path, module) {
+                findings.push(error(Some(relative), Some(*line), modules::not_found(config_dir, &search_path, module, e)));
+            }
+        }
+        for (what, line, permission) in &scan.gated {
+            if !declared.contains(permission) {
+                findings.push(warning(Some(relative), Some(*line), format!(
+                    "{} needs the '{}' permission, which index.xml does not declare", what, permission.name()
+                )));
+            }
+        }
+    }
+    findings.sort_by_key(|finding| finding.severity);
+    findings
+}
+
+/// Flags elements of `index.xml` that are not part of the manifest format.
+fn check_elements(xml_content: &str) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    let mut reader = Reader::from_str(xml_content);
+    reader.trim_text(true);
+
+    let mut buf = Vec::new();
+    let mut depth = 0;
+    let mut unknown = BTreeSet::new();
+    loop {
+        match reader.read_event_into(&mut buf) {
+            Ok(Event::Start(ref e)) => {
+                findings.extend(check_element(e.name().as_ref(), depth, &mut unknown));
+                depth += 1;
+            },
+            Ok(Event::Empty(ref e)) => findings.extend(check_element(e.name().as_ref(), depth, &mut unknown)),
+            Ok(Event::End(_)) => depth = depth.saturating_sub(1),
+            Ok(Event::Eof) | Err(_) => break,
+            _ => {}
+        }
+        buf.clear();
+    }
+    findings
+}
+
+fn check_element(name: &[u8], depth: usize, unknown: &mut BTreeSet<String>) -> Option<Finding> {
+    let index = Path::new("index.xml");
+    let name = String::from_utf8_lossy(name).to_string();
+    if depth == 0 && name != "plugin" {
+        Some(error(Some(index), None, format!("the root element is <{}> instead of <plugin>", name)))
+    } else if !MANIFEST_ELEMENTS.contains(&name.as_str()) && unknown.insert(name.clone()) {
+        Some(warning(Some(index), None, format!("unknown element <{}>", name)))
+    } else {
+        None
+    }
+}
+
+fn lua_files(root: &Path, dir: &Path, files: &mut Vec<PathBuf>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    let mut entries: Vec<_> = entries.flatten().collect();
+    entries.sort_by_key(|entry| entry.file_name());
+    for entry in entries {
+        if entry.file_name().to_string_lossy().starts_with('.') {
+            continue;
+        }
+        let path = entry.path();
+        if path.is_dir() {
+            lua_files(root, &path, files);
+        } else if path.extension().is_some_and(|ext| ext == "lua") {
+            if let Ok(relative) = path.strip_prefix(root) {
+                files.push(relative.to_path_buf());
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum TokenKind {
+    Name,
+    String,
+    Number,
+    Symbol,
+}
+
+struct Token {
+    kind: TokenKind,
+    text: String,
+    line: usize,
+}
+
+const KEYWORDS: [&str; 22] = [
+    "and", "break", "do", "else", "elseif", "end", "false", "for", "function", "goto", "if", "in",
+    "local", "nil", "not", "or", "repeat", "return", "then", "true", "until", "while",
+];
+const SYMBOLS: [&str; 10] = ["...", "..", "==", "~=", "<=", ">=", "::", "//", "<<", ">>"];
+
+/// Length of the `[==[` opening a long string or comment at `i`, with its level.
+fn long_bracket(chars: &[char], i: usize) -> Option<(usize, usize)> {
+    if chars.get(i) != Some(&'[') {
+        return None;
+    }
+    let level = chars[i + 1..].iter().take_while(|c| **c == '=').count();
+    (chars.get(i + 1 + level) == Some(&'[')).then_some((level + 2, level))
+}
+
+/// Skips to after the `]==]` closing a long bracket of `level`, counting lines.
+fn skip_long(chars: &[char], mut i: usize, level: usize, line: &mut usize) -> usize {
+    while i < chars.len() {
+        if chars[i] == '\n' {
+            *line += 1;
+        } else if chars[i] == ']'
+            && chars[i + 1..].iter().take_while(|c| **c == '=').count() == level
+            && chars.get(i + 1 + level) == Some(&']') {
+            return i + level + 2;
+        }
+        i += 1;
+    }
+    i
+}
+
+/// Splits Lua source into names, strings, numbers and symbols, dropping comments.
+/// Only meant for code that compiles.
+fn tokenize(code: &str) -> Vec<Token> {
+    let chars: Vec<char> = code.chars().collect();
+    let mut tokens = Vec::new();
+    let mut line = 1;
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let start_line = line;
+        if c == '\n' {
+            line += 1;
+            i += 1;
+        } else if c.is_whitespace() {
+            i += 1;
+        } else if c == '-' && chars.get(i + 1) == Some(&'-') {
+            i += 2;
+            if let Some((open, level)) = long_bracket(&chars, i) {
+                i = skip_long(&chars, i + open, level, &mut line);
+            } else {
+                while i < chars.len() && chars[i] != '\n' {
+                    i += 1;
+                }
+            }
+        } else if let Some((open, level)) = long_bracket(&chars, i) {
+            let start = i + open;
+            i = skip_long(&chars, start, level, &mut line);
+            let end = i.saturating_sub(level + 2).max(start);
+            tokens.push(Token { kind: TokenKind::String, text: chars[start..end].iter().collect(), line: start_line });
+        } else if c == '"' || c == '\'' {
+            let mut text = String::new();
+            i += 1;
+            while i < chars.len() && chars[i] != c && chars[i] != '\n' {
+                if chars[i] == '\\' {
+                    i += 1;
+                    if chars.get(i) == Some(&'\n') {
+                        line += 1;
+                    }
+                } else {
+                    text.push(chars[i]);
+                }
+                i += 1;
+            }
+            i += 1;
+            tokens.push(Token { kind: TokenKind::String, text, line: start_line });
+        } else if c.is_ascii_digit() || (c == '.' && chars.get(i + 1).is_some_and(|d| d.is_ascii_digit())) {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '.'
+                || (matches!(chars[i], '+' | '-') && matches!(chars[i - 1], 'e' | 'E' | 'p' | 'P'))) {
+                i += 1;
+            }
+            tokens.push(Token { kind: TokenKind::Number, text: chars[start..i].iter().collect(), line });
+        } else if c.is_alphabetic() || c == '_' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            tokens.push(Token { kind: TokenKind::Name, text: chars[start..i].iter().collect(), line });
+        } else {
+            let symbol = SYMBOLS.iter()
+                .find(|symbol| symbol.chars().enumerate().all(|(n, s)| chars.get(i + n) == Some(&s)))
+                .map(|symbol| symbol.to_string())
+                .unwrap_or_else(|| c.to_string());
+            i += symbol.chars().count();
+            tokens.push(Token { kind: TokenKind::Symbol, text: symbol, line });
+        }
+    }
+    tokens
+}
+
+/// What a Lua file does with names it does not declare as locals.
+#[derive(Default)]
+struct Scan {
+    /// Globals the file assigns or defines functions as.
+    defined: BTreeSet<String>,
+    /// Globals the file reads, with the line of the first use.
+    globals: BTreeMap<String, usize>,
+    /// Modules passed to `require` as a literal string.
+    requires: Vec<(String, usize)>,
+    /// Permission-gated functions the file uses, like `os.execute`.
+    gated: Vec<(String, usize, Permission)>,
+}
+
+/// Follows local scopes through the tokens to find which names are globals. It
+/// only knows about blocks, so `local x = x` counts the second `x` as the local.
+fn scan(tokens: &[Token]) -> Scan {
+    let mut result = Scan::default();
+    let mut scopes: Vec<Vec<String>> = vec![Vec::new()];
+    // locals of the block about to open: loop variables and function parameters
+    let mut pending: Vec<String> = Vec::new();
+    let mut brackets: Vec<&str> = Vec::new();
+    let is = |i: usize, text: &str| tokens.get(i).is_some_and(|t| t.text == text && t.kind != TokenKind::String);
+    let is_local = |scopes: &Vec<Vec<String>>, name: &str| scopes.iter().any(|scope| scope.iter().any(|local| local == name));
+    let mut i = 0;
+    while i < tokens.len() {
+        let token = &tokens[i];
+        if token.kind != TokenKind::Name && token.kind != TokenKind::Symbol {
+            i += 1;
+            continue;
+        }
+        match token.text.as_str() {
+            "{" | "(" | "[" => brackets.push(if token.text == "{" { "{" } else { "(" }),
+            "}" | ")" | "]" => {
+                brackets.pop();
+            },
+            "do" | "then" | "repeat" => scopes.push(std::mem::take(&mut pending)),
+            "end" | "elseif" if scopes.len() > 1 => {
+                scopes.pop();
+            },
+            // the condition after `until` still sees the loop body's locals
+            "until" if scopes.len() > 1 => {
+                let body = scopes.pop().unwrap_or_default();
+                scopes.last_mut().expect("the file scope").extend(body);
+            },
+            "else" => {
+                if scopes.len() > 1 {
+                    scopes.pop();
+                }
+                scopes.push(Vec::new());
+            },
+            "goto" => i += 1,
+            "::" => i += 2,
+            "for" => {
+                while i + 1 < tokens.len() && !is(i + 1, "=") && !is(i + 1, "in") {
+                    i += 1;
+                    if tokens[i].kind == TokenKind::Name {
+                        pending.push(tokens[i].text.clone());
+                    }
+                }
+            },
+            "local" if is(i + 1, "function") => {
+                if let Some(name) = tokens.get(i + 2) {
+                    scopes.last_mut().expect("the file scope").push(name.text.clone());
+                }
+                i += 1;
+                continue;
+            },
+            "local" => {
+                // local a <const>, b
+                while let Some(next) = tokens.get(i + 1) {
+                    if next.kind == TokenKind::Name && !KEYWORDS.contains(&next.text.as_str()) {
+                        scopes.last_mut().expect("the file scope").push(next.text.clone());
+                        i += 1;
+                    } else if is(i + 1, "<") && is(i + 3, ">") {
+                        i += 3;
+                    } else if is(i + 1, ",") {
+                        i += 1;
+                    } else {
+                        break;
+                    }
+                }
+            },
+            "function" => {
+                let mut parameters = Vec::new();
+                // function a.b:c(...) names a global only when there is no dot or colon
+                if tokens.get(i + 1).is_some_and(|t| t.kind == TokenKind::Name) {
+                    let name = &tokens[i + 1];
+                    let path = is(i + 2, ".") || is(i + 2, ":");
+                    if !is_local(&scopes, &name.text) {
+                        if path {
+                            result.globals.entry(name.text.clone()).or_insert(name.line);
+                        } else {
+                            result.defined.insert(name.text.clone());
+                        }
+                    }
+                    i += 1;
+                    while is(i + 1, ".") || is(i + 1, ":") {
+                        if is(i + 1, ":") {
+                            parameters.push("self".to_string());
+                        }
+                        i += 2;
+                    }
+                }
+                if is(i + 1, "(") {
+                    i += 1;
+                    while i + 1 < tokens.len() && !is(i + 1, ")") {
+                        i += 1;
+                        if tokens[i].kind == TokenKind::Name {
+                            parameters.push(tokens[i].text.clone());
+                        }
+                    }
+                    i += 1;
+                }
+                scopes.push(parameters);
+            },
+            _ if token.kind == TokenKind::Name && !KEYWORDS.contains(&token.text.as_str()) => {
+                let previous = i.checked_sub(1).and_then(|p| tokens.get(p));
+                let field = previous.is_some_and(|p| p.kind == TokenKind::Symbol && matches!(p.text.as_str(), "." | ":"));
+                let key = brackets.last() == Some(&"{")
+                    && previous.is_some_and(|p| matches!(p.text.as_str(), "{" | "," | ";"))
+                    && is(i + 1, "=");
+                if !field && !key && !is_local(&scopes, &token.text) {
+                    if is(i + 1, "=") {
+                        result.defined.insert(token.text.clone());
+                    } else {
+                        result.globals.entry(token.text.clone()).or_insert(token.line);
+                    }
+                    if token.text == "require" {
+                        let argument = if is(i + 1, "(") { i + 2 } else { i + 1 };
+                        if let Some(module) = tokens.get(argument).filter(|t| t.kind == TokenKind::String) {
+                            result.requires.push((module.text.clone(), module.line));
+                        }
+                    }
+                    if is(i + 1, ".") {
+                        if let Some(member) = tokens.get(i + 2) {
+                            for (table, function, permission) in GATED {
+                                if token.text == table && member.text == function {
+                                    result.gated.push((format!("{}.{}", table, function), token.line, permission));
+                                }
+                            }
+                        }
+                    }
+                }
+            },
+            _ => {},
+        }
+        i += 1;
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use TokenKind::{Name, Number, Symbol};
+
+    #[test]
+    fn tokenize_skips_comments_and_counts_lines() {
+        let code = "local n = 1.5e-3 -- note\nprint('say \"hi\"', [[x\ny]])\n--[==[\n]==] return n ~= ...";
+        let tokens = tokenize(code);
+        let tokens: Vec<(TokenKind, &str, usize)> = tokens.iter()
+            .map(|token| (token.kind, token.text.as_str(), token.line))
+            .collect();
+        assert_eq!(tokens, [
+            (Name, "local", 1), (Name, "n", 1), (Symbol, "=", 1), (Number, "1.5e-3", 1),
+            (Name, "print", 2), (Symbol, "(", 2), (TokenKind::String, "say \"hi\"", 2), (Symbol, ",", 2),
+            (TokenKind::String, "x\ny", 2), (Symbol, ")", 3),
+            (Name, "return", 5), (Name, "n", 5), (Symbol, "~=", 5), (Symbol, "...", 5),
+        ]);
+    }
+
+    #[test]
+    fn scan_separates_globals_from_locals() {
+        let code = r#"local helper = require("lib.helper")
+require "other"
+function setup(a, b)
+    local total = a + b + helper.value
+    for i, v in ipairs(items) do
+        total = total + i + v
+    end
+    score = total
+    return os.execute("ls")
+end
+local function tick(dt) return dt * speed end
+local t = { width = 1, [key] = 2 }
+function t.method(self) return self end
+function Game:update() return self.x end
+repeat local done = true until done
+print(io.popen, missing.field)
+"#;
+        let scan = scan(&tokenize(code));
+        assert_eq!(scan.defined, BTreeSet::from(["score".to_string(), "setup".to_string()]));
+        let globals: Vec<(&str, usize)> = scan.globals.iter().map(|(name, line)| (name.as_str(), *line)).collect();
+        assert_eq!(globals, [
+            ("Game", 14), ("io", 16), ("ipairs", 5), ("items", 5), ("key", 12), ("missing", 16),
+            ("os", 9), ("print", 16), ("require", 1), ("speed", 11),
+        ]);
+        assert_eq!(scan.requires, [("lib.helper".to_string(), 1), ("other".to_string(), 2)]);
+        assert_eq!(scan.gated, [
+            ("os.execute".to_string(), 9, Permission::Process),
+            ("io.popen".to_string(), 16, Permission::Process),
+        ]);
+    }
+}
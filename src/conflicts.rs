@@ -0,0 +1,179 @@
+use crate::load_order;
+use crate::manifest::Manifest;
+use crate::modules;
+use crate::signing::PACKAGE_SIGNATURE;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Files every plugin has for itself; they are never shared with other plugins.
+const PLUGIN_FILES: [&str; 6] = ["index.xml", "index.lua", "package.xml", PACKAGE_SIGNATURE, "readme", "changelog"];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Kind {
+    /// A Lua module, named the way `require` names it (`utiletc/json.lua` -> `utiletc.json`).
+    Module,
+    /// Any other file, by its path inside the plugin.
+    Asset,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Provider {
+    pub plugin: String,
+    pub file: PathBuf,
+}
+
+/// Several plugins providing the same module or asset.
+#[derive(Clone, Debug)]
+pub struct Conflict {
+    pub kind: Kind,
+    pub name: String,
+    /// In load order; the last one wins. A plugin always loads after its dependencies,
+    /// so this is also the module `require` finds first.
+    pub providers: Vec<Provider>,
+}
+
+impl Conflict {
+    pub fn winner(&self) -> &Provider {
+        self.providers.last().expect("a conflict has providers")
+    }
+}
+
+/// What a plugin folder provides: module or asset name -> file.
+pub fn provided(dir: &Path) -> Vec<(Kind, String, PathBuf)> {
+    let logo = Manifest::load(dir).ok().and_then(|manifest| manifest.logo);
+    let mut files = Vec::new();
+    collect(dir, dir, &mut files);
+    files.into_iter()
+        .filter(|relative| !is_plugin_file(relative, logo.as_deref()))
+        .map(|relative| {
+            let name = relative.to_string_lossy().replace('\\', "/");
+            let file = dir.join(&relative);
+            match name.strip_suffix(".lua") {
+                Some(module) => {
+                    let module = module.strip_suffix("/init").unwrap_or(module);
+                    (Kind::Module, module.replace('/', "."), file)
+                },
+                None => (Kind::Asset, name, file),
+            }
+        })
+        .collect()
+}
+
+/// Whether `relative`, a path inside a plugin, is one of the plugin's own files: its
+/// manifest, entrypoint, readme, changelog, license or logo.
+pub fn is_plugin_file(relative: &Path, logo: Option<&Path>) -> bool {
+    if Some(relative) == logo {
+        return true;
+    }
+    let name = relative.to_string_lossy().replace('\\', "/");
+    let stem = relative.file_stem().map(|s| s.to_string_lossy().to_lowercase()).unwrap_or_default();
+    relative.components().count() == 1
+        && (PLUGIN_FILES.contains(&name.as_str()) || PLUGIN_FILES.contains(&stem.as_str()) || stem == "license")
+}
+
+pub(crate) fn collect(root: &Path, dir: &Path, files: &mut Vec<PathBuf>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        if entry.file_name().to_string_lossy().starts_with('.') {
+            continue;
+        }
+        let path = entry.path();
+        if path.is_dir() {
+            collect(root, &path, files);
+        } else if let Ok(relative) = path.strip_prefix(root) {
+            files.push(relative.to_path_buf());
+        }
+    }
+}
+
+/// Finds modules and assets provided by more than one enabled plugin in `config_dir`.
+/// Every plugin sees every asset, so any two providers of an asset conflict. A module
+/// is only looked up along a plugin's search path (itself, then its dependencies), so
+/// providers only conflict when some plugin has more than one of them on its path.
+pub fn detect(config_dir: &Path) -> Vec<Conflict> {
+    let plugins = load_order::enabled_plugins(config_dir);
+    let mut providers: BTreeMap<(Kind, String), Vec<Provider>> = BTreeMap::new();
+    for (plugin, dir) in &plugins {
+        for (kind, name, file) in provided(dir) {
+            let providers = providers.entry((kind, name)).or_default();
+            // `a.lua` and `a/init.lua` in one plugin are the same module to `require`
+            if !providers.iter().any(|provider| provider.plugin == *plugin) {
+                providers.push(Provider { plugin: plugin.clone(), file });
+            }
+        }
+    }
+    let search_paths: Vec<BTreeSet<String>> = plugins.iter()
+        .map(|(plugin, dir)| modules::search_path(config_dir, plugin, dir).into_iter().map(|(name, _)| name).collect())
+        .collect();
+    let mut conflicts = Vec::new();
+    for ((kind, name), providers) in providers {
+        if providers.len() < 2 {
+            continue;
+        }
+        if kind == Kind::Asset {
+            conflicts.push(Conflict { kind, name, providers });
+            continue;
+        }
+        // the providers each plugin can reach, keeping only the largest distinct groups
+        let mut groups: Vec<Vec<Provider>> = Vec::new();
+        for search_path in &search_paths {
+            let group: Vec<Provider> = providers.iter()
+                .filter(|provider| search_path.contains(&provider.plugin))
+                .cloned()
+                .collect();
+            if group.len() > 1 && !groups.iter().any(|other| group.iter().all(|provider| other.contains(provider))) {
+                groups.retain(|other| !other.iter().all(|provider| group.contains(provider)));
+                groups.push(group);
+            }
+        }
+        conflicts.extend(groups.into_iter().map(|providers| Conflict { kind, name: name.clone(), providers }));
+    }
+    conflicts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_dir::TestDir;
+
+    fn plugin(config: &TestDir, name: &str, dependencies: &[&str], files: &[&str]) {
+        let depends: String = dependencies.iter().map(|d| format!("<depends>{}</depends>", d)).collect();
+        config.write(&format!("plugins/{}/index.xml", name), &format!("<plugin><name>{}</name>{}</plugin>", name, depends));
+        for file in files {
+            config.write(&format!("plugins/{}/{}", name, file), "");
+        }
+    }
+
+    fn summary(conflicts: &[Conflict]) -> Vec<String> {
+        conflicts.iter()
+            .map(|conflict| {
+                let providers: Vec<_> = conflict.providers.iter().map(|p| p.plugin.as_str()).collect();
+                format!("{:?} {}: {}", conflict.kind, conflict.name, providers.join(" "))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn modules_conflict_only_on_a_shared_search_path() {
+        let config = TestDir::new("conflicts");
+        plugin(&config, "a", &[], &["util.lua", "logo.png"]);
+        plugin(&config, "b", &[], &["util/init.lua", "logo.png"]);
+        plugin(&config, "c", &["a"], &[]);
+        assert_eq!(summary(&detect(config.path())), ["Asset logo.png: a b"]);
+
+        plugin(&config, "d", &["a", "b"], &[]);
+        assert_eq!(summary(&detect(config.path())), ["Module util: a b", "Asset logo.png: a b"]);
+    }
+
+    #[test]
+    fn a_plugin_overriding_its_dependency_conflicts() {
+        let config = TestDir::new("conflicts");
+        plugin(&config, "base", &[], &["util.lua", "index.lua"]);
+        plugin(&config, "mod", &["base"], &["util.lua", "index.lua"]);
+        // the dependency loads first, so the plugin's own module wins
+        assert_eq!(summary(&detect(config.path())), ["Module util: base mod"]);
+    }
+}
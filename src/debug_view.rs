@@ -0,0 +1,108 @@
+use eframe::egui;
+use mlua::Lua;
+use orbit::vfs::{Source, Vfs};
+use std::collections::VecDeque;
+use crate::{budget, events};
+
+const KEPT_ERRORS: usize = 20;
+/// Files listed at once in the virtual filesystem section; the filter narrows it down.
+const SHOWN_FILES: usize = 200;
+
+/// Developer window, toggled with F12, showing who listens to which event, the most
+/// recent errors raised by event handlers, the memory each plugin keeps and which
+/// layer each game file comes from.
+#[derive(Default)]
+pub struct DebugView {
+    pub show: bool,
+    errors: VecDeque<String>,
+    file_filter: String,
+}
+
+impl DebugView {
+    pub fn record_errors(&mut self, errors: &[String]) {
+        for error in errors {
+            if self.errors.len() == KEPT_ERRORS {
+                self.errors.pop_front();
+            }
+            self.errors.push_back(error.clone());
+        }
+    }
+
+    pub fn draw(&mut self, ctx: &egui::Context, lua: &Lua) {
+        if ctx.input(|i| i.key_pressed(egui::Key::F12)) {
+            self.show = !self.show;
+        }
+        if !self.show {
+            return;
+        }
+        let file_filter = &mut self.file_filter;
+        egui::Window::new("Debug")
+            .open(&mut self.show)
+            .default_size([400.0, 300.0])
+            .show(ctx, |ui| {
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    egui::CollapsingHeader::new("Event subscribers").default_open(true).show(ui, |ui| {
+                        let subscribers = events::subscribers(lua);
+                        if subscribers.is_empty() {
+                            ui.label("No plugin listens to any event.");
+                        }
+                        for (event, handlers) in subscribers {
+                            ui.label(egui::RichText::new(event).strong());
+                            for (plugin, id) in handlers {
+                                ui.label(format!("    {} (handler {})", plugin, id));
+                            }
+                        }
+                    });
+                    egui::CollapsingHeader::new(format!("Handler errors ({})", self.errors.len())).show(ui, |ui| {
+                        for error in self.errors.iter().rev() {
+                            ui.colored_label(egui::Color32::RED, error);
+                        }
+                    });
+                    egui::CollapsingHeader::new("Memory kept by plugins").show(ui, |ui| {
+                        for (plugin, bytes) in budget::retained(lua) {
+                            ui.label(format!("{}: {:.1} MB", plugin, bytes as f64 / 1048576.0));
+                        }
+                    });
+                    egui::CollapsingHeader::new("Virtual filesystem").show(ui, |ui| {
+                        match lua.app_data_ref::<Vfs>() {
+                            Some(vfs) => draw_vfs(ui, &vfs, file_filter),
+                            None => { ui.label("Not mounted."); },
+                        }
+                    });
+                });
+            });
+    }
+}
+
+/// Layers bottom to top, then every file with the layer it is read from and the
+/// layers it overrides.
+fn draw_vfs(ui: &mut egui::Ui, vfs: &Vfs, filter: &mut String) {
+    for (i, layer) in vfs.layers.iter().enumerate() {
+        let kind = match layer.source {
+            Source::Folder(_) => "folder",
+            Source::Archive(_) => "archive",
+        };
+        ui.label(format!("{}. {} ({} {})", i + 1, layer.plugin, kind, layer.source.path().display()));
+    }
+    ui.horizontal(|ui| {
+        ui.label("Filter:");
+        ui.text_edit_singleline(filter);
+    });
+    let filter = filter.to_lowercase();
+    let mut files = vfs.files().filter(|(path, _)| path.to_lowercase().contains(&filter));
+    for (path, layers) in files.by_ref().take(SHOWN_FILES) {
+        let Some((winner, overridden)) = layers.split_last() else {
+            continue;
+        };
+        let mut line = format!("{}  <-  {}", path, winner.plugin);
+        if !overridden.is_empty() {
+            let overridden: Vec<&str> = overridden.iter().map(|layer| layer.plugin.as_str()).collect();
+            line.push_str(&format!(" (overrides {})", overridden.join(", ")));
+        }
+        ui.label(line);
+    }
+    let more = files.count();
+    if more > 0 {
+        ui.weak(format!("{} more files; narrow the filter to see them", more));
+    }
+}
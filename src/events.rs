@@ -0,0 +1,248 @@
+use mlua::prelude::*;
+use std::collections::BTreeMap;
+use crate::budget;
+
+/// Sender name used for events raised by Orbit itself.
+pub const HOST: &str = "orbit";
+/// `{ screen = "menu" | "game" | "plugin", plugin = <name of the plugin showing its screen> }`
+pub const SCREEN_CHANGED: &str = "screen_changed";
+/// `{ plugin = <name> }`
+pub const PLUGIN_LOADED: &str = "plugin_loaded";
+/// `{ width = <points>, height = <points> }`
+pub const WINDOW_RESIZED: &str = "window_resized";
+/// `{ key = <key of the setting changed on the Settings screen> }`
+pub const SETTINGS_CHANGED: &str = "settings_changed";
+
+/// One `orbit.events.on` subscription.
+struct Handler {
+    id: u64,
+    plugin: String,
+    function: LuaRegistryKey,
+}
+
+/// Subscriptions of the inter-plugin event bus, kept in the Lua state's app data.
+/// Handlers run in plugin load order, and a failing handler does not stop the others;
+/// its error is kept until the host picks it up with [`take_errors`].
+#[derive(Default)]
+pub struct EventBus {
+    next_id: u64,
+    handlers: BTreeMap<String, Vec<Handler>>,
+    /// Plugin names in load order.
+    order: Vec<String>,
+    errors: Vec<String>,
+}
+
+fn bus(lua: &Lua) -> LuaResult<mlua::AppDataRefMut<'_, EventBus>> {
+    lua.app_data_mut::<EventBus>()
+        .ok_or_else(|| LuaError::RuntimeError("the event bus is not available".to_string()))
+}
+
+pub fn set_load_order(lua: &Lua, order: Vec<String>) {
+    if let Ok(mut bus) = bus(lua) {
+        bus.order = order;
+    }
+}
+
+pub fn subscribe(lua: &Lua, plugin: &str, event: &str, function: LuaFunction) -> LuaResult<u64> {
+    let function = lua.create_registry_value(function)?;
+    let mut bus = bus(lua)?;
+    bus.next_id += 1;
+    let id = bus.next_id;
+    bus.handlers.entry(event.to_string()).or_default().push(Handler { id, plugin: plugin.to_string(), function });
+    Ok(id)
+}
+
+/// Removes `plugin`'s handler for `event` given by the id `on` returned or by the
+/// function itself. Returns whether one was removed.
+pub fn unsubscribe(lua: &Lua, plugin: &str, event: &str, handler: LuaValue) -> LuaResult<bool> {
+    let mut bus = bus(lua)?;
+    let Some(handlers) = bus.handlers.get_mut(event) else {
+        return Ok(false);
+    };
+    let position = handlers.iter().position(|h| {
+        h.plugin == plugin && match &handler {
+            LuaValue::Integer(id) => h.id == *id as u64,
+            LuaValue::Number(id) => h.id as f64 == *id,
+            LuaValue::Function(function) => lua.registry_value::<LuaFunction>(&h.function).is_ok_and(|f| f == *function),
+            _ => false,
+        }
+    });
+    let Some(position) = position else {
+        return Ok(false);
+    };
+    let removed = handlers.remove(position);
+    if handlers.is_empty() {
+        bus.handlers.remove(event);
+    }
+    drop(bus);
+    lua.remove_registry_value(removed.function)?;
+    Ok(true)
+}
+
+/// Drops every handler of a plugin, before its code is loaded again.
+pub fn forget_plugin(lua: &Lua, plugin: &str) {
+    let Ok(mut bus) = bus(lua) else {
+        return;
+    };
+    let mut removed = Vec::new();
+    for handlers in bus.handlers.values_mut() {
+        let (gone, kept) = std::mem::take(handlers).into_iter().partition(|h| h.plugin == plugin);
+        *handlers = kept;
+        removed.extend(gone);
+    }
+    bus.handlers.retain(|_, handlers| !handlers.is_empty());
+    drop(bus);
+    for handler in removed {
+        let _ = lua.remove_registry_value(handler.function);
+    }
+}
+
+/// Calls every handler of `event` with `(payload, sender)`.
+pub fn emit<'lua>(lua: &'lua Lua, sender: &str, event: &str, payload: LuaValue<'lua>) -> LuaResult<()> {
+    // collect first: handlers may subscribe, unsubscribe or emit themselves
+    let mut calls: Vec<(usize, u64, String, LuaFunction)> = {
+        let bus = bus(lua)?;
+        let Some(handlers) = bus.handlers.get(event) else {
+            return Ok(());
+        };
+        handlers.iter()
+            .filter_map(|h| {
+                let position = bus.order.iter().position(|plugin| *plugin == h.plugin).unwrap_or(usize::MAX);
+                let function = lua.registry_value::<LuaFunction>(&h.function).ok()?;
+                Some((position, h.id, h.plugin.clone(), function))
+            })
+            .collect()
+    };
+    calls.sort_by_key(|(position, id, _, _)| (*position, *id));
+    let mut errors = Vec::new();
+    for (_, _, plugin, function) in calls {
+        if let Err(e) = budget::run(lua, &plugin, || function.call::<_, ()>((payload.clone(), sender))) {
+            errors.push(format!("{}'s handler for '{}' failed: {}", plugin, event, e));
+        }
+    }
+    bus(lua)?.errors.extend(errors);
+    Ok(())
+}
+
+/// Emits an event from the host with a table payload built from `fields`.
+pub fn emit_host<'lua, V: IntoLua<'lua>>(lua: &'lua Lua, event: &str, fields: Vec<(&str, V)>) -> LuaResult<()> {
+    let payload = lua.create_table_from(fields)?;
+    emit(lua, HOST, event, LuaValue::Table(payload))
+}
+
+/// Keeps an error about delivering an event for the host to show with the handler errors.
+pub fn report_error(lua: &Lua, error: String) {
+    if let Ok(mut bus) = bus(lua) {
+        bus.errors.push(error);
+    }
+}
+
+pub fn take_errors(lua: &Lua) -> Vec<String> {
+    bus(lua).map(|mut bus| std::mem::take(&mut bus.errors)).unwrap_or_default()
+}
+
+/// Event name -> (plugin, handler id) in delivery order, for the debug view.
+pub fn subscribers(lua: &Lua) -> Vec<(String, Vec<(String, u64)>)> {
+    let Ok(bus) = bus(lua) else {
+        return Vec::new();
+    };
+    bus.handlers.iter()
+        .map(|(event, handlers)| {
+            let mut subscribers: Vec<(String, u64)> = handlers.iter().map(|h| (h.plugin.clone(), h.id)).collect();
+            subscribers.sort_by_key(|(plugin, id)| (bus.order.iter().position(|p| p == plugin).unwrap_or(usize::MAX), *id));
+            (event.clone(), subscribers)
+        })
+        .collect()
+}
+
+/// Builds the `orbit.events` table for a plugin.
+pub fn create_api<'lua>(lua: &'lua Lua, plugin: &str) -> LuaResult<LuaTable<'lua>> {
+    let events = lua.create_table()?;
+    let name = plugin.to_string();
+    events.set("on", lua.create_function(move |lua, (event, function): (String, LuaFunction)| {
+        subscribe(lua, &name, &event, function)
+    })?)?;
+    let name = plugin.to_string();
+    events.set("off", lua.create_function(move |lua, (event, handler): (String, LuaValue)| {
+        unsubscribe(lua, &name, &event, handler)
+    })?)?;
+    let name = plugin.to_string();
+    events.set("emit", lua.create_function(move |lua, (event, payload): (String, LuaValue)| {
+        emit(lua, &name, &event, payload)
+    })?)?;
+    Ok(events)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lua() -> Lua {
+        let lua = Lua::new();
+        budget::install(&lua);
+        lua.set_app_data(EventBus::default());
+        lua.globals().set("calls", lua.create_table().unwrap()).unwrap();
+        lua
+    }
+
+    /// A handler that appends `label` and what it received to the global `calls`.
+    fn on(lua: &Lua, plugin: &str, event: &str, label: &str) -> u64 {
+        let code = format!("return function(payload, sender) table.insert(calls, '{}:' .. tostring(payload) .. ':' .. sender) end", label);
+        subscribe(lua, plugin, event, lua.load(code).eval().unwrap()).unwrap()
+    }
+
+    fn calls(lua: &Lua) -> Vec<String> {
+        lua.globals().get::<_, Vec<String>>("calls").unwrap()
+    }
+
+    #[test]
+    fn handlers_run_in_load_order() {
+        let lua = lua();
+        set_load_order(&lua, vec!["game".to_string(), "a".to_string(), "b".to_string()]);
+        on(&lua, "b", "tick", "b1");
+        on(&lua, "unknown", "tick", "unknown");
+        on(&lua, "a", "tick", "a");
+        on(&lua, "game", "tick", "game");
+        on(&lua, "b", "tick", "b2");
+        on(&lua, "a", "other", "other");
+        emit(&lua, "a", "tick", LuaValue::Integer(1)).unwrap();
+        assert_eq!(calls(&lua), ["game:1:a", "a:1:a", "b1:1:a", "b2:1:a", "unknown:1:a"]);
+        let (event, subscribers) = subscribers(&lua).remove(1);
+        assert_eq!(event, "tick");
+        let order: Vec<_> = subscribers.into_iter().map(|(plugin, _)| plugin).collect();
+        assert_eq!(order, ["game", "a", "b", "b", "unknown"]);
+    }
+
+    #[test]
+    fn failing_handlers_do_not_stop_the_others() {
+        let lua = lua();
+        set_load_order(&lua, vec!["a".to_string(), "b".to_string()]);
+        let failing = lua.load("return function() error('boom') end").eval().unwrap();
+        subscribe(&lua, "a", "tick", failing).unwrap();
+        on(&lua, "b", "tick", "b");
+        emit(&lua, HOST, "tick", LuaValue::Boolean(true)).unwrap();
+        assert_eq!(calls(&lua), ["b:true:orbit"]);
+        let errors = take_errors(&lua);
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].starts_with("a's handler for 'tick' failed"), "{}", errors[0]);
+        assert!(errors[0].contains("boom"), "{}", errors[0]);
+        assert!(take_errors(&lua).is_empty());
+    }
+
+    #[test]
+    fn handlers_can_be_removed() {
+        let lua = lua();
+        let id = on(&lua, "a", "tick", "a");
+        on(&lua, "b", "tick", "b");
+        on(&lua, "b", "other", "other");
+        // only the plugin that subscribed can remove its handler
+        assert!(!unsubscribe(&lua, "b", "tick", LuaValue::Integer(id as i64)).unwrap());
+        assert!(unsubscribe(&lua, "a", "tick", LuaValue::Integer(id as i64)).unwrap());
+        assert!(!unsubscribe(&lua, "a", "tick", LuaValue::Integer(id as i64)).unwrap());
+        forget_plugin(&lua, "b");
+        emit(&lua, HOST, "tick", LuaValue::Nil).unwrap();
+        emit(&lua, HOST, "other", LuaValue::Nil).unwrap();
+        assert!(calls(&lua).is_empty());
+        assert!(subscribers(&lua).is_empty());
+    }
+}
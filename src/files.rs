@@ -0,0 +1,106 @@
+use mlua::prelude::*;
+use std::fs;
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+use crate::sandbox::PluginContext;
+
+fn failed(what: &str, path: &Path, error: std::io::Error) -> LuaError {
+    LuaError::RuntimeError(format!("Failed to {} {:?}: {}", what, path, error))
+}
+
+fn stat<'lua>(lua: &'lua Lua, path: &Path) -> LuaResult<Option<LuaTable<'lua>>> {
+    let Ok(metadata) = fs::metadata(path) else {
+        return Ok(None);
+    };
+    let kind = if metadata.is_dir() {
+        "directory"
+    } else if metadata.is_file() {
+        "file"
+    } else {
+        "other"
+    };
+    let modified = metadata.modified().ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map(|since| since.as_secs());
+    let stat = lua.create_table()?;
+    stat.set("type", kind)?;
+    stat.set("size", metadata.len())?;
+    stat.set("modified", modified)?;
+    stat.set("readonly", metadata.permissions().readonly())?;
+    Ok(Some(stat))
+}
+
+/// Builds the `orbit.fs` table for a plugin. Paths go through
+/// [`PluginContext::resolve_path`], so they are relative to the plugin directory and
+/// stay inside it or `orbit.data_dir` unless the filesystem permission was granted.
+/// Failures raise errors naming the path.
+pub fn create_api<'lua>(lua: &'lua Lua, plugin: &PluginContext) -> LuaResult<LuaTable<'lua>> {
+    let files = lua.create_table()?;
+
+    // list(path) -> sorted entry names
+    let ctx = plugin.clone();
+    files.set("list", lua.create_function(move |_, path: String| {
+        let path = ctx.resolve_path(&path)?;
+        let mut names: Vec<String> = fs::read_dir(&path)
+            .map_err(|e| failed("list", &path, e))?
+            .flatten()
+            .map(|entry| entry.file_name().to_string_lossy().to_string())
+            .collect();
+        names.sort();
+        Ok(names)
+    })?)?;
+
+    // stat(path) -> { type = "file" | "directory" | "other", size, modified, readonly }, or nil if missing
+    let ctx = plugin.clone();
+    files.set("stat", lua.create_function(move |lua, path: String| {
+        stat(lua, &ctx.resolve_path(&path)?)
+    })?)?;
+
+    let ctx = plugin.clone();
+    files.set("read_text", lua.create_function(move |_, path: String| {
+        let path = ctx.resolve_path(&path)?;
+        fs::read_to_string(&path).map_err(|e| failed("read", &path, e))
+    })?)?;
+    let ctx = plugin.clone();
+    files.set("read_bytes", lua.create_function(move |lua, path: String| {
+        let path = ctx.resolve_path(&path)?;
+        lua.create_string(fs::read(&path).map_err(|e| failed("read", &path, e))?)
+    })?)?;
+    let ctx = plugin.clone();
+    files.set("write_text", lua.create_function(move |_, (path, text): (String, String)| {
+        let path = ctx.resolve_path(&path)?;
+        fs::write(&path, text).map_err(|e| failed("write", &path, e))
+    })?)?;
+    let ctx = plugin.clone();
+    files.set("write_bytes", lua.create_function(move |_, (path, bytes): (String, LuaString)| {
+        let path = ctx.resolve_path(&path)?;
+        fs::write(&path, bytes.as_bytes()).map_err(|e| failed("write", &path, e))
+    })?)?;
+
+    // mkdir(path) also creates missing parents
+    let ctx = plugin.clone();
+    files.set("mkdir", lua.create_function(move |_, path: String| {
+        let path = ctx.resolve_path(&path)?;
+        fs::create_dir_all(&path).map_err(|e| failed("create", &path, e))
+    })?)?;
+
+    // remove(path, recursive): directories must be empty unless recursive is true
+    let ctx = plugin.clone();
+    files.set("remove", lua.create_function(move |_, (path, recursive): (String, Option<bool>)| {
+        let path = ctx.resolve_path(&path)?;
+        let result = match fs::symlink_metadata(&path) {
+            Ok(metadata) if metadata.is_dir() && recursive.unwrap_or(false) => fs::remove_dir_all(&path),
+            Ok(metadata) if metadata.is_dir() => fs::remove_dir(&path),
+            _ => fs::remove_file(&path),
+        };
+        result.map_err(|e| failed("remove", &path, e))
+    })?)?;
+
+    let ctx = plugin.clone();
+    files.set("rename", lua.create_function(move |_, (from, to): (String, String)| {
+        let from = ctx.resolve_path(&from)?;
+        let to = ctx.resolve_path(&to)?;
+        fs::rename(&from, &to).map_err(|e| failed("rename", &from, e))
+    })?)?;
+    Ok(files)
+}
@@ -1,4 +1,22 @@
 pub mod popup;
+pub mod conflicts;
+pub mod load_order;
+pub mod manifest;
+pub mod modules;
+pub mod package;
+pub mod permissions;
+pub mod plugin_settings;
+pub mod plugin_state;
+pub mod profiles;
+pub mod registry;
+pub mod settings;
+pub mod signing;
+#[cfg(test)]
+mod test_dir;
+pub mod trash;
+pub mod updater;
+pub mod vfs;
+pub mod watcher;
 use popup::PopupMessage;
 use std::fs;
 use std::path::PathBuf;
@@ -140,83 +158,3 @@ pub fn jumpscare(message: &str, duration_ms: u32) -> Result<(), String> {
 
     Ok(())
 }
-
-pub fn update_plugins() -> Result<(), String> {
-    let config_path = std::env::var("CONFIG")
-        .map_err(|e| format!("Failed to get CONFIG environment variable: {}", e))?;
-    let plugins_dir = PathBuf::from(config_path).join("plugins");
-
-    if let Ok(entries) = fs::read_dir(&plugins_dir) {
-        for entry in entries.flatten() {
-            if !entry.path().is_dir() {
-                continue;
-            }
-
-            let index_path = entry.path().join("index.xml");
-            if !index_path.exists() {
-                continue;
-            }
-            let xml_content = fs::read_to_string(&index_path)
-                .map_err(|e| format!("Failed to read index.xml: {}", e))?;
-            
-            let mut reader = Reader::from_str(&xml_content);
-            reader.trim_text(true);
-            
-            let mut buf = Vec::new();
-            let mut current_element = String::new();
-            let mut update_url = None;
-            loop {
-                match reader.read_event_into(&mut buf) {
-                    Ok(Event::Start(ref e)) => {
-                        current_element = String::from_utf8_lossy(e.name().as_ref()).to_string();
-                    },
-                    Ok(Event::Text(ref e)) => {
-                        if current_element == "update" {
-                            update_url = Some(e.unescape().unwrap_or_default().to_string());
-                        }
-                    },
-                    Ok(Event::Eof) => break,
-                    Err(_) => break,
-                    _ => {}
-                }
-            }
-            if let Some(url) = update_url {
-                let status = Command::new("git")
-                    .current_dir(&entry.path())
-                    .args(["remote", "update"])
-                    .output()
-                    .map_err(|e| format!("Failed to check for updates: {}", e))?;
-                if !status.status.success() {
-                    let plugin_name = entry.path().file_name()
-                        .ok_or("Invalid plugin path")?
-                        .to_string_lossy()
-                        .to_string();
-                    
-                    let temp_dir = std::env::temp_dir().join(&plugin_name);
-                    Command::new("git")
-                        .args(["clone", &url, temp_dir.to_str().unwrap()])
-                        .output()
-                        .map_err(|e| format!("Failed to clone repository: {}", e))?;
-                    fs::remove_dir_all(&entry.path())
-                        .map_err(|e| format!("Failed to remove old plugin: {}", e))?;
-                    fs::rename(&temp_dir, &entry.path())
-                        .map_err(|e| format!("Failed to move new plugin version: {}", e))?;
-                } else {
-                    let status = Command::new("git")
-                        .current_dir(&entry.path())
-                        .args(["status", "-uno"])
-                        .output()
-                        .map_err(|e| format!("Failed to check git status: {}", e))?;
-                    if !String::from_utf8_lossy(&status.stdout).contains("Your branch is up to date") {
-                        Command::new("git")
-                            .current_dir(&entry.path())
-                            .args(["pull", "origin", "main"])
-                            .output()
-                            .map_err(|e| format!("Failed to pull updates: {}", e))?;
-                    }
-                }
-            }
-        }
-    }
-    Ok(())
-}
@@ -39,6 +39,9 @@ function SaveSystem.save(saveName, data)
     local serialized = "return " .. serialize(data)
     file:write(serialized)
     file:close()
+    if orbit and orbit.events then
+        orbit.events.emit("game_saved", { name = saveName })
+    end
     return true
 end
 function SaveSystem.load(saveName)
@@ -0,0 +1,164 @@
+use crate::manifest::Manifest;
+use crate::plugin_state::PluginStates;
+use quick_xml::escape::escape;
+use quick_xml::events::Event;
+use quick_xml::reader::Reader;
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Orders plugins so each one comes after the plugins it depends on. Plugins whose
+/// dependencies are all placed keep their relative order from `plugins`; dependencies
+/// that are not installed are ignored, and plugins caught in a cycle go last.
+pub fn resolve(plugins: &[(String, Vec<String>)]) -> Vec<String> {
+    let installed: BTreeSet<&str> = plugins.iter().map(|(name, _)| name.as_str()).collect();
+    let mut placed: BTreeSet<&str> = BTreeSet::new();
+    let mut order = Vec::with_capacity(plugins.len());
+    while order.len() < plugins.len() {
+        let next = plugins.iter().find(|(name, dependencies)| {
+            !placed.contains(name.as_str())
+                && dependencies.iter()
+                    .all(|dependency| placed.contains(dependency.as_str()) || !installed.contains(dependency.as_str()) || dependency == name)
+        });
+        match next {
+            Some((name, _)) => {
+                placed.insert(name);
+                order.push(name.clone());
+            },
+            None => {
+                for (name, _) in plugins {
+                    if placed.insert(name) {
+                        order.push(name.clone());
+                    }
+                }
+            },
+        }
+    }
+    order
+}
+
+/// The order the user put plugins in, persisted in `CONFIG/load_order.xml` as
+/// `<load_order><plugin>name</plugin>...</load_order>`. Plugins later in the order
+/// load later and win when two plugins provide the same module or asset.
+#[derive(Default)]
+pub struct LoadOrder {
+    path: PathBuf,
+    pub order: Vec<String>,
+}
+
+impl LoadOrder {
+    pub fn load(config_dir: &Path) -> LoadOrder {
+        let path = config_dir.join("load_order.xml");
+        let mut order = Vec::new();
+        if let Ok(xml_content) = fs::read_to_string(&path) {
+            let mut reader = Reader::from_str(&xml_content);
+            reader.trim_text(true);
+
+            let mut buf = Vec::new();
+            let mut in_plugin = false;
+            loop {
+                match reader.read_event_into(&mut buf) {
+                    Ok(Event::Start(ref e)) => in_plugin = e.name().as_ref() == b"plugin",
+                    Ok(Event::Text(ref e)) if in_plugin => {
+                        order.push(e.unescape().unwrap_or_default().trim().to_string());
+                    },
+                    Ok(Event::End(_)) => in_plugin = false,
+                    Ok(Event::Eof) => break,
+                    Err(_) => break,
+                    _ => {}
+                }
+                buf.clear();
+            }
+        }
+        LoadOrder { path, order }
+    }
+
+    /// Orders `plugins` (name and dependency names) the way they will load: the user's
+    /// order first, plugins it does not mention after it by name, then dependencies
+    /// moved in front of the plugins that need them.
+    pub fn arrange(&self, plugins: &[(String, Vec<String>)]) -> Vec<String> {
+        let mut sorted = plugins.to_vec();
+        sorted.sort_by_key(|(name, _)| {
+            let position = self.order.iter().position(|listed| listed == name).unwrap_or(usize::MAX);
+            (position, name.clone())
+        });
+        resolve(&sorted)
+    }
+
+    /// Stores `order` as the user's order.
+    pub fn set(&mut self, order: Vec<String>) -> Result<(), String> {
+        self.order = order;
+        self.save()
+    }
+
+    fn save(&self) -> Result<(), String> {
+        let mut xml = String::from("<load_order>\n");
+        for plugin in &self.order {
+            xml.push_str(&format!("    <plugin>{}</plugin>\n", escape(plugin)));
+        }
+        xml.push_str("</load_order>\n");
+        fs::write(&self.path, xml)
+            .map_err(|e| format!("Failed to save the load order to {:?}: {}", self.path, e))
+    }
+}
+
+/// Enabled plugins in `CONFIG/plugins` as name and folder, in the order they load.
+pub fn enabled_plugins(config_dir: &Path) -> Vec<(String, PathBuf)> {
+    let plugins_dir = config_dir.join("plugins");
+    let states = PluginStates::load(config_dir);
+    let mut plugins = Vec::new();
+    if let Ok(entries) = fs::read_dir(&plugins_dir) {
+        for entry in entries.flatten() {
+            let name = entry.file_name().to_string_lossy().to_string();
+            if name.starts_with('.') || !entry.path().is_dir() || !states.is_enabled(&name) {
+                continue;
+            }
+            let dependencies = Manifest::load(&entry.path())
+                .map(|manifest| manifest.dependencies.into_iter().map(|dependency| dependency.name).collect())
+                .unwrap_or_default();
+            plugins.push((name, dependencies));
+        }
+    }
+    LoadOrder::load(config_dir).arrange(&plugins)
+        .into_iter()
+        .map(|name| {
+            let dir = plugins_dir.join(&name);
+            (name, dir)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn plugins(list: &[(&str, &[&str])]) -> Vec<(String, Vec<String>)> {
+        list.iter()
+            .map(|(name, dependencies)| (name.to_string(), dependencies.iter().map(|d| d.to_string()).collect()))
+            .collect()
+    }
+
+    #[test]
+    fn dependencies_come_first() {
+        let order = resolve(&plugins(&[("a", &["b"]), ("b", &["c"]), ("c", &[]), ("d", &[])]));
+        assert_eq!(order, ["c", "b", "a", "d"]);
+    }
+
+    #[test]
+    fn independent_plugins_keep_their_order() {
+        let order = resolve(&plugins(&[("z", &[]), ("a", &[]), ("m", &[])]));
+        assert_eq!(order, ["z", "a", "m"]);
+    }
+
+    #[test]
+    fn missing_and_own_dependencies_are_ignored() {
+        let order = resolve(&plugins(&[("a", &["missing", "a"]), ("b", &[])]));
+        assert_eq!(order, ["a", "b"]);
+    }
+
+    #[test]
+    fn cycles_go_last() {
+        let order = resolve(&plugins(&[("a", &["b"]), ("b", &["a"]), ("c", &[]), ("d", &["c"])]));
+        assert_eq!(order, ["c", "d", "a", "b"]);
+    }
+}
@@ -1,6 +1,15 @@
 use mlua::prelude::*;
 use eframe::egui;
 use std::sync::mpsc;
+use orbit::permissions::Permission;
+use orbit::plugin_settings::SettingValue;
+use orbit::settings::Settings;
+use orbit::vfs::Vfs;
+use crate::assets::{self, AssetCache, LuaImage};
+use crate::events;
+use crate::files;
+use crate::menu;
+use crate::sandbox::PluginContext;
 
 pub struct LuaError(pub String);
 
@@ -20,13 +29,45 @@ impl LuaUserData for LuaUi {
             Ok(clicked)
         });
 
-        methods.add_method("label", |_lua, this, text: String| {
+        // Optional style table: { size = 24, font = orbit.assets.font("font.ttf") }
+        methods.add_method("label", |lua, this, (text, style): (String, Option<LuaTable>)| {
+            let mut text = egui::RichText::new(text);
+            if let Some(style) = style {
+                if let Some(size) = style.get::<_, Option<f32>>("size")? {
+                    text = text.size(size);
+                }
+                if let Some(font) = style.get::<_, Option<String>>("font")? {
+                    let known = lua.app_data_ref::<AssetCache>().is_some_and(|cache| cache.has_font(&font));
+                    if !known {
+                        return Err(mlua::Error::RuntimeError(format!("unknown font '{}'; load it with orbit.assets.font", font)));
+                    }
+                    text = text.family(egui::FontFamily::Name(font.into()));
+                }
+            }
             egui::CentralPanel::default().show(&this.ctx, |ui| {
                 ui.label(text);
             });
             Ok(())
         });
 
+        methods.add_method("image", |lua, this, (image, width, height): (LuaUserDataRef<LuaImage>, Option<f32>, Option<f32>)| {
+            let texture = lua.app_data_mut::<AssetCache>()
+                .ok_or_else(|| mlua::Error::RuntimeError("assets are not available".to_string()))?
+                .texture(&this.ctx, &image.path)
+                .map_err(mlua::Error::RuntimeError)?;
+            let natural = texture.size_vec2();
+            let size = match (width, height) {
+                (Some(width), Some(height)) => egui::vec2(width, height),
+                (Some(width), None) => egui::vec2(width, natural.y * width / natural.x),
+                (None, Some(height)) => egui::vec2(natural.x * height / natural.y, height),
+                (None, None) => natural,
+            };
+            egui::CentralPanel::default().show(&this.ctx, |ui| {
+                ui.add(egui::Image::new(&texture).fit_to_exact_size(size));
+            });
+            Ok(())
+        });
+
         // Layout methods
         methods.add_method("vertical", |lua, this, callback: LuaFunction| {
             egui::CentralPanel::default().show(&this.ctx, |ui| {
@@ -138,4 +179,210 @@ pub fn create_lua_module(lua: &Lua, ctx: egui::Context, error_sender: mpsc::Send
     })?)?;
 
     Ok(exports)
-}
\ No newline at end of file
+}
+
+fn app_context(lua: &Lua) -> LuaResult<egui::Context> {
+    lua.app_data_ref::<egui::Context>()
+        .map(|ctx| ctx.clone())
+        .ok_or_else(|| mlua::Error::RuntimeError("the UI is not running yet".to_string()))
+}
+
+fn app_settings(lua: &Lua) -> LuaResult<mlua::AppDataRef<'_, Settings>> {
+    lua.app_data_ref::<Settings>()
+        .ok_or_else(|| mlua::Error::RuntimeError("settings are not loaded".to_string()))
+}
+
+fn vfs(lua: &Lua) -> LuaResult<mlua::AppDataRef<'_, Vfs>> {
+    lua.app_data_ref::<Vfs>()
+        .ok_or_else(|| mlua::Error::RuntimeError("the virtual filesystem is not mounted".to_string()))
+}
+
+/// The per-plugin `orbit` table: identity and granted permissions, the
+/// permission-checked clipboard and window controls, assets and the VFS, the
+/// plugin's settings and the central preferences, events, menu entries and `fs`.
+pub fn create_plugin_api<'lua>(lua: &'lua Lua, plugin: &PluginContext) -> LuaResult<LuaTable<'lua>> {
+    let api = lua.create_table()?;
+    api.set("plugin", plugin.name.as_str())?;
+    api.set("plugin_dir", plugin.dir.to_string_lossy().to_string())?;
+    api.set("data_dir", plugin.data_dir.to_string_lossy().to_string())?;
+    api.set("permissions", plugin.permissions.iter().map(|p| p.name()).collect::<Vec<_>>())?;
+    let granted = plugin.permissions.clone();
+    api.set("has_permission", lua.create_function(move |_, name: String| {
+        Ok(Permission::parse(&name).is_some_and(|p| granted.contains(&p)))
+    })?)?;
+
+    let clipboard = lua.create_table()?;
+    let ctx = plugin.clone();
+    clipboard.set("set", lua.create_function(move |lua, text: String| {
+        ctx.check(Permission::Clipboard, "orbit.clipboard.set")?;
+        app_context(lua)?.copy_text(text);
+        Ok(())
+    })?)?;
+    api.set("clipboard", clipboard)?;
+
+    let window = lua.create_table()?;
+    let ctx = plugin.clone();
+    window.set("set_title", lua.create_function(move |lua, title: String| {
+        ctx.check(Permission::Window, "orbit.window.set_title")?;
+        app_context(lua)?.send_viewport_cmd(egui::ViewportCommand::Title(title));
+        Ok(())
+    })?)?;
+    let ctx = plugin.clone();
+    window.set("set_fullscreen", lua.create_function(move |lua, fullscreen: bool| {
+        ctx.check(Permission::Window, "orbit.window.set_fullscreen")?;
+        app_context(lua)?.send_viewport_cmd(egui::ViewportCommand::Fullscreen(fullscreen));
+        Ok(())
+    })?)?;
+    let ctx = plugin.clone();
+    window.set("set_size", lua.create_function(move |lua, (width, height): (f32, f32)| {
+        ctx.check(Permission::Window, "orbit.window.set_size")?;
+        app_context(lua)?.send_viewport_cmd(egui::ViewportCommand::InnerSize(egui::vec2(width, height)));
+        Ok(())
+    })?)?;
+    let ctx = plugin.clone();
+    window.set("minimize", lua.create_function(move |lua, ()| {
+        ctx.check(Permission::Window, "orbit.window.minimize")?;
+        app_context(lua)?.send_viewport_cmd(egui::ViewportCommand::Minimized(true));
+        Ok(())
+    })?)?;
+    api.set("window", window)?;
+
+    let assets = lua.create_table()?;
+    let ctx = plugin.clone();
+    assets.set("image", lua.create_function(move |lua, path: String| {
+        Ok(LuaImage { path: assets::locate(lua, &ctx, &path)? })
+    })?)?;
+    let ctx = plugin.clone();
+    assets.set("font", lua.create_function(move |lua, path: String| {
+        let path = assets::locate(lua, &ctx, &path)?;
+        let stem = path.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+        let family = format!("{}/{}", ctx.name, stem);
+        let app = app_context(lua)?;
+        lua.app_data_mut::<AssetCache>()
+            .ok_or_else(|| mlua::Error::RuntimeError("assets are not available".to_string()))?
+            .add_font(&app, &family, &path)
+            .map_err(mlua::Error::RuntimeError)?;
+        Ok(family)
+    })?)?;
+    api.set("assets", assets)?;
+
+    // the game's files with enabled mods layered on top; paths are relative to the game
+    let layered = lua.create_table()?;
+    layered.set("read", lua.create_function(|lua, path: String| {
+        let data = vfs(lua)?.read(&path).map_err(mlua::Error::RuntimeError)?;
+        lua.create_string(data)
+    })?)?;
+    layered.set("read_text", lua.create_function(|lua, path: String| {
+        let data = vfs(lua)?.read(&path).map_err(mlua::Error::RuntimeError)?;
+        String::from_utf8(data).map_err(|_| mlua::Error::RuntimeError(format!("'{}' is not UTF-8 text", path)))
+    })?)?;
+    layered.set("exists", lua.create_function(|lua, path: String| {
+        Ok(vfs(lua)?.locate(&path).is_some())
+    })?)?;
+    layered.set("list", lua.create_function(|lua, dir: Option<String>| {
+        Ok(vfs(lua)?.list(dir.as_deref().unwrap_or("")))
+    })?)?;
+    layered.set("layer", lua.create_function(|lua, path: String| {
+        Ok(vfs(lua)?.layer_of(&path).map(|layer| layer.plugin.clone()))
+    })?)?;
+    api.set("vfs", layered)?;
+
+    // current values and change handlers live in the registry so the host can
+    // update them when the user edits a setting
+    let values = lua.create_table()?;
+    for spec in &plugin.settings.specs {
+        if let Some(value) = plugin.settings.get(&spec.key) {
+            values.set(spec.key.as_str(), setting_to_lua(lua, value)?)?;
+        }
+    }
+    lua.set_named_registry_value(&settings_key(&plugin.name), values)?;
+    lua.set_named_registry_value(&handlers_key(&plugin.name), lua.create_table()?)?;
+    let settings = lua.create_table()?;
+    let name = plugin.name.clone();
+    settings.set("get", lua.create_function(move |lua, key: String| {
+        let values: LuaTable = lua.named_registry_value(&settings_key(&name))?;
+        let value: LuaValue = values.get(key.as_str())?;
+        if value.is_nil() {
+            return Err(mlua::Error::RuntimeError(format!(
+                "plugin '{}' has no setting '{}'; declare it in index.xml",
+                name, key
+            )));
+        }
+        Ok(value)
+    })?)?;
+    let name = plugin.name.clone();
+    settings.set("on_change", lua.create_function(move |lua, handler: LuaFunction| {
+        let handlers: LuaTable = lua.named_registry_value(&handlers_key(&name))?;
+        handlers.raw_push(handler)
+    })?)?;
+    api.set("settings", settings)?;
+
+    // Orbit's own settings from the Settings screen, read-only
+    let preferences = lua.create_table()?;
+    preferences.set("get", lua.create_function(|lua, key: String| {
+        let settings = app_settings(lua)?;
+        let value = settings.get(&key)
+            .ok_or_else(|| mlua::Error::RuntimeError(format!("there is no setting '{}'", key)))?;
+        setting_to_lua(lua, value)
+    })?)?;
+    preferences.set("keybind", lua.create_function(|lua, action: String| {
+        let settings = app_settings(lua)?;
+        match settings.get(&format!("keybind.{}", action)) {
+            Some(SettingValue::Text(key)) => Ok(key.clone()),
+            _ => Err(mlua::Error::RuntimeError(format!("there is no key binding for '{}'", action))),
+        }
+    })?)?;
+    preferences.set("all", lua.create_function(|lua, ()| {
+        let settings = app_settings(lua)?;
+        let all = lua.create_table()?;
+        for spec in settings.specs() {
+            if let Some(value) = settings.get(&spec.key) {
+                all.set(spec.key.as_str(), setting_to_lua(lua, value)?)?;
+            }
+        }
+        Ok(all)
+    })?)?;
+    api.set("preferences", preferences)?;
+    api.set("events", events::create_api(lua, &plugin.name)?)?;
+    api.set("menu", menu::create_api(lua, plugin)?)?;
+    api.set("fs", files::create_api(lua, plugin)?)?;
+    Ok(api)
+}
+
+fn settings_key(plugin: &str) -> String {
+    format!("orbit.settings.{}", plugin)
+}
+
+fn handlers_key(plugin: &str) -> String {
+    format!("orbit.settings_handlers.{}", plugin)
+}
+
+fn setting_to_lua<'lua>(lua: &'lua Lua, value: &SettingValue) -> LuaResult<LuaValue<'lua>> {
+    match value {
+        SettingValue::Bool(value) => value.into_lua(lua),
+        SettingValue::Int(value) => value.into_lua(lua),
+        SettingValue::Float(value) => value.into_lua(lua),
+        SettingValue::Text(value) => value.as_str().into_lua(lua),
+    }
+}
+
+/// Updates the value a loaded plugin sees and calls its `orbit.settings.on_change`
+/// handlers with `(key, value)`. Plugins that are not loaded are skipped. A failing
+/// handler does not stop the others; their errors are returned together.
+pub fn notify_setting_changed(lua: &Lua, plugin: &str, key: &str, value: &SettingValue) -> LuaResult<()> {
+    let Ok(values) = lua.named_registry_value::<LuaTable>(&settings_key(plugin)) else {
+        return Ok(());
+    };
+    values.set(key, setting_to_lua(lua, value)?)?;
+    let handlers: LuaTable = lua.named_registry_value(&handlers_key(plugin))?;
+    let mut errors = Vec::new();
+    for handler in handlers.sequence_values::<LuaFunction>() {
+        if let Err(e) = handler.and_then(|handler| handler.call::<_, ()>((key, setting_to_lua(lua, value)?))) {
+            errors.push(e.to_string());
+        }
+    }
+    if !errors.is_empty() {
+        return Err(mlua::Error::RuntimeError(errors.join("\n")));
+    }
+    Ok(())
+}
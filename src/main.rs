@@ -1,18 +1,115 @@
 use eframe::egui;
 use egui::ViewportBuilder;
+use std::collections::BTreeSet;
 use std::path::PathBuf;
 use std::fs;
 use orbit::keyid;
 use mlua::Lua;
 use std::sync::mpsc;
+use std::time::Duration;
+use orbit::plugin_state::PluginStates;
+use orbit::{conflicts, load_order};
+use orbit::settings::DisplayMode;
+use orbit::vfs::Vfs;
+use orbit::watcher::FileWatcher;
 
 mod plugin_manager;
 use plugin_manager::PluginManager;
 mod lua_bindings;
+mod assets;
+mod budget;
+mod check;
+mod debug_view;
+use debug_view::DebugView;
+mod events;
+mod files;
+mod markdown;
+mod menu;
+mod sandbox;
+use sandbox::PluginContext;
+mod settings_screen;
+use settings_screen::SettingsScreen;
+#[cfg(test)]
+mod test_dir;
 
-fn main() -> eframe::Result<()> {
-    let mut app = OrbitApp::default();
-    let config_dir = if cfg!(windows) {
+/// Handles `orbit <command> ...` invocations that run without opening a window.
+fn run_command(args: &[String]) -> Option<i32> {
+    match args.first().map(String::as_str) {
+        Some("pack") => {
+            let mut positional = Vec::new();
+            let mut key = None;
+            let mut rest = args[1..].iter();
+            while let Some(arg) = rest.next() {
+                if arg == "--sign" {
+                    key = rest.next();
+                } else {
+                    positional.push(arg);
+                }
+            }
+            let Some(dir) = positional.first() else {
+                eprintln!("usage: orbit pack <plugin dir> [output dir] [--sign <key file>]");
+                return Some(2);
+            };
+            let key = match key.map(|path| orbit::signing::load_signing_key(&PathBuf::from(path))).transpose() {
+                Ok(key) => key,
+                Err(e) => {
+                    eprintln!("orbit pack: {}", e);
+                    return Some(1);
+                }
+            };
+            let out_dir = PathBuf::from(positional.get(1).map(|s| s.as_str()).unwrap_or("."));
+            match orbit::package::pack(&PathBuf::from(dir), &out_dir, key.as_ref()) {
+                Ok(path) => {
+                    println!("Wrote {}", path.display());
+                    Some(0)
+                },
+                Err(e) => {
+                    eprintln!("orbit pack: {}", e);
+                    Some(1)
+                }
+            }
+        },
+        Some("keygen") => {
+            let Some(path) = args.get(1) else {
+                eprintln!("usage: orbit keygen <key file>");
+                return Some(2);
+            };
+            match orbit::signing::generate_key(&PathBuf::from(path)) {
+                Ok(public_key) => {
+                    println!("Wrote secret key to {}. Keep it private.", path);
+                    println!("Public key (add it to trusted_keys.xml): {}", public_key);
+                    Some(0)
+                },
+                Err(e) => {
+                    eprintln!("orbit keygen: {}", e);
+                    Some(1)
+                }
+            }
+        },
+        Some("plugin") if args.get(1).map(String::as_str) == Some("check") => {
+            let Some(dir) = args.get(2) else {
+                eprintln!("usage: orbit plugin check <plugin dir>");
+                return Some(2);
+            };
+            let dir = PathBuf::from(dir);
+            if !dir.is_dir() {
+                eprintln!("orbit plugin check: {} is not a directory", dir.display());
+                return Some(1);
+            }
+            let findings = check::check(&dir, &config_dir());
+            for finding in &findings {
+                println!("{}", finding);
+            }
+            let errors = findings.iter().filter(|f| f.severity == check::Severity::Error).count();
+            println!("{}: {} errors, {} warnings", dir.display(), errors, findings.len() - errors);
+            Some(if errors == 0 { 0 } else { 1 })
+        },
+        _ => None,
+    }
+}
+
+fn config_dir() -> PathBuf {
+    if cfg!(windows) {
         let mut path = PathBuf::from(std::env::var("USERPROFILE").unwrap_or_default());
         path.push("Documents");
         path.push("Orbit");
@@ -22,8 +119,18 @@ fn main() -> eframe::Result<()> {
         path.push(".config");
         path.push("Orbit");
         path
-    };
+    }
+}
+
+fn main() -> eframe::Result<()> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let Some(code) = run_command(&args) {
+        std::process::exit(code);
+    }
+    let config_dir = config_dir();
+    // set before the app exists: the plugin manager reads it when it is created
     std::env::set_var("CONFIG", config_dir.to_str().unwrap());
+    let mut app = OrbitApp::default();
     if let Err(e) = fs::create_dir_all(&config_dir) {
         app.popup.show_warning(format!("Failed to create config directory: {}", e));
     }
@@ -31,9 +138,13 @@ fn main() -> eframe::Result<()> {
     if let Err(_) = keyid(&mut popup) {
         popup.show_info("DRM check Error occurred.");
     }
+    let display_mode = app.settings_screen.settings.display_mode();
     let options = eframe::NativeOptions {
         viewport: ViewportBuilder::default()
-            .with_inner_size([1152.0, 864.0]),
+            .with_inner_size([1152.0, 864.0])
+            .with_fullscreen(display_mode == DisplayMode::Fullscreen)
+            .with_decorations(display_mode != DisplayMode::Borderless)
+            .with_maximized(display_mode == DisplayMode::Borderless),
         ..Default::default()
     };
     eframe::run_native(
@@ -50,6 +161,7 @@ fn main() -> eframe::Result<()> {
                 lua.globals().set("orbit_egui", exports).unwrap_or_default();
             }
             
+            app.settings_screen.settings.apply_style(&cc.egui_ctx);
             Ok(Box::new(app))
         }),
     )
@@ -62,11 +174,40 @@ struct OrbitApp {
     lua_error_receiver: mpsc::Receiver<String>,
     show_menu: bool,
     current_screen: Option<mlua::RegistryKey>,
+    watcher: Option<FileWatcher>,
+    debug_view: DebugView,
+    window_size: Option<egui::Vec2>,
+    /// Plugins whose entrypoints ran at startup, other than the game.
+    loaded_plugins: Option<Vec<String>>,
+    plugin_manager_was_open: bool,
+    /// The showing screen is a settings page opened from here, so going back reopens it.
+    settings_page_opener: Option<PageOpener>,
+    settings_screen: SettingsScreen,
+    /// The plugin whose screen is showing.
+    screen_plugin: String,
+    /// Plugins stopped for going over their execution budget, waiting for the user to
+    /// decide whether to disable them.
+    stopped_plugins: Vec<(String, String)>,
+}
+
+/// Where a plugin's settings page was opened.
+#[derive(Clone, Copy)]
+enum PageOpener {
+    PluginManager,
+    Settings,
 }
 
 impl Default for OrbitApp {
     fn default() -> Self {
+        let settings_screen = SettingsScreen::new(&config_dir());
         let lua = Lua::new();
+        lua.set_app_data(assets::AssetCache::default());
+        lua.set_app_data(events::EventBus::default());
+        lua.set_app_data(menu::MenuRegistry::default());
+        lua.set_app_data(sandbox::SharedModules::default());
+        lua.set_app_data(Vfs::mount(&config_dir()));
+        lua.set_app_data(settings_screen.settings.clone());
+        budget::install(&lua);
         let (error_sender, error_receiver) = mpsc::channel();
         
         Self {
@@ -76,6 +217,240 @@ impl Default for OrbitApp {
             lua_error_receiver: error_receiver,
             show_menu: true,
             current_screen: None,
+            watcher: None,
+            debug_view: DebugView::default(),
+            window_size: None,
+            loaded_plugins: None,
+            plugin_manager_was_open: false,
+            settings_page_opener: None,
+            settings_screen,
+            screen_plugin: String::new(),
+            stopped_plugins: Vec::new(),
+        }
+    }
+}
+
+impl OrbitApp {
+    /// Runs the game plugin's entrypoint in a fresh sandbox and returns its screen table.
+    fn load_game_screen(&self) -> Result<mlua::RegistryKey, String> {
+        let config_dir = config_dir();
+        if !PluginStates::load(&config_dir).is_enabled("game") {
+            return Err("The game plugin is disabled. Enable it in the plugin manager.".to_string());
+        }
+        let order = load_order::enabled_plugins(&config_dir).into_iter().map(|(name, _)| name).collect();
+        events::set_load_order(&self.lua, order);
+        // handlers of the previous run would otherwise fire twice after a reload
+        events::forget_plugin(&self.lua, "game");
+        budget::forget_plugin(&self.lua, "game");
+        // and modules of plugins without an entrypoint would keep their old code; the
+        // others keep the one instance their own code uses until they are reloaded
+        let loaded = self.loaded_plugins.as_deref().unwrap_or_default();
+        sandbox::forget_shared_modules(&self.lua, |plugin| plugin == "game" || !loaded.iter().any(|name| name == plugin));
+        self.lua.set_app_data(Vfs::mount(&config_dir));
+        menu::forget_plugin(&self.lua, "game");
+        let plugin = PluginContext::new(&config_dir, "game");
+        let index_path = plugin.dir.join("index.lua");
+        let chunk = sandbox::create_plugin_env(&self.lua, &plugin)
+            .and_then(|env| sandbox::load_file(&self.lua, &env, &index_path))
+            .map_err(|e| format!("Error loading Lua chunk: {}", e))?;
+        let screen = budget::run(&self.lua, "game", || chunk.call::<_, mlua::Table>(()))
+            .map_err(|e| format!("Error executing Lua chunk: {}", e))?;
+        // the game is loaded either way; handler errors are collected by the bus
+        if let Err(e) = events::emit_host(&self.lua, events::PLUGIN_LOADED, vec![("plugin", "game")]) {
+            events::report_error(&self.lua, format!("Failed to emit {} for game: {}", events::PLUGIN_LOADED, e));
+        }
+        self.lua.create_registry_value(screen).map_err(|e| e.to_string())
+    }
+
+    /// Runs the entrypoint of every enabled plugin other than the game, in load order,
+    /// so they can add main menu entries, settings pages and event handlers. Plugins
+    /// loaded before are forgotten first, so disabled ones disappear from the menu.
+    fn load_plugins(&mut self) {
+        let config_dir = config_dir();
+        // the game keeps running with its sandbox until it is started again
+        sandbox::forget_shared_modules(&self.lua, |plugin| plugin != "game");
+        self.lua.set_app_data(Vfs::mount(&config_dir));
+        for name in self.loaded_plugins.take().unwrap_or_default() {
+            events::forget_plugin(&self.lua, &name);
+            budget::forget_plugin(&self.lua, &name);
+            menu::forget_plugin(&self.lua, &name);
+        }
+        let plugins = load_order::enabled_plugins(&config_dir);
+        events::set_load_order(&self.lua, plugins.iter().map(|(name, _)| name.clone()).collect());
+        let mut loaded = Vec::new();
+        let mut errors = Vec::new();
+        for (name, dir) in plugins.into_iter().filter(|(name, _)| name != "game") {
+            let index_path = dir.join("index.lua");
+            if !index_path.is_file() {
+                continue;
+            }
+            let plugin = PluginContext::new(&config_dir, &name);
+            let result = sandbox::create_plugin_env(&self.lua, &plugin)
+                .and_then(|env| sandbox::load_file(&self.lua, &env, &index_path))
+                .and_then(|chunk| budget::run(&self.lua, &name, || chunk.call::<_, ()>(())))
+                .and_then(|()| events::emit_host(&self.lua, events::PLUGIN_LOADED, vec![("plugin", name.as_str())]));
+            if let Err(e) = result {
+                errors.push(format!("Error loading plugin {}: {}", name, e));
+            }
+            loaded.push(name);
+        }
+        self.loaded_plugins = Some(loaded);
+        self.plugin_manager.set_plugin_pages(menu::settings_pages(&self.lua));
+        self.settings_screen.set_plugin_pages(menu::settings_pages(&self.lua));
+        if let Some(first) = errors.first() {
+            let more = match errors.len() {
+                1 => String::new(),
+                n => format!(" ({} more plugins failed)", n - 1),
+            };
+            self.popup.show_error(format!("{}{}", first, more));
+        }
+    }
+
+    /// Shows a screen table a plugin handed over through its menu entry or settings page.
+    fn show_plugin_screen(&mut self, screen: mlua::RegistryKey, plugin: &str) {
+        self.current_screen = Some(screen);
+        self.screen_plugin = plugin.to_string();
+        self.show_menu = false;
+        // hot reload follows the game plugin only
+        self.watcher = None;
+        self.screen_changed("plugin", Some(plugin));
+    }
+
+    /// Tells plugins which screen is showing now.
+    fn screen_changed(&mut self, screen: &str, plugin: Option<&str>) {
+        let mut fields = vec![("screen", screen)];
+        fields.extend(plugin.map(|plugin| ("plugin", plugin)));
+        if let Err(e) = events::emit_host(&self.lua, events::SCREEN_CHANGED, fields) {
+            self.popup.show_error(format!("Lua error: {}", e));
+        }
+    }
+
+    /// Raises `window_resized` when the window size changes and shows errors from event handlers.
+    fn pump_events(&mut self, ctx: &egui::Context) {
+        let size = ctx.screen_rect().size();
+        if self.window_size.is_some_and(|old| old != size) {
+            let resized = events::emit_host(&self.lua, events::WINDOW_RESIZED, vec![("width", size.x), ("height", size.y)]);
+            if let Err(e) = resized {
+                self.popup.show_error(format!("Lua error: {}", e));
+            }
+        }
+        self.window_size = Some(size);
+        let errors = events::take_errors(&self.lua);
+        if let Some(first) = errors.first() {
+            let more = match errors.len() {
+                1 => String::new(),
+                n => format!(" ({} more in the debug view, F12)", n - 1),
+            };
+            self.popup.show_error(format!("{}{}", first, more));
+        }
+        self.debug_view.record_errors(&errors);
+        self.debug_view.draw(ctx, &self.lua);
+        self.stopped_plugins.extend(budget::take_stopped(&self.lua));
+        self.draw_stopped_prompt(ctx);
+    }
+
+    /// Offers to disable a plugin that was stopped for running too long or using too much memory.
+    fn draw_stopped_prompt(&mut self, ctx: &egui::Context) {
+        let Some((plugin, reason)) = self.stopped_plugins.first() else {
+            return;
+        };
+        let plugin = plugin.clone();
+        let mut decided = None;
+        egui::Window::new("Plugin stopped")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+            .show(ctx, |ui| {
+                ui.label(reason.as_str());
+                ui.label("Disable it so it does not run again?");
+                ui.horizontal(|ui| {
+                    if ui.button(format!("Disable {}", plugin)).clicked() {
+                        decided = Some(true);
+                    }
+                    if ui.button("Keep enabled").clicked() {
+                        decided = Some(false);
+                    }
+                });
+            });
+        let Some(disable) = decided else {
+            return;
+        };
+        self.stopped_plugins.retain(|(stopped, _)| *stopped != plugin);
+        if disable {
+            match self.plugin_manager.disable_plugin(&plugin) {
+                Ok(()) => self.load_plugins(),
+                Err(e) => self.popup.show_error(e),
+            }
+        }
+    }
+
+    /// Puts settings changed on the Settings screen into effect and tells plugins which ones changed.
+    fn apply_settings(&mut self, ctx: &egui::Context, changed: &[String]) {
+        let settings = &self.settings_screen.settings;
+        if changed.iter().any(|key| key == "display_mode") {
+            settings.apply_display_mode(ctx);
+        }
+        settings.apply_style(ctx);
+        self.lua.set_app_data(settings.clone());
+        for key in changed {
+            if let Err(e) = events::emit_host(&self.lua, events::SETTINGS_CHANGED, vec![("key", key.as_str())]) {
+                self.popup.show_error(format!("Lua error: {}", e));
+            }
+        }
+    }
+
+    /// Reloads the plugins whose Lua files changed and then the running game, handing the
+    /// old screen's `state` to the new screen's `on_reload`, and drops cached assets that changed.
+    fn hot_reload(&mut self, ctx: &egui::Context) {
+        let Some(watcher) = &mut self.watcher else {
+            return;
+        };
+        ctx.request_repaint_after(watcher.interval());
+        let changed = watcher.poll();
+        if changed.is_empty() {
+            return;
+        }
+        let config_dir = config_dir();
+        // files may have been added or removed
+        self.lua.set_app_data(Vfs::mount(&config_dir));
+        if let Some(mut cache) = self.lua.app_data_mut::<assets::AssetCache>() {
+            if let Err(e) = cache.invalidate(ctx, &changed) {
+                self.popup.show_error(e);
+            }
+        }
+        let plugins_dir = config_dir.join("plugins");
+        let states = PluginStates::load(&config_dir);
+        let lua_changed: BTreeSet<String> = changed.iter()
+            .filter(|path| path.extension().is_some_and(|ext| ext == "lua"))
+            .filter_map(|path| path.strip_prefix(&plugins_dir).ok()?.components().next())
+            .map(|c| c.as_os_str().to_string_lossy().to_string())
+            .filter(|plugin| states.is_enabled(plugin))
+            .collect();
+        if lua_changed.is_empty() {
+            return;
+        }
+        // other plugins run their entrypoints again, and with them every module the
+        // game got from them; plugins without an entrypoint are reloaded with the game
+        let loaded = self.loaded_plugins.as_deref().unwrap_or_default();
+        if lua_changed.iter().any(|plugin| loaded.contains(plugin)) {
+            self.load_plugins();
+        }
+        let old_state = self.current_screen.as_ref()
+            .and_then(|key| self.lua.registry_value::<mlua::Table>(key).ok())
+            .and_then(|screen| screen.get::<_, mlua::Value>("state").ok())
+            .unwrap_or(mlua::Value::Nil);
+        let reloaded = self.load_game_screen().and_then(|key| {
+            let screen: mlua::Table = self.lua.registry_value(&key).map_err(|e| e.to_string())?;
+            if let Ok(on_reload) = screen.get::<_, mlua::Function>("on_reload") {
+                budget::run(&self.lua, "game", || on_reload.call::<_, ()>((screen, old_state)))
+                    .map_err(|e| format!("Error in on_reload: {}", e))?;
+            }
+            Ok(key)
+        });
+        match reloaded {
+            Ok(key) => self.current_screen = Some(key),
+            // keep running the old code until the file is fixed
+            Err(e) => self.popup.show_error(format!("Reload failed: {}", e)),
         }
     }
 }
@@ -87,14 +462,39 @@ impl eframe::App for OrbitApp {
         if let Ok(exports) = lua_bindings::create_lua_module(&self.lua, ctx.clone(), error_sender) {
             self.lua.globals().set("orbit_egui", exports).unwrap_or_default();
         }
+        self.lua.set_app_data(ctx.clone());
+        budget::new_frame(&self.lua);
 
         if let Ok(error) = self.lua_error_receiver.try_recv() {
             self.popup.show_error(error);
         }
 
+        // plugins are enabled and disabled in the plugin manager; pick up its changes once it closes
+        if self.loaded_plugins.is_none() || (self.plugin_manager_was_open && !self.plugin_manager.show) {
+            self.load_plugins();
+        }
+        self.plugin_manager_was_open = self.plugin_manager.show;
+
+        self.plugin_manager.handle_dropped_files(ctx);
+        self.pump_events(ctx);
         if self.show_menu {
+            // a request from a screen that is gone already
+            menu::take_back_request(&self.lua);
+            let entries = menu::entries(&self.lua);
+            let mut clicked_entry = None;
             self.popup.draw(ctx);
             self.plugin_manager.draw(ctx);
+            self.settings_screen.draw(ctx);
+            let changed_settings = self.settings_screen.take_changes();
+            if !changed_settings.is_empty() {
+                self.apply_settings(ctx, &changed_settings);
+            }
+            for (plugin, key, value) in self.plugin_manager.take_setting_changes() {
+                let notified = budget::run(&self.lua, &plugin, || lua_bindings::notify_setting_changed(&self.lua, &plugin, &key, &value));
+                if let Err(e) = notified {
+                    self.popup.show_error(format!("Lua error in {}'s settings handler: {}", plugin, e));
+                }
+            }
             egui::CentralPanel::default().show(ctx, |ui| {
                 let available_width = ui.available_width();
                 let available_height = ui.available_height();
@@ -113,34 +513,43 @@ impl eframe::App for OrbitApp {
                         if ui.add_sized(button_size, egui::Button::new(
                             egui::RichText::new("Start").size(button_text_size)
                         ).rounding(20.0)).clicked() {
-                            let config_path = std::env::var("CONFIG").unwrap_or_default();
-                            let index_path = PathBuf::from(config_path).join("plugins").join("game").join("index.lua");
-                            
-                            let lua_code = fs::read_to_string(&index_path).unwrap_or_else(|_| {
-                                self.popup.show_error(format!("Failed to read Lua file at: {:?}", index_path));
-                                String::new() // Return an empty string if reading fails
+                            match self.load_game_screen() {
+                                Ok(screen) => {
+                                    self.current_screen = Some(screen);
+                                    self.screen_plugin = "game".to_string();
+                                    self.show_menu = false;
+                                    self.screen_changed("game", None);
+                                    let config_dir = config_dir();
+                                    let conflicts = conflicts::detect(&config_dir);
+                                    if !conflicts.is_empty() {
+                                        self.popup.show_warning(format!(
+                                            "{} modules or assets are provided by more than one plugin; see Conflicts in the plugin manager.",
+                                            conflicts.len()
+                                        ));
+                                    }
+                                    let plugins_dir = config_dir.join("plugins");
+                                    self.watcher = Some(FileWatcher::new(&plugins_dir, Duration::from_millis(500)));
+                                },
+                                Err(e) => {
+                                    self.popup.show_error(e);
+                                }
+                            }
+                        }
+                        for entry in &entries {
+                            ui.add_space(20.0);
+                            let text = egui::RichText::new(&entry.label).size(button_text_size);
+                            let icon = entry.icon.as_ref().and_then(|path| {
+                                self.lua.app_data_mut::<assets::AssetCache>()?.texture(ctx, path).ok()
                             });
-
-                            if !lua_code.is_empty() {
-                                let lua_result = self.lua.load(&lua_code);
-match lua_result.into_function() {
-    Ok(chunk) => {
-        match chunk.call::<_, mlua::Table>(()) {
-            Ok(screen) => {
-                self.current_screen = Some(self.lua.create_registry_value(screen).unwrap());
-                self.show_menu = false;
-            },
-            Err(e) => {
-                self.popup.show_error(format!("Error executing Lua chunk: {}", e));
-            }
-        }
-    },
-    Err(e) => {
-        self.popup.show_error(format!("Error loading Lua chunk: {}", e));
-    }
-}
-                            } else {
-                                self.popup.show_error("Lua code is empty, cannot proceed.");
+                            let button = match icon {
+                                Some(texture) => egui::Button::image_and_text(
+                                    egui::Image::new(&texture).fit_to_exact_size(egui::vec2(button_text_size, button_text_size)),
+                                    text,
+                                ),
+                                None => egui::Button::new(text),
+                            };
+                            if ui.add_sized(button_size, button.rounding(20.0)).clicked() {
+                                clicked_entry = Some((entry.id, entry.plugin.clone()));
                             }
                         }
                         ui.add_space(20.0);
@@ -151,6 +560,12 @@ match lua_result.into_function() {
                             self.plugin_manager.refresh_plugins();
                         }
                         ui.add_space(20.0);
+                        if ui.add_sized(button_size, egui::Button::new(
+                            egui::RichText::new("Settings").size(button_text_size)
+                        ).rounding(20.0)).clicked() {
+                            self.settings_screen.show = true;
+                        }
+                        ui.add_space(20.0);
                         if ui.add_sized(button_size, egui::Button::new(
                             egui::RichText::new("Quit").size(button_text_size)
                         ).rounding(20.0)).clicked() {
@@ -159,16 +574,56 @@ match lua_result.into_function() {
                     });
                 });
             });
-        } else if let Some(screen_key) = &self.current_screen {
+            if let Some((id, plugin)) = clicked_entry {
+                match budget::run(&self.lua, &plugin, || menu::activate(&self.lua, id)) {
+                    Ok(Some(screen)) => self.show_plugin_screen(screen, &plugin),
+                    Ok(None) => {},
+                    Err(e) => self.popup.show_error(format!("Lua error in {}'s menu entry: {}", plugin, e)),
+                }
+            }
+            let opened_page = self.plugin_manager.take_opened_page().map(|page| (page, PageOpener::PluginManager))
+                .or_else(|| self.settings_screen.take_opened_page().map(|page| (page, PageOpener::Settings)));
+            if let Some(((plugin, title), opener)) = opened_page {
+                match menu::settings_page(&self.lua, &plugin, &title) {
+                    Ok(Some(screen)) => {
+                        self.show_plugin_screen(screen, &plugin);
+                        self.settings_page_opener = Some(opener);
+                    },
+                    Ok(None) => self.popup.show_error(format!("{} no longer has a '{}' page.", plugin, title)),
+                    Err(e) => self.popup.show_error(format!("Lua error: {}", e)),
+                }
+            }
+        } else if self.current_screen.is_some() {
+            self.hot_reload(ctx);
+            self.popup.draw(ctx);
+            let Some(screen_key) = &self.current_screen else {
+                return;
+            };
             // Draw the current Lua screen
             if let Ok(screen) = self.lua.registry_value::<mlua::Table>(screen_key) {
                 if let Ok(show) = screen.get::<_, mlua::Function>("show") {
-                    if let Err(e) = show.call::<_, ()>(screen) {
+                    if let Err(e) = budget::run(&self.lua, &self.screen_plugin, || show.call::<_, ()>(screen)) {
                         self.popup.show_error(format!("Lua error: {}", e));
                         self.show_menu = true; // Return to menu on error
+                        self.watcher = None;
                     }
                 }
             }
+            if menu::take_back_request(&self.lua) {
+                self.show_menu = true;
+                self.watcher = None;
+            }
+            if self.show_menu {
+                self.screen_changed("menu", None);
+                match self.settings_page_opener.take() {
+                    Some(PageOpener::PluginManager) => {
+                        self.plugin_manager.show = true;
+                        self.plugin_manager.refresh_plugins();
+                    },
+                    Some(PageOpener::Settings) => self.settings_screen.show = true,
+                    None => {},
+                }
+            }
         }
     }
 }
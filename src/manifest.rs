@@ -0,0 +1,136 @@
+use crate::permissions::Permission;
+use crate::plugin_settings::SettingSpec;
+use quick_xml::events::Event;
+use quick_xml::reader::Reader;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The contents of a plugin's `index.xml`.
+#[derive(Clone, Default)]
+pub struct Manifest {
+    pub name: String,
+    pub description: String,
+    pub version: String,
+    pub author: String,
+    pub homepage: String,
+    pub tags: Vec<String>,
+    pub logo: Option<PathBuf>,
+    pub update: Option<UpdateSource>,
+    pub permissions: Vec<Permission>,
+    pub dependencies: Vec<Dependency>,
+    pub settings: Vec<SettingSpec>,
+    /// Problems that did not stop the manifest loading, such as permissions this
+    /// version of Orbit does not know; those are left out of `permissions`.
+    pub warnings: Vec<String>,
+}
+
+/// `<depends version="1.2">name</depends>`: another plugin, by folder name, that has
+/// to be installed first, optionally at least at the given version.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Dependency {
+    pub name: String,
+    pub version: Option<String>,
+}
+
+/// `<update branch="main" tags="v*" beta="next">url</update>`: where updates come from
+/// and which revisions count as releases.
+#[derive(Clone, Default)]
+pub struct UpdateSource {
+    pub url: String,
+    /// Branch followed by default; `main` when not given.
+    pub branch: Option<String>,
+    /// Glob over tag names; when set, the newest matching tag is the release.
+    pub tags: Option<String>,
+    /// Branch users can opt into from the plugin manager.
+    pub beta: Option<String>,
+}
+
+pub(crate) fn attribute(e: &quick_xml::events::BytesStart, name: &str) -> Option<String> {
+    e.try_get_attribute(name).ok().flatten()
+        .and_then(|a| a.unescape_value().ok().map(|v| v.trim().to_string()))
+        .filter(|v| !v.is_empty())
+}
+
+impl Manifest {
+    pub fn load(plugin_dir: &Path) -> Result<Manifest, String> {
+        let index_path = plugin_dir.join("index.xml");
+        let xml_content = fs::read_to_string(&index_path)
+            .map_err(|e| format!("Failed to read {:?}: {}", index_path, e))?;
+        Manifest::parse(&xml_content)
+    }
+
+    pub fn parse(xml_content: &str) -> Result<Manifest, String> {
+        let mut manifest = Manifest::default();
+        let mut reader = Reader::from_str(xml_content);
+        reader.trim_text(true);
+
+        let mut buf = Vec::new();
+        let mut current_element = String::new();
+        let mut update_source = UpdateSource::default();
+        let mut dependency_version = None;
+        loop {
+            match reader.read_event_into(&mut buf) {
+                Ok(Event::Start(ref e)) => {
+                    current_element = String::from_utf8_lossy(e.name().as_ref()).to_string();
+                    if current_element == "update" {
+                        update_source = UpdateSource {
+                            url: String::new(),
+                            branch: attribute(e, "branch"),
+                            tags: attribute(e, "tags"),
+                            beta: attribute(e, "beta"),
+                        };
+                    }
+                    if current_element == "depends" {
+                        dependency_version = attribute(e, "version");
+                    }
+                    if current_element == "setting" {
+                        manifest.settings.push(SettingSpec::from_element(e)?);
+                    }
+                },
+                Ok(Event::Empty(ref e)) if e.name().as_ref() == b"setting" => {
+                    manifest.settings.push(SettingSpec::from_element(e)?);
+                },
+                Ok(Event::Text(ref e)) => {
+                    let text = e.unescape().unwrap_or_default().trim().to_string();
+                    match current_element.as_str() {
+                        "name" => manifest.name = text,
+                        "description" => manifest.description = text,
+                        "version" => manifest.version = text,
+                        "author" => manifest.author = text,
+                        "homepage" => manifest.homepage = text,
+                        "tag" => manifest.tags.push(text),
+                        "logo" => manifest.logo = Some(PathBuf::from(text)),
+                        "update" => {
+                            update_source.url = text;
+                            manifest.update = Some(update_source.clone());
+                        },
+                        "depends" => manifest.dependencies.push(Dependency {
+                            name: text,
+                            version: dependency_version.take(),
+                        }),
+                        "setting" => {
+                            if let Some(setting) = manifest.settings.last_mut() {
+                                setting.label = text;
+                            }
+                        },
+                        "permission" => {
+                            match Permission::parse(&text) {
+                                Some(permission) if !manifest.permissions.contains(&permission) => {
+                                    manifest.permissions.push(permission);
+                                },
+                                Some(_) => {},
+                                None => manifest.warnings.push(format!("Unknown permission '{}' in index.xml, ignored", text)),
+                            }
+                        },
+                        _ => {}
+                    }
+                },
+                Ok(Event::Eof) => break,
+                Err(e) => return Err(format!("Invalid index.xml at position {}: {}", reader.buffer_position(), e)),
+                _ => {}
+            }
+            buf.clear();
+        }
+        Ok(manifest)
+    }
+}
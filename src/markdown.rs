@@ -0,0 +1,214 @@
+use eframe::egui;
+use egui::text::LayoutJob;
+
+/// Draws the subset of Markdown plugin READMEs and changelogs tend to use: `#` headings,
+/// `-`/`*`/numbered list items, fenced code blocks, paragraphs, and inline `**bold**`,
+/// `*italic*`, `` `code` `` and `[links](url)`.
+pub fn show(ui: &mut egui::Ui, text: &str) {
+    let mut paragraph = String::new();
+    let mut code_block: Option<String> = None;
+    for line in text.lines() {
+        if line.trim_start().starts_with("```") {
+            match code_block.take() {
+                Some(code) => {
+                    egui::Frame::group(ui.style()).show(ui, |ui| {
+                        ui.label(egui::RichText::new(code.trim_end()).monospace());
+                    });
+                },
+                None => {
+                    flush_paragraph(ui, &mut paragraph);
+                    code_block = Some(String::new());
+                },
+            }
+            continue;
+        }
+        if let Some(code) = &mut code_block {
+            code.push_str(line);
+            code.push('\n');
+            continue;
+        }
+        let trimmed = line.trim();
+        if trimmed.is_empty() {
+            flush_paragraph(ui, &mut paragraph);
+            continue;
+        }
+        let level = trimmed.chars().take_while(|&c| c == '#').count();
+        if level > 0 && trimmed[level..].starts_with(' ') {
+            flush_paragraph(ui, &mut paragraph);
+            let size = match level {
+                1 => 22.0,
+                2 => 18.0,
+                _ => 15.0,
+            };
+            ui.add_space(4.0);
+            ui.label(egui::RichText::new(trimmed[level..].trim()).size(size).strong());
+            continue;
+        }
+        if let Some(item) = list_item(trimmed) {
+            flush_paragraph(ui, &mut paragraph);
+            ui.horizontal_wrapped(|ui| {
+                ui.label(item.0);
+                ui.label(inline(ui, item.1));
+            });
+            continue;
+        }
+        if !paragraph.is_empty() {
+            paragraph.push(' ');
+        }
+        paragraph.push_str(trimmed);
+    }
+    if let Some(code) = code_block {
+        ui.label(egui::RichText::new(code.trim_end()).monospace());
+    }
+    flush_paragraph(ui, &mut paragraph);
+}
+
+fn flush_paragraph(ui: &mut egui::Ui, paragraph: &mut String) {
+    if paragraph.is_empty() {
+        return;
+    }
+    let job = inline(ui, paragraph);
+    ui.label(job);
+    ui.add_space(4.0);
+    paragraph.clear();
+}
+
+/// Splits `- item`, `* item` and `1. item` into the bullet to draw and the item text.
+fn list_item(line: &str) -> Option<(String, &str)> {
+    if let Some(item) = line.strip_prefix("- ").or_else(|| line.strip_prefix("* ")) {
+        return Some(("•".to_string(), item));
+    }
+    let digits = line.chars().take_while(|c| c.is_ascii_digit()).count();
+    if digits > 0 {
+        if let Some(item) = line[digits..].strip_prefix(". ") {
+            return Some((line[..digits + 1].to_string(), item));
+        }
+    }
+    None
+}
+
+fn inline(ui: &egui::Ui, text: &str) -> LayoutJob {
+    let style = ui.style();
+    let color = style.visuals.text_color();
+    let body = egui::TextStyle::Body.resolve(style);
+    let monospace = egui::TextStyle::Monospace.resolve(style);
+    let mut job = LayoutJob::default();
+    let mut bold = false;
+    let mut italic = false;
+    let mut rest = text;
+    while !rest.is_empty() {
+        let format = |font_id: egui::FontId, bold: bool, italic: bool| egui::TextFormat {
+            font_id,
+            color: if bold { style.visuals.strong_text_color() } else { color },
+            italics: italic,
+            ..Default::default()
+        };
+        if let Some(after) = rest.strip_prefix("**") {
+            bold = !bold;
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix('*') {
+            italic = !italic;
+            rest = after;
+        } else if let Some((code, after)) = rest.strip_prefix('`').and_then(|r| r.split_once('`')) {
+            job.append(code, 0.0, format(monospace.clone(), false, false));
+            rest = after;
+        } else if let Some((label, after)) = rest.strip_prefix('[').and_then(link) {
+            let mut link_format = format(body.clone(), bold, italic);
+            link_format.color = style.visuals.hyperlink_color;
+            job.append(label, 0.0, link_format);
+            rest = after;
+        } else {
+            let first = rest.chars().next().map_or(1, char::len_utf8);
+            let end = rest[first..].find(['*', '`', '['])
+                .map(|i| i + first)
+                .unwrap_or(rest.len());
+            job.append(&rest[..end], 0.0, format(body.clone(), bold, italic));
+            rest = &rest[end..];
+        }
+    }
+    job
+}
+
+/// Given the text after `[`, returns the link label and what follows `](url)`.
+fn link(text: &str) -> Option<(&str, &str)> {
+    let (label, after) = text.split_once("](")?;
+    let (_, after) = after.split_once(')')?;
+    Some((label, after))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Runs `f` with a `Ui` from a headless frame.
+    fn with_ui(f: impl FnOnce(&mut egui::Ui)) {
+        let ctx = egui::Context::default();
+        let mut f = Some(f);
+        let _ = ctx.run(egui::RawInput::default(), |ctx| {
+            egui::CentralPanel::default().show(ctx, |ui| {
+                if let Some(f) = f.take() {
+                    f(ui);
+                }
+            });
+        });
+    }
+
+    /// Each piece of a layout job: its text, whether it is bold, italic, code or a link.
+    fn sections(ui: &egui::Ui, text: &str) -> Vec<(String, bool, bool, bool, bool)> {
+        let job = inline(ui, text);
+        let visuals = &ui.style().visuals;
+        job.sections.iter().map(|section| (
+            job.text[section.byte_range.clone()].to_string(),
+            section.format.color == visuals.strong_text_color(),
+            section.format.italics,
+            section.format.font_id.family == egui::FontFamily::Monospace,
+            section.format.color == visuals.hyperlink_color,
+        )).collect()
+    }
+
+    #[test]
+    fn splits_list_items() {
+        assert_eq!(list_item("- apples"), Some(("•".to_string(), "apples")));
+        assert_eq!(list_item("* pears"), Some(("•".to_string(), "pears")));
+        assert_eq!(list_item("12. plums"), Some(("12.".to_string(), "plums")));
+        assert_eq!(list_item("-no space"), None);
+        assert_eq!(list_item("2024 was a year"), None);
+    }
+
+    #[test]
+    fn finds_links() {
+        assert_eq!(link("docs](https://example.com) for more"), Some(("docs", " for more")));
+        assert_eq!(link("not a link]"), None);
+        assert_eq!(link("unclosed](https://example.com"), None);
+    }
+
+    #[test]
+    fn formats_inline_markup() {
+        with_ui(|ui| {
+            let text = |s: &str| (s.to_string(), false, false, false, false);
+            assert_eq!(sections(ui, "plain ünïcode"), [text("plain ünïcode")]);
+            assert_eq!(sections(ui, "a **bold** *word*"), [
+                text("a "),
+                ("bold".to_string(), true, false, false, false),
+                text(" "),
+                ("word".to_string(), false, true, false, false),
+            ]);
+            assert_eq!(sections(ui, "run `make *all*` or [read](docs.md)"), [
+                text("run "),
+                ("make *all*".to_string(), false, false, true, false),
+                text(" or "),
+                ("read".to_string(), false, false, false, true),
+            ]);
+            // brackets and backticks that open nothing stay text
+            assert_eq!(sections(ui, "[x] `y"), [text("[x] "), text("`y")]);
+        });
+    }
+
+    #[test]
+    fn draws_unfinished_markup() {
+        with_ui(|ui| {
+            show(ui, "# Title\n\nSome **bold\ntext\n\n- item\n1. first\n\n```\nlet x = 1;\n");
+            show(ui, "");
+        });
+    }
+}
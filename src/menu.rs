@@ -0,0 +1,260 @@
+use mlua::prelude::*;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use crate::assets::{self, LuaImage};
+use crate::sandbox::PluginContext;
+
+/// A button added to the main menu with `orbit.menu.add`.
+struct Entry {
+    id: u64,
+    plugin: String,
+    label: String,
+    icon: Option<PathBuf>,
+    order: i64,
+    /// The screen table to show, or the function to call when clicked.
+    action: LuaRegistryKey,
+}
+
+/// A page added with `orbit.menu.add_settings_page`, opened from the plugin manager.
+struct SettingsPage {
+    plugin: String,
+    title: String,
+    screen: LuaRegistryKey,
+}
+
+/// Main menu entries and settings pages registered by plugins, kept in the Lua
+/// state's app data.
+#[derive(Default)]
+pub struct MenuRegistry {
+    next_id: u64,
+    entries: Vec<Entry>,
+    settings_pages: Vec<SettingsPage>,
+    /// Set by `orbit.menu.back()` until the host returns to the main menu.
+    back_requested: bool,
+}
+
+/// What the host needs to draw a menu entry.
+pub struct MenuEntry {
+    pub id: u64,
+    pub plugin: String,
+    pub label: String,
+    pub icon: Option<PathBuf>,
+}
+
+fn registry(lua: &Lua) -> LuaResult<mlua::AppDataRefMut<'_, MenuRegistry>> {
+    lua.app_data_mut::<MenuRegistry>()
+        .ok_or_else(|| LuaError::RuntimeError("the menu is not available".to_string()))
+}
+
+/// Drops every entry and page of a plugin, before its code is loaded again.
+pub fn forget_plugin(lua: &Lua, plugin: &str) {
+    let Ok(mut registry) = registry(lua) else {
+        return;
+    };
+    let (gone, kept): (Vec<_>, Vec<_>) = std::mem::take(&mut registry.entries).into_iter().partition(|e| e.plugin == plugin);
+    registry.entries = kept;
+    let (gone_pages, kept): (Vec<_>, Vec<_>) = std::mem::take(&mut registry.settings_pages).into_iter().partition(|p| p.plugin == plugin);
+    registry.settings_pages = kept;
+    drop(registry);
+    for entry in gone {
+        let _ = lua.remove_registry_value(entry.action);
+    }
+    for page in gone_pages {
+        let _ = lua.remove_registry_value(page.screen);
+    }
+}
+
+/// Entries in the order they are drawn: by `order`, then by label.
+pub fn entries(lua: &Lua) -> Vec<MenuEntry> {
+    let Ok(registry) = registry(lua) else {
+        return Vec::new();
+    };
+    let mut entries: Vec<&Entry> = registry.entries.iter().collect();
+    entries.sort_by(|a, b| a.order.cmp(&b.order).then_with(|| a.label.cmp(&b.label)));
+    entries.into_iter()
+        .map(|e| MenuEntry { id: e.id, plugin: e.plugin.clone(), label: e.label.clone(), icon: e.icon.clone() })
+        .collect()
+}
+
+/// Runs a clicked entry. Returns the screen to show: the entry's screen table, or
+/// the table its `on_click` returned; `None` keeps the main menu up.
+pub fn activate(lua: &Lua, id: u64) -> LuaResult<Option<LuaRegistryKey>> {
+    let action: LuaValue = {
+        let registry = registry(lua)?;
+        let Some(entry) = registry.entries.iter().find(|e| e.id == id) else {
+            return Ok(None);
+        };
+        lua.registry_value(&entry.action)?
+    };
+    let screen = match action {
+        LuaValue::Function(on_click) => on_click.call::<_, Option<LuaTable>>(())?,
+        LuaValue::Table(screen) => Some(screen),
+        _ => None,
+    };
+    screen.map(|screen| lua.create_registry_value(screen)).transpose()
+}
+
+/// Titles of the settings pages each plugin added, in the order they were added.
+pub fn settings_pages(lua: &Lua) -> BTreeMap<String, Vec<String>> {
+    let mut pages: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    if let Ok(registry) = registry(lua) {
+        for page in &registry.settings_pages {
+            pages.entry(page.plugin.clone()).or_default().push(page.title.clone());
+        }
+    }
+    pages
+}
+
+pub fn settings_page(lua: &Lua, plugin: &str, title: &str) -> LuaResult<Option<LuaRegistryKey>> {
+    let registry = registry(lua)?;
+    let Some(page) = registry.settings_pages.iter().find(|p| p.plugin == plugin && p.title == title) else {
+        return Ok(None);
+    };
+    let screen: LuaTable = lua.registry_value(&page.screen)?;
+    lua.create_registry_value(screen).map(Some)
+}
+
+/// Whether a screen called `orbit.menu.back()` since the last call.
+pub fn take_back_request(lua: &Lua) -> bool {
+    registry(lua).is_ok_and(|mut registry| std::mem::take(&mut registry.back_requested))
+}
+
+/// Builds the `orbit.menu` table for a plugin:
+/// `add{ label, icon, order, screen | on_click }`, `add_settings_page{ title, screen }` and `back()`.
+pub fn create_api<'lua>(lua: &'lua Lua, plugin: &PluginContext) -> LuaResult<LuaTable<'lua>> {
+    let menu = lua.create_table()?;
+    let ctx = plugin.clone();
+    menu.set("add", lua.create_function(move |lua, spec: LuaTable| {
+        let label: String = spec.get("label")?;
+        let icon = match spec.get::<_, LuaValue>("icon")? {
+            LuaValue::Nil => None,
+            LuaValue::String(path) => Some(assets::locate(lua, &ctx, path.to_str()?)?),
+            LuaValue::UserData(image) => Some(image.borrow::<LuaImage>()?.path.clone()),
+            other => {
+                return Err(LuaError::RuntimeError(format!("menu icon must be a path or an image, got {}", other.type_name())));
+            },
+        };
+        let action = match (spec.get::<_, LuaValue>("screen")?, spec.get::<_, LuaValue>("on_click")?) {
+            (LuaValue::Table(screen), _) => LuaValue::Table(screen),
+            (_, LuaValue::Function(on_click)) => LuaValue::Function(on_click),
+            _ => {
+                return Err(LuaError::RuntimeError(format!("menu entry '{}' needs a screen table or an on_click function", label)));
+            },
+        };
+        let order = spec.get::<_, Option<i64>>("order")?.unwrap_or(0);
+        let action = lua.create_registry_value(action)?;
+        let mut registry = registry(lua)?;
+        registry.next_id += 1;
+        let id = registry.next_id;
+        registry.entries.push(Entry { id, plugin: ctx.name.clone(), label, icon, order, action });
+        Ok(id)
+    })?)?;
+    let name = plugin.name.clone();
+    menu.set("add_settings_page", lua.create_function(move |lua, spec: LuaTable| {
+        let title: String = spec.get("title")?;
+        let screen = lua.create_registry_value(spec.get::<_, LuaTable>("screen")?)?;
+        let mut registry = registry(lua)?;
+        if registry.settings_pages.iter().any(|p| p.plugin == name && p.title == title) {
+            return Err(LuaError::RuntimeError(format!("settings page '{}' was already added", title)));
+        }
+        registry.settings_pages.push(SettingsPage { plugin: name.clone(), title, screen });
+        Ok(())
+    })?)?;
+    menu.set("back", lua.create_function(|lua, ()| {
+        registry(lua)?.back_requested = true;
+        Ok(())
+    })?)?;
+    Ok(menu)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_dir::TestDir;
+
+    fn lua() -> Lua {
+        let lua = Lua::new();
+        lua.set_app_data(MenuRegistry::default());
+        lua
+    }
+
+    /// Runs `code` with the plugin's `orbit.menu` table as the global `menu`.
+    fn run(lua: &Lua, config: &TestDir, plugin: &str, code: &str) -> LuaResult<()> {
+        let plugin = PluginContext::new(config.path(), plugin);
+        lua.globals().set("menu", create_api(lua, &plugin)?)?;
+        lua.load(code).exec()
+    }
+
+    fn labels(lua: &Lua) -> Vec<String> {
+        entries(lua).into_iter().map(|entry| entry.label).collect()
+    }
+
+    #[test]
+    fn entries_are_ordered_and_activated() {
+        let lua = lua();
+        let config = TestDir::new("menu-entries");
+        let icon = config.write("plugins/a/icon.png", "");
+        run(&lua, &config, "a", r#"
+            menu.add{ label = "Zebra", screen = { name = "zebra" } }
+            menu.add{ label = "Apple", icon = "icon.png", on_click = function() return { name = "clicked" } end }
+            menu.add{ label = "First", order = -1, on_click = function() end }
+        "#).unwrap();
+        assert_eq!(labels(&lua), ["First", "Apple", "Zebra"]);
+        let entries = entries(&lua);
+        assert_eq!(entries[1].icon, Some(icon));
+        assert!(entries.iter().all(|entry| entry.plugin == "a"));
+
+        let name = |id| activate(&lua, id).unwrap()
+            .map(|key| lua.registry_value::<LuaTable>(&key).unwrap().get::<_, String>("name").unwrap());
+        assert_eq!(name(entries[2].id), Some("zebra".to_string()));
+        assert_eq!(name(entries[1].id), Some("clicked".to_string()));
+        assert_eq!(name(entries[0].id), None);
+        assert_eq!(name(999), None);
+    }
+
+    #[test]
+    fn bad_entries_are_refused() {
+        let lua = lua();
+        let config = TestDir::new("menu-bad");
+        assert!(run(&lua, &config, "a", r#"menu.add{ label = "Nothing" }"#).is_err());
+        assert!(run(&lua, &config, "a", r#"menu.add{ label = "Icon", icon = 3, on_click = print }"#).is_err());
+        assert!(run(&lua, &config, "a", r#"menu.add{ label = "Missing", icon = "missing.png", on_click = print }"#).is_err());
+        assert!(entries(&lua).is_empty());
+    }
+
+    #[test]
+    fn settings_pages_are_kept_per_plugin() {
+        let lua = lua();
+        let config = TestDir::new("menu-pages");
+        run(&lua, &config, "a", r#"
+            menu.add_settings_page{ title = "Controls", screen = { name = "controls" } }
+            menu.add_settings_page{ title = "Audio", screen = {} }
+        "#).unwrap();
+        run(&lua, &config, "b", r#"menu.add_settings_page{ title = "Controls", screen = {} }"#).unwrap();
+        assert!(run(&lua, &config, "a", r#"menu.add_settings_page{ title = "Audio", screen = {} }"#).is_err());
+
+        let pages = settings_pages(&lua);
+        assert_eq!(pages["a"], ["Controls", "Audio"]);
+        assert_eq!(pages["b"], ["Controls"]);
+        let key = settings_page(&lua, "a", "Controls").unwrap().unwrap();
+        assert_eq!(lua.registry_value::<LuaTable>(&key).unwrap().get::<_, String>("name").unwrap(), "controls");
+        assert!(settings_page(&lua, "b", "Audio").unwrap().is_none());
+    }
+
+    #[test]
+    fn forgetting_a_plugin_drops_its_entries_and_pages() {
+        let lua = lua();
+        let config = TestDir::new("menu-forget");
+        run(&lua, &config, "a", r#"
+            menu.add{ label = "A", screen = {} }
+            menu.add_settings_page{ title = "A", screen = {} }
+            menu.back()
+        "#).unwrap();
+        run(&lua, &config, "b", r#"menu.add{ label = "B", screen = {} }"#).unwrap();
+        forget_plugin(&lua, "a");
+        assert_eq!(labels(&lua), ["B"]);
+        assert!(!settings_pages(&lua).contains_key("a"));
+        assert!(take_back_request(&lua));
+        assert!(!take_back_request(&lua));
+    }
+}
@@ -0,0 +1,150 @@
+use crate::load_order;
+use crate::manifest::Manifest;
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Files that can back `module`: `a.b` is `a/b.lua` or `a/b/init.lua`.
+pub fn candidates(dir: &Path, module: &str) -> [PathBuf; 2] {
+    let relative = module.replace('.', "/");
+    [dir.join(format!("{}.lua", relative)), dir.join(relative).join("init.lua")]
+}
+
+/// Refuses module names that would not map to a file inside the folder searched:
+/// empty ones, ones starting or ending with `.` or containing `..`, and path separators.
+pub fn check_name(module: &str) -> Result<(), String> {
+    if module.is_empty() || module.starts_with('.') || module.ends_with('.') || module.contains("..")
+        || module.contains(['/', '\\', ':']) {
+        return Err(format!("invalid module name '{}'; use names like 'ui.button'", module));
+    }
+    Ok(())
+}
+
+/// The plugins `require` looks in for a plugin's modules, as name and folder: the plugin
+/// itself, then the enabled plugins it depends on directly or through other
+/// dependencies, the one loading latest first so it wins like it does for conflicts.
+pub fn search_path(config_dir: &Path, name: &str, dir: &Path) -> Vec<(String, PathBuf)> {
+    let enabled = load_order::enabled_plugins(config_dir);
+    let mut dependencies = BTreeSet::new();
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(plugin_dir) = pending.pop() {
+        let Ok(manifest) = Manifest::load(&plugin_dir) else {
+            continue;
+        };
+        for dependency in manifest.dependencies {
+            if dependency.name == name || !dependencies.insert(dependency.name.clone()) {
+                continue;
+            }
+            if let Some((_, dependency_dir)) = enabled.iter().find(|(enabled, _)| *enabled == dependency.name) {
+                pending.push(dependency_dir.clone());
+            }
+        }
+    }
+    let mut path = vec![(name.to_string(), dir.to_path_buf())];
+    path.extend(enabled.into_iter().rev().filter(|(plugin, _)| dependencies.contains(plugin)));
+    path
+}
+
+/// Finds the plugin and file providing `module` along `search_path`, or says where it looked.
+pub fn find(search_path: &[(String, PathBuf)], module: &str) -> Result<(String, PathBuf), String> {
+    check_name(module)?;
+    let mut searched = Vec::new();
+    for (plugin, dir) in search_path {
+        for candidate in candidates(dir, module) {
+            if candidate.is_file() {
+                // a symlinked folder could still lead elsewhere
+                let inside = match (candidate.canonicalize(), dir.canonicalize()) {
+                    (Ok(file), Ok(dir)) => file.starts_with(dir),
+                    _ => false,
+                };
+                if !inside {
+                    return Err(format!("module '{}' resolves to {:?}, outside plugin '{}'", module, candidate, plugin));
+                }
+                return Ok((plugin.clone(), candidate));
+            }
+            searched.push(candidate.display().to_string());
+        }
+    }
+    Err(format!("module '{}' not found; searched: {}", module, searched.join(", ")))
+}
+
+/// An installed plugin outside `search_path` that provides `module`, to point out a
+/// missing dependency.
+pub fn provider_elsewhere(config_dir: &Path, search_path: &[(String, PathBuf)], module: &str) -> Option<String> {
+    check_name(module).ok()?;
+    let mut plugins: Vec<PathBuf> = fs::read_dir(config_dir.join("plugins")).ok()?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|dir| dir.is_dir() && !search_path.iter().any(|(_, searched)| searched == dir))
+        .collect();
+    plugins.sort();
+    plugins.into_iter()
+        .find(|dir| candidates(dir, module).iter().any(|candidate| candidate.is_file()))
+        .and_then(|dir| dir.file_name().map(|name| name.to_string_lossy().to_string()))
+}
+
+/// `find`'s error, plus a hint when an installed plugin that is not a dependency has the module.
+pub fn not_found(config_dir: &Path, search_path: &[(String, PathBuf)], module: &str, error: String) -> String {
+    match provider_elsewhere(config_dir, search_path, module) {
+        Some(plugin) => format!("{}; plugin '{}' has it, list it with <depends>{}</depends> in index.xml", error, plugin, plugin),
+        None => error,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_dir::TestDir;
+
+    #[test]
+    fn finds_modules_along_the_search_path() {
+        let dir = TestDir::new("modules");
+        let own = dir.write("own/shared.lua", "");
+        dir.write("lib/shared.lua", "");
+        let button = dir.write("own/ui/button/init.lua", "");
+        let util = dir.write("lib/util.lua", "");
+        let search_path = vec![
+            ("own".to_string(), dir.path().join("own")),
+            ("lib".to_string(), dir.path().join("lib")),
+        ];
+
+        assert_eq!(find(&search_path, "shared").unwrap(), ("own".to_string(), own));
+        assert_eq!(find(&search_path, "ui.button").unwrap(), ("own".to_string(), button));
+        assert_eq!(find(&search_path, "util").unwrap(), ("lib".to_string(), util));
+
+        let error = find(&search_path, "missing").unwrap_err();
+        assert!(error.starts_with("module 'missing' not found"));
+        for searched in ["own/missing.lua", "own/missing/init.lua", "lib/missing.lua", "lib/missing/init.lua"] {
+            assert!(error.contains(&dir.path().join(searched).display().to_string()), "{} not in {}", searched, error);
+        }
+    }
+
+    #[test]
+    fn module_names_cannot_leave_the_plugin() {
+        let dir = TestDir::new("modules-escape");
+        dir.write("own/ok.lua", "");
+        dir.write("other/init.lua", "");
+        dir.write("other/evil.lua", "");
+        let search_path = vec![("own".to_string(), dir.path().join("own"))];
+        let absolute = dir.path().join("other").display().to_string().replace('/', ".");
+
+        for module in ["", ".ok", "ok.", "a..ok", "x/../../other/init", "..other.evil", &absolute, "a\\b", "c:ok"] {
+            let error = find(&search_path, module).unwrap_err();
+            assert!(error.starts_with("invalid module name"), "{}: {}", module, error);
+            assert_eq!(provider_elsewhere(dir.path(), &search_path, module), None);
+        }
+        assert!(find(&search_path, "ok").is_ok());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinked_modules_cannot_leave_the_plugin() {
+        let dir = TestDir::new("modules-symlink");
+        dir.write("other/evil.lua", "");
+        fs::create_dir_all(dir.path().join("own")).unwrap();
+        std::os::unix::fs::symlink(dir.path().join("other"), dir.path().join("own/linked")).unwrap();
+        let search_path = vec![("own".to_string(), dir.path().join("own"))];
+        let error = find(&search_path, "linked.evil").unwrap_err();
+        assert!(error.contains("outside plugin 'own'"), "{}", error);
+    }
+}
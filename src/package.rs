@@ -0,0 +1,803 @@
+use crate::manifest::Manifest;
+use crate::signing::{self, TrustedKeys, PACKAGE_SIGNATURE};
+use ed25519_dalek::SigningKey;
+use quick_xml::escape::escape;
+use quick_xml::events::Event;
+use quick_xml::reader::Reader;
+use std::collections::BTreeMap;
+use sha2::{Digest, Sha256};
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+const IGNORE_FILE: &str = ".orbitignore";
+/// What an archive may unpack to in total, so a zip bomb cannot fill the disk.
+const MAX_UNPACKED_SIZE: u64 = 1024 * 1024 * 1024;
+/// Entries an archive may have.
+const MAX_ENTRIES: usize = 20_000;
+/// Written at the root of packed archives, next to the plugin folder.
+pub const PACKAGE_MANIFEST: &str = "package.xml";
+
+/// Whether a package's files match its signed hash list.
+#[derive(Clone)]
+pub enum Verification {
+    /// Signed by a trusted key (named here) and every file matches.
+    Verified(String),
+    /// Intact, but unsigned or signed by a key nobody trusted yet (given in hex).
+    Unverified(Option<String>),
+    /// Files differ from the hash list or the signature is wrong.
+    Tampered(String),
+}
+
+impl Verification {
+    pub fn is_tampered(&self) -> bool {
+        matches!(self, Verification::Tampered(_))
+    }
+}
+
+/// The plugin name `package.xml` was written for, and its file hashes by path.
+fn parse_package_manifest(xml_content: &str) -> Result<(Option<String>, BTreeMap<String, String>), String> {
+    let mut name = None;
+    let mut files = BTreeMap::new();
+    let mut reader = Reader::from_str(xml_content);
+    reader.trim_text(true);
+
+    let mut buf = Vec::new();
+    let mut in_name = false;
+    loop {
+        match reader.read_event_into(&mut buf) {
+            Ok(Event::Empty(ref e)) | Ok(Event::Start(ref e)) if e.name().as_ref() == b"file" => {
+                let attribute = |name: &str| e.try_get_attribute(name).ok().flatten()
+                    .and_then(|a| a.unescape_value().ok().map(|v| v.to_string()));
+                match (attribute("path"), attribute("sha256")) {
+                    (Some(path), Some(hash)) => { files.insert(path, hash); },
+                    _ => return Err(format!("{} has a <file> without path or sha256", PACKAGE_MANIFEST)),
+                }
+            },
+            Ok(Event::Start(ref e)) => in_name = e.name().as_ref() == b"name",
+            Ok(Event::Text(ref e)) if in_name => name = Some(e.unescape().unwrap_or_default().trim().to_string()),
+            Ok(Event::End(_)) => in_name = false,
+            Ok(Event::Eof) => break,
+            Err(e) => return Err(format!("Invalid {}: {}", PACKAGE_MANIFEST, e)),
+            _ => {}
+        }
+        buf.clear();
+    }
+    Ok((name, files))
+}
+
+/// Compares actual file hashes against `package.xml` and checks `package.sig` and that
+/// the package was made for the plugin `name`. Files the package does not list are
+/// tampering in an archive; once installed only extra `.lua` files are, because
+/// plugins keep saves and caches in their own folder.
+fn verify(package_xml: Option<&str>, signature: Option<&str>, hashes: &BTreeMap<String, String>, name: &str, installed: bool, trusted: &TrustedKeys) -> Verification {
+    let Some(package_xml) = package_xml else {
+        return Verification::Unverified(None);
+    };
+    let (signed_name, listed) = match parse_package_manifest(package_xml) {
+        Ok(parsed) => parsed,
+        Err(e) => return Verification::Tampered(e),
+    };
+    if let Some(signed_name) = signed_name.as_deref().filter(|signed_name| *signed_name != name) {
+        return Verification::Tampered(format!("package is for '{}', not '{}'", signed_name, name));
+    }
+    for (path, hash) in &listed {
+        match hashes.get(path) {
+            None => return Verification::Tampered(format!("{} is missing", path)),
+            Some(actual) if actual != hash => return Verification::Tampered(format!("{} was modified", path)),
+            _ => {}
+        }
+    }
+    let extra = hashes.keys()
+        .filter(|path| !listed.contains_key(*path))
+        .find(|path| !installed || path.ends_with(".lua"));
+    if let Some(extra) = extra {
+        return Verification::Tampered(format!("{} is not part of the package", extra));
+    }
+    let Some(signature) = signature else {
+        return Verification::Unverified(None);
+    };
+    if signed_name.is_none() {
+        return Verification::Tampered(format!("{} does not name the plugin it was signed for", PACKAGE_MANIFEST));
+    }
+    match signing::verify(signature, package_xml.as_bytes()) {
+        Ok(key) => match trusted.name_of(&key) {
+            Some(name) => Verification::Verified(name.to_string()),
+            None => Verification::Unverified(Some(key)),
+        },
+        Err(e) => Verification::Tampered(e),
+    }
+}
+
+/// A cheap summary of a folder to tell whether it changed since it was last verified:
+/// how many files and folders it has and the newest modification time among them.
+pub fn folder_stamp(dir: &Path) -> (usize, SystemTime) {
+    let mut stamp = (0, SystemTime::UNIX_EPOCH);
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        if let Ok(modified) = fs::metadata(&dir).and_then(|m| m.modified()) {
+            stamp.1 = stamp.1.max(modified);
+        }
+        for entry in fs::read_dir(&dir).into_iter().flatten().flatten() {
+            stamp.0 += 1;
+            match entry.metadata() {
+                Ok(metadata) if metadata.is_dir() => pending.push(entry.path()),
+                Ok(metadata) => stamp.1 = stamp.1.max(metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH)),
+                Err(_) => {},
+            }
+        }
+    }
+    stamp
+}
+
+/// Re-checks an installed plugin against the package files kept from its install.
+pub fn verify_installed(plugin_dir: &Path, trusted: &TrustedKeys) -> Verification {
+    let package_xml = fs::read_to_string(plugin_dir.join(PACKAGE_MANIFEST)).ok();
+    let signature = fs::read_to_string(plugin_dir.join(PACKAGE_SIGNATURE)).ok();
+    if package_xml.is_none() {
+        return Verification::Unverified(None);
+    }
+    let mut files = Vec::new();
+    if let Err(e) = collect_files(plugin_dir, "", &[], &mut files) {
+        return Verification::Tampered(e);
+    }
+    let mut hashes = BTreeMap::new();
+    for file in files {
+        if file == PACKAGE_MANIFEST || file == PACKAGE_SIGNATURE {
+            continue;
+        }
+        match fs::read(plugin_dir.join(&file)) {
+            Ok(data) => { hashes.insert(file, sha256_hex(&data)); },
+            Err(e) => return Verification::Tampered(format!("Failed to read {}: {}", file, e)),
+        }
+    }
+    let name = plugin_dir.file_name().unwrap_or_default().to_string_lossy();
+    verify(package_xml.as_deref(), signature.as_deref(), &hashes, &name, true, trusted)
+}
+
+/// What an archive would install, shown to the user before anything touches the disk.
+pub struct PackagePreview {
+    pub archive: PathBuf,
+    /// Folder name the plugin is installed under.
+    pub name: String,
+    pub manifest: Manifest,
+    pub files: Vec<String>,
+    pub total_size: u64,
+    /// The plugin already installed under the same name, if any.
+    pub existing: Option<(PathBuf, Manifest)>,
+    pub verification: Verification,
+    prefix: PathBuf,
+    package_xml: Option<String>,
+    signature: Option<String>,
+}
+
+impl PackagePreview {
+    /// Where the plugin ends up: over the existing copy, or a new folder in `plugins`.
+    pub fn target(&self, plugins_dir: &Path) -> PathBuf {
+        match &self.existing {
+            Some((path, _)) => path.clone(),
+            None => plugins_dir.join(&self.name),
+        }
+    }
+}
+
+pub(crate) fn open_archive(path: &Path) -> Result<ZipArchive<File>, String> {
+    let file = File::open(path).map_err(|e| format!("Failed to open {:?}: {}", path, e))?;
+    ZipArchive::new(file).map_err(|e| format!("{:?} is not a valid zip archive: {}", path, e))
+}
+
+pub(crate) fn valid_plugin_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name.starts_with('.')
+        && name.chars().all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+}
+
+/// Whether `version` can go into a file name: no path separators and no `..`.
+pub(crate) fn valid_version(version: &str) -> bool {
+    !version.is_empty()
+        && !version.contains("..")
+        && version.chars().all(|c| c.is_alphanumeric() || matches!(c, '.' | '-' | '_' | '+'))
+}
+
+/// Strips a trailing `-<version>` from an archive file stem ("mod-1.2.0" -> "mod").
+pub(crate) fn name_from_stem(stem: &str) -> String {
+    match stem.rsplit_once('-') {
+        Some((name, version)) if version.starts_with(|c: char| c.is_ascii_digit()) => name.to_string(),
+        _ => stem.to_string(),
+    }
+}
+
+/// Copies an archive entry into `out`, taking what it unpacks to off `budget`. The
+/// sizes in an archive's headers are not trusted; the data itself is counted.
+fn copy_capped(entry: impl Read, out: &mut impl Write, budget: &mut u64) -> Result<(), String> {
+    let copied = io::copy(&mut entry.take(*budget + 1), out)
+        .map_err(|e| format!("Failed to read archive entry: {}", e))?;
+    if copied > *budget {
+        return Err(format!("Archive unpacks to more than {} MB", MAX_UNPACKED_SIZE / 1024 / 1024));
+    }
+    *budget -= copied;
+    Ok(())
+}
+
+/// Reads an archive's manifest and file list. The plugin's `index.xml` may sit at the
+/// root of the archive or inside a single top-level folder.
+pub fn inspect(archive_path: &Path, search_dirs: &[PathBuf], trusted: &TrustedKeys) -> Result<PackagePreview, String> {
+    let mut archive = open_archive(archive_path)?;
+    if archive.len() > MAX_ENTRIES {
+        return Err(format!("Archive has more than {} entries", MAX_ENTRIES));
+    }
+    let mut entries = Vec::new();
+    for i in 0..archive.len() {
+        let file = archive.by_index(i).map_err(|e| format!("Failed to read archive entry: {}", e))?;
+        let path = file.enclosed_name()
+            .ok_or_else(|| format!("Archive entry '{}' points outside the plugin folder", file.name()))?;
+        if !file.is_dir() {
+            entries.push((i, path, file.size()));
+        }
+    }
+    if entries.iter().map(|(_, _, size)| *size).sum::<u64>() > MAX_UNPACKED_SIZE {
+        return Err(format!("Archive unpacks to more than {} MB", MAX_UNPACKED_SIZE / 1024 / 1024));
+    }
+
+    let (index_entry, index_path) = entries.iter()
+        .filter(|(_, path, _)| path.file_name().is_some_and(|n| n == "index.xml") && path.components().count() <= 2)
+        .min_by_key(|(_, path, _)| path.components().count())
+        .map(|(i, path, _)| (*i, path.clone()))
+        .ok_or("Archive does not contain an index.xml")?;
+    let prefix = index_path.parent().map(Path::to_path_buf).unwrap_or_default();
+
+    let mut xml_content = String::new();
+    archive.by_index(index_entry)
+        .map_err(|e| format!("Failed to read index.xml: {}", e))?
+        .read_to_string(&mut xml_content)
+        .map_err(|e| format!("Failed to read index.xml: {}", e))?;
+    let manifest = Manifest::parse(&xml_content)?;
+    if manifest.name.is_empty() {
+        return Err("index.xml is missing a <name>".to_string());
+    }
+
+    let name = match prefix.file_name() {
+        Some(folder) => folder.to_string_lossy().to_string(),
+        None => name_from_stem(&archive_path.file_stem().unwrap_or_default().to_string_lossy()),
+    };
+    if !valid_plugin_name(&name) {
+        return Err(format!("'{}' is not a valid plugin folder name", name));
+    }
+
+    let mut budget = MAX_UNPACKED_SIZE;
+    let mut read_entry = |archive: &mut ZipArchive<File>, i: usize| -> Result<Vec<u8>, String> {
+        let mut data = Vec::new();
+        let entry = archive.by_index(i).map_err(|e| format!("Failed to read archive entry: {}", e))?;
+        copy_capped(entry, &mut data, &mut budget)?;
+        Ok(data)
+    };
+    let mut files = Vec::new();
+    let mut hashes = BTreeMap::new();
+    let mut package_xml = None;
+    let mut signature = None;
+    let mut total_size = 0;
+    for (i, path, _) in &entries {
+        if path == Path::new(PACKAGE_MANIFEST) {
+            package_xml = Some(String::from_utf8_lossy(&read_entry(&mut archive, *i)?).to_string());
+        } else if path == Path::new(PACKAGE_SIGNATURE) {
+            signature = Some(String::from_utf8_lossy(&read_entry(&mut archive, *i)?).to_string());
+        } else if let Ok(relative) = path.strip_prefix(&prefix) {
+            let relative = relative.to_string_lossy().replace('\\', "/");
+            let data = read_entry(&mut archive, *i)?;
+            total_size += data.len() as u64;
+            hashes.insert(relative.clone(), sha256_hex(&data));
+            files.push(relative);
+        }
+    }
+    files.sort();
+    let signed_name = package_xml.as_deref()
+        .and_then(|xml| parse_package_manifest(xml).ok())
+        .and_then(|(signed_name, _)| signed_name);
+    if let Some(signed_name) = signed_name.filter(|signed_name| *signed_name != name) {
+        return Err(format!("Package is for '{}' but would be installed as '{}'", signed_name, name));
+    }
+    let verification = verify(package_xml.as_deref(), signature.as_deref(), &hashes, &name, false, trusted);
+
+    let existing = search_dirs.iter()
+        .map(|dir| dir.join(&name))
+        .find(|path| path.is_dir())
+        .map(|path| {
+            let manifest = Manifest::load(&path).unwrap_or_default();
+            (path, manifest)
+        });
+
+    Ok(PackagePreview {
+        archive: archive_path.to_path_buf(),
+        name,
+        manifest,
+        files,
+        total_size,
+        existing,
+        verification,
+        prefix,
+        package_xml,
+        signature,
+    })
+}
+
+fn extract(preview: &PackagePreview, destination: &Path) -> Result<(), String> {
+    let mut archive = open_archive(&preview.archive)?;
+    if archive.len() > MAX_ENTRIES {
+        return Err(format!("Archive has more than {} entries", MAX_ENTRIES));
+    }
+    let mut budget = MAX_UNPACKED_SIZE;
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i).map_err(|e| format!("Failed to read archive entry: {}", e))?;
+        let Some(path) = file.enclosed_name() else {
+            continue;
+        };
+        let Ok(relative) = path.strip_prefix(&preview.prefix) else {
+            continue;
+        };
+        if relative == Path::new(PACKAGE_MANIFEST) || relative == Path::new(PACKAGE_SIGNATURE) {
+            continue;
+        }
+        let out_path = destination.join(relative);
+        if file.is_dir() {
+            fs::create_dir_all(&out_path).map_err(|e| format!("Failed to create {:?}: {}", out_path, e))?;
+            continue;
+        }
+        if let Some(parent) = out_path.parent() {
+            fs::create_dir_all(parent).map_err(|e| format!("Failed to create {:?}: {}", parent, e))?;
+        }
+        let mut out = File::create(&out_path).map_err(|e| format!("Failed to create {:?}: {}", out_path, e))?;
+        copy_capped(&mut file, &mut out, &mut budget).map_err(|e| format!("Failed to extract {:?}: {}", relative, e))?;
+    }
+    // keep the hash list and signature so the installed copy can be re-checked later
+    for (name, content) in [(PACKAGE_MANIFEST, &preview.package_xml), (PACKAGE_SIGNATURE, &preview.signature)] {
+        if let Some(content) = content {
+            fs::write(destination.join(name), content).map_err(|e| format!("Failed to write {}: {}", name, e))?;
+        }
+    }
+    Ok(())
+}
+
+/// Extracts next to the final location first and only then swaps folders, so a
+/// failed install never leaves a half-written or missing plugin behind. Tampered
+/// packages are refused unless `allow_tampered` is set.
+pub fn install(preview: &PackagePreview, plugins_dir: &Path, allow_tampered: bool) -> Result<PathBuf, String> {
+    if let Verification::Tampered(reason) = &preview.verification {
+        if !allow_tampered {
+            return Err(format!("Package was tampered with: {}", reason));
+        }
+    }
+    let target = preview.target(plugins_dir);
+    let parent = target.parent().ok_or("Invalid plugin path")?;
+    let staging = parent.join(format!(".install-{}", preview.name));
+    let backup = parent.join(format!(".backup-{}", preview.name));
+    let _ = fs::remove_dir_all(&staging);
+    let _ = fs::remove_dir_all(&backup);
+
+    if let Err(e) = extract(preview, &staging) {
+        let _ = fs::remove_dir_all(&staging);
+        return Err(e);
+    }
+    if target.exists() {
+        if let Err(e) = fs::rename(&target, &backup) {
+            let _ = fs::remove_dir_all(&staging);
+            return Err(format!("Failed to move the old version aside: {}", e));
+        }
+    }
+    if let Err(e) = fs::rename(&staging, &target) {
+        let _ = fs::rename(&backup, &target);
+        let _ = fs::remove_dir_all(&staging);
+        return Err(format!("Failed to move the new version into place: {}", e));
+    }
+    let _ = fs::remove_dir_all(&backup);
+    Ok(target)
+}
+
+/// One line of an `.orbitignore`: `*`/`?` globs, a trailing `/` for folders only and
+/// a leading `/` to anchor the pattern at the plugin root.
+struct IgnoreRule {
+    pattern: String,
+    anchored: bool,
+    dir_only: bool,
+}
+
+impl IgnoreRule {
+    fn parse(line: &str) -> Option<IgnoreRule> {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            return None;
+        }
+        let dir_only = line.ends_with('/');
+        let line = line.trim_end_matches('/');
+        let anchored = line.starts_with('/') || line.contains('/');
+        Some(IgnoreRule { pattern: line.trim_start_matches('/').to_string(), anchored, dir_only })
+    }
+
+    fn matches(&self, relative: &str, is_dir: bool) -> bool {
+        if self.dir_only && !is_dir {
+            return false;
+        }
+        if self.anchored {
+            glob_match(&self.pattern, relative)
+        } else {
+            glob_match(&self.pattern, relative.rsplit('/').next().unwrap_or(relative))
+        }
+    }
+}
+
+pub(crate) fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    let mut backtrack = None;
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, t));
+            p += 1;
+        } else if let Some((star, matched)) = backtrack {
+            p = star + 1;
+            t = matched + 1;
+            backtrack = Some((star, matched + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+fn collect_files(dir: &Path, relative: &str, rules: &[IgnoreRule], files: &mut Vec<String>) -> Result<(), String> {
+    let mut entries: Vec<_> = fs::read_dir(dir)
+        .map_err(|e| format!("Failed to read {:?}: {}", dir, e))?
+        .flatten()
+        .collect();
+    entries.sort_by_key(|entry| entry.file_name());
+    for entry in entries {
+        let name = entry.file_name().to_string_lossy().to_string();
+        let path = if relative.is_empty() { name.clone() } else { format!("{}/{}", relative, name) };
+        let is_dir = entry.path().is_dir();
+        if name == ".git" || name == IGNORE_FILE || rules.iter().any(|rule| rule.matches(&path, is_dir)) {
+            continue;
+        }
+        if relative.is_empty() && (name == PACKAGE_MANIFEST || name == PACKAGE_SIGNATURE) {
+            continue;
+        }
+        if is_dir {
+            collect_files(&entry.path(), &path, rules, files)?;
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+fn sha256_hex(data: &[u8]) -> String {
+    signing::to_hex(&Sha256::digest(data))
+}
+
+fn write_archive(plugin_dir: &Path, archive_path: &Path, name: &str, version: &str, files: &[String], key: Option<&SigningKey>) -> Result<(), String> {
+    let archive_file = File::create(archive_path)
+        .map_err(|e| format!("Failed to create {:?}: {}", archive_path, e))?;
+    let mut zip = ZipWriter::new(archive_file);
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+    let mut package_xml = format!(
+        "<package>\n    <name>{}</name>\n    <version>{}</version>\n    <files>\n",
+        escape(name), escape(version)
+    );
+    for file in files {
+        let data = fs::read(plugin_dir.join(file)).map_err(|e| format!("Failed to read {}: {}", file, e))?;
+        package_xml.push_str(&format!("        <file path=\"{}\" sha256=\"{}\"/>\n", escape(file), sha256_hex(&data)));
+        zip.start_file(format!("{}/{}", name, file), options)
+            .and_then(|_| zip.write_all(&data).map_err(Into::into))
+            .map_err(|e| format!("Failed to add {} to the archive: {}", file, e))?;
+    }
+    package_xml.push_str("    </files>\n</package>\n");
+    zip.start_file(PACKAGE_MANIFEST, options)
+        .and_then(|_| zip.write_all(package_xml.as_bytes()).map_err(Into::into))
+        .map_err(|e| format!("Failed to add {} to the archive: {}", PACKAGE_MANIFEST, e))?;
+    if let Some(key) = key {
+        zip.start_file(PACKAGE_SIGNATURE, options)
+            .and_then(|_| zip.write_all(signing::sign(key, package_xml.as_bytes()).as_bytes()).map_err(Into::into))
+            .map_err(|e| format!("Failed to add {} to the archive: {}", PACKAGE_SIGNATURE, e))?;
+    }
+    zip.finish().map_err(|e| format!("Failed to finish {:?}: {}", archive_path, e))?;
+    Ok(())
+}
+
+/// Checks a plugin folder and writes `<name>-<version>.zip` into `out_dir`. The archive
+/// holds the plugin under a `<name>/` folder plus a `package.xml` listing every
+/// file's SHA-256, signed with `key` when one is given. `.zip` files are never packed.
+pub fn pack(plugin_dir: &Path, out_dir: &Path, key: Option<&SigningKey>) -> Result<PathBuf, String> {
+    let manifest = Manifest::load(plugin_dir)?;
+    if manifest.name.is_empty() {
+        return Err("index.xml is missing a <name>".to_string());
+    }
+    if manifest.version.is_empty() {
+        return Err("index.xml is missing a <version>".to_string());
+    }
+    if !valid_version(&manifest.version) {
+        return Err(format!("'{}' is not a valid version: use letters, digits, '.', '-', '_' or '+'", manifest.version));
+    }
+    if let Some(logo) = &manifest.logo {
+        if !plugin_dir.join(logo).is_file() {
+            return Err(format!("Logo {:?} declared in index.xml does not exist", logo));
+        }
+    }
+    let name = fs::canonicalize(plugin_dir)
+        .ok()
+        .and_then(|dir| dir.file_name().map(|n| n.to_string_lossy().to_string()))
+        .ok_or_else(|| format!("Invalid plugin directory {:?}", plugin_dir))?;
+    if !valid_plugin_name(&name) {
+        return Err(format!("'{}' is not a valid plugin folder name", name));
+    }
+
+    let rules: Vec<IgnoreRule> = fs::read_to_string(plugin_dir.join(IGNORE_FILE))
+        .unwrap_or_default()
+        .lines()
+        .filter_map(IgnoreRule::parse)
+        .collect();
+    let mut files = Vec::new();
+    collect_files(plugin_dir, "", &rules, &mut files)?;
+    // archives from earlier runs, when packing into the plugin folder itself
+    files.retain(|file| !file.to_ascii_lowercase().ends_with(".zip"));
+    if !files.iter().any(|f| f == "index.xml") {
+        return Err("index.xml is excluded by .orbitignore".to_string());
+    }
+
+    let lua = mlua::Lua::new();
+    let mut errors = Vec::new();
+    for file in files.iter().filter(|f| f.ends_with(".lua")) {
+        let code = fs::read_to_string(plugin_dir.join(file))
+            .map_err(|e| format!("Failed to read {}: {}", file, e))?;
+        if let Err(e) = lua.load(&code).set_name(format!("@{}", file)).into_function() {
+            errors.push(e.to_string());
+        }
+    }
+    if !errors.is_empty() {
+        return Err(format!("Lua syntax errors:\n{}", errors.join("\n")));
+    }
+
+    let archive_path = out_dir.join(format!("{}-{}.zip", name, manifest.version));
+    if let Err(e) = write_archive(plugin_dir, &archive_path, &name, &manifest.version, &files, key) {
+        let _ = fs::remove_file(&archive_path);
+        return Err(e);
+    }
+    Ok(archive_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_dir::TestDir;
+
+    const INDEX: &str = "<plugin><name>Snake</name><version>1.2.0</version></plugin>";
+
+    /// Writes a zip holding `entries` as name and contents.
+    fn archive(dir: &TestDir, file: &str, entries: &[(&str, &str)]) -> PathBuf {
+        let path = dir.path().join(file);
+        let mut zip = ZipWriter::new(File::create(&path).unwrap());
+        for (name, contents) in entries {
+            zip.start_file(*name, SimpleFileOptions::default()).unwrap();
+            zip.write_all(contents.as_bytes()).unwrap();
+        }
+        zip.finish().unwrap();
+        path
+    }
+
+    #[test]
+    fn preview_finds_the_plugin_in_the_archive() {
+        let dir = TestDir::new("package-preview");
+        let flat = archive(&dir, "snake-1.2.0.zip", &[("index.xml", INDEX), ("index.lua", "return 1")]);
+        let preview = inspect(&flat, &[], &TrustedKeys::default()).unwrap();
+        assert_eq!(preview.name, "snake");
+        assert_eq!(preview.manifest.version, "1.2.0");
+        assert_eq!(preview.files, ["index.lua", "index.xml"]);
+        assert_eq!(preview.total_size, (INDEX.len() + "return 1".len()) as u64);
+        assert!(preview.existing.is_none());
+        assert!(matches!(preview.verification, Verification::Unverified(None)));
+
+        let nested = archive(&dir, "download.zip", &[("worm/index.xml", INDEX), ("worm/sprites/head.png", "png")]);
+        let preview = inspect(&nested, &[], &TrustedKeys::default()).unwrap();
+        assert_eq!(preview.name, "worm");
+        assert_eq!(preview.files, ["index.xml", "sprites/head.png"]);
+
+        let empty = archive(&dir, "empty.zip", &[("readme.txt", "")]);
+        assert!(inspect(&empty, &[], &TrustedKeys::default()).is_err());
+    }
+
+    #[test]
+    fn entries_outside_the_plugin_are_refused() {
+        let dir = TestDir::new("package-slip");
+        for entry in ["../evil.lua", "snake/../../evil.lua", "/etc/evil.lua"] {
+            let slip = archive(&dir, "snake.zip", &[("index.xml", INDEX), (entry, "os.execute('rm -rf ~')")]);
+            let error = inspect(&slip, &[], &TrustedKeys::default()).err();
+            assert!(error.is_some_and(|e| e.contains("points outside")), "{} was accepted", entry);
+        }
+        assert!(!dir.path().parent().unwrap().join("evil.lua").exists());
+    }
+
+    #[test]
+    fn install_replaces_the_existing_copy() {
+        let dir = TestDir::new("package-install");
+        let plugins = dir.path().join("plugins");
+        dir.write("plugins/snake/index.xml", INDEX);
+        dir.write("plugins/snake/old.lua", "return 0");
+        let path = archive(&dir, "snake-1.3.0.zip", &[("snake/index.xml", INDEX), ("snake/new.lua", "return 1")]);
+        let preview = inspect(&path, std::slice::from_ref(&plugins), &TrustedKeys::default()).unwrap();
+        assert_eq!(preview.existing.as_ref().map(|(path, _)| path.clone()), Some(plugins.join("snake")));
+
+        assert_eq!(install(&preview, &plugins, false).unwrap(), plugins.join("snake"));
+        assert!(!plugins.join("snake/old.lua").exists());
+        assert_eq!(fs::read_to_string(plugins.join("snake/new.lua")).unwrap(), "return 1");
+        let left: Vec<_> = fs::read_dir(&plugins).unwrap().flatten().map(|entry| entry.file_name()).collect();
+        assert_eq!(left, ["snake"]);
+    }
+
+    #[test]
+    fn tampered_packages_are_not_installed() {
+        let dir = TestDir::new("package-tampered");
+        let plugins = dir.path().join("plugins");
+        let listing = "<package><files><file path=\"index.xml\" sha256=\"00\"/></files></package>";
+        let path = archive(&dir, "snake.zip", &[("snake/index.xml", INDEX), ("package.xml", listing)]);
+        let preview = inspect(&path, std::slice::from_ref(&plugins), &TrustedKeys::default()).unwrap();
+        assert!(preview.verification.is_tampered());
+        assert!(install(&preview, &plugins, false).is_err());
+        assert!(!plugins.join("snake").exists());
+    }
+
+    /// A `package.xml` for `name` listing `files` with their hashes.
+    fn listing(name: &str, files: &[(&str, &str)]) -> String {
+        let files: String = files.iter()
+            .map(|(path, contents)| format!("<file path=\"{}\" sha256=\"{}\"/>", path, sha256_hex(contents.as_bytes())))
+            .collect();
+        format!("<package><name>{}</name><version>1.2.0</version><files>{}</files></package>", name, files)
+    }
+
+    fn key() -> SigningKey {
+        SigningKey::from_bytes(&[7; 32])
+    }
+
+    fn key_hex() -> String {
+        signing::to_hex(key().verifying_key().as_bytes())
+    }
+
+    #[test]
+    fn signatures_are_checked_against_trusted_keys() {
+        let dir = TestDir::new("package-signed");
+        dir.write("snake/index.xml", INDEX);
+        dir.write("snake/index.lua", "return 1");
+        let path = pack(&dir.path().join("snake"), dir.path(), Some(&key())).unwrap();
+
+        let untrusted = inspect(&path, &[], &TrustedKeys::default()).unwrap();
+        assert!(matches!(untrusted.verification, Verification::Unverified(Some(ref key)) if *key == key_hex()));
+
+        let mut trusted = TrustedKeys::default();
+        trusted.trust(dir.path(), "alice", &key_hex()).unwrap();
+        let preview = inspect(&path, &[], &trusted).unwrap();
+        assert!(matches!(preview.verification, Verification::Verified(ref name) if name == "alice"));
+        assert!(matches!(inspect(&path, &[], &TrustedKeys::load(dir.path())).unwrap().verification, Verification::Verified(_)));
+    }
+
+    #[test]
+    fn archives_must_match_their_signed_listing() {
+        let dir = TestDir::new("package-listing");
+        let files = [("snake/index.xml", INDEX), ("snake/index.lua", "return 1")];
+        let package_xml = listing("snake", &[("index.xml", INDEX), ("index.lua", "return 1")]);
+        let signature = signing::sign(&key(), package_xml.as_bytes());
+        let mut trusted = TrustedKeys::default();
+        trusted.trust(dir.path(), "alice", &key_hex()).unwrap();
+        let check = |entries: &[(&str, &str)]| {
+            let path = archive(&dir, "snake.zip", entries);
+            inspect(&path, &[], &trusted).unwrap().verification
+        };
+        // the signed files with `changes` applied; empty contents drop the file
+        let signed = |changes: &[(&'static str, &'static str)]| {
+            let mut entries = vec![("package.xml", package_xml.as_str()), ("package.sig", signature.as_str())];
+            entries.extend(files.iter().filter(|(path, _)| !changes.iter().any(|(changed, _)| changed == path)));
+            entries.extend(changes.iter().filter(|(_, contents)| !contents.is_empty()));
+            entries
+        };
+
+        assert!(matches!(check(&signed(&[])), Verification::Verified(_)));
+        let modified = check(&signed(&[("snake/index.lua", "os.execute('evil')")]));
+        assert!(matches!(modified, Verification::Tampered(ref reason) if reason == "index.lua was modified"));
+        let added = check(&signed(&[("snake/extra.lua", "return 2")]));
+        assert!(matches!(added, Verification::Tampered(ref reason) if reason == "extra.lua is not part of the package"));
+        let missing = check(&signed(&[("snake/index.lua", "")]));
+        assert!(matches!(missing, Verification::Tampered(ref reason) if reason == "index.lua is missing"));
+
+        let forged = signing::sign(&key(), b"<package>something else</package>");
+        let mut entries = signed(&[]);
+        entries[1] = ("package.sig", forged.as_str());
+        assert!(matches!(check(&entries), Verification::Tampered(ref reason) if reason.contains("does not match")));
+    }
+
+    #[test]
+    fn signed_packages_install_only_under_their_own_name() {
+        let dir = TestDir::new("package-renamed");
+        let package_xml = listing("snake", &[("index.xml", INDEX)]);
+        let signature = signing::sign(&key(), package_xml.as_bytes());
+        let renamed = archive(&dir, "worm.zip", &[
+            ("worm/index.xml", INDEX), ("package.xml", &package_xml), ("package.sig", &signature),
+        ]);
+        let error = inspect(&renamed, &[], &TrustedKeys::default()).err().unwrap();
+        assert!(error.contains("is for 'snake'"), "{}", error);
+    }
+
+    #[test]
+    fn installed_plugins_are_rechecked() {
+        let dir = TestDir::new("package-installed");
+        let plugins = dir.path().join("plugins");
+        dir.write("snake/index.xml", INDEX);
+        dir.write("snake/index.lua", "return 1");
+        let path = pack(&dir.path().join("snake"), dir.path(), Some(&key())).unwrap();
+        let mut trusted = TrustedKeys::default();
+        trusted.trust(dir.path(), "alice", &key_hex()).unwrap();
+        let installed = install(&inspect(&path, &[], &trusted).unwrap(), &plugins, false).unwrap();
+        let verification = || verify_installed(&installed, &trusted);
+        assert!(matches!(verification(), Verification::Verified(_)));
+
+        // saves and caches next to the code are fine, new code is not
+        fs::write(installed.join("slot1.orbit"), "score=3").unwrap();
+        assert!(matches!(verification(), Verification::Verified(_)));
+        fs::write(installed.join("extra.lua"), "return 2").unwrap();
+        assert!(verification().is_tampered());
+        fs::remove_file(installed.join("extra.lua")).unwrap();
+
+        fs::write(installed.join("index.lua"), "return 2").unwrap();
+        assert!(verification().is_tampered());
+        fs::remove_file(installed.join("index.lua")).unwrap();
+        assert!(verification().is_tampered());
+
+        // the signature covers the folder name
+        let renamed = plugins.join("worm");
+        fs::write(installed.join("index.lua"), "return 1").unwrap();
+        fs::rename(&installed, &renamed).unwrap();
+        assert!(verify_installed(&renamed, &trusted).is_tampered());
+    }
+
+    #[test]
+    fn pack_leaves_out_earlier_archives() {
+        let dir = TestDir::new("package-pack");
+        dir.write("snake/index.xml", INDEX);
+        dir.write("snake/index.lua", "return 1");
+        let plugin_dir = dir.path().join("snake");
+        let first = pack(&plugin_dir, &plugin_dir, None).unwrap();
+        assert_eq!(first, plugin_dir.join("snake-1.2.0.zip"));
+        let second = pack(&plugin_dir, &plugin_dir, None).unwrap();
+        let preview = inspect(&second, &[], &TrustedKeys::default()).unwrap();
+        assert_eq!(preview.files, ["index.lua", "index.xml"]);
+    }
+
+    #[test]
+    fn unpacking_stops_at_the_budget() {
+        let mut out = Vec::new();
+        let mut budget = 4;
+        assert!(copy_capped(&b"hello"[..], &mut out, &mut budget).is_err());
+        let mut budget = 8;
+        copy_capped(&b"hello"[..], &mut out, &mut budget).unwrap();
+        assert_eq!(budget, 3);
+        assert!(copy_capped(&b"more"[..], &mut out, &mut budget).is_err());
+    }
+
+    #[test]
+    fn glob_matches_wildcards() {
+        assert!(glob_match("v*", "v1.10.0"));
+        assert!(!glob_match("v*", "1.10.0"));
+        assert!(glob_match("*.png", "player.png"));
+        assert!(!glob_match("*.png", "player.png.bak"));
+        assert!(glob_match("?at", "cat"));
+        assert!(!glob_match("?at", "at"));
+        assert!(glob_match("a*b*c", "axxbyybc"));
+        assert!(glob_match("a*b", "ab"));
+        assert!(!glob_match("a*b", "abc"));
+        assert!(glob_match("*", ""));
+        assert!(glob_match("", ""));
+        assert!(!glob_match("", "a"));
+        assert!(glob_match("release-*-final", "release-2.0-final"));
+    }
+}
@@ -0,0 +1,182 @@
+use quick_xml::escape::escape;
+use quick_xml::events::Event;
+use quick_xml::reader::Reader;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Capabilities a plugin has to declare in `index.xml` and the user has to grant.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Permission {
+    Filesystem,
+    Process,
+    Network,
+    Clipboard,
+    Window,
+}
+
+impl Permission {
+    pub const ALL: [Permission; 5] = [
+        Permission::Filesystem,
+        Permission::Process,
+        Permission::Network,
+        Permission::Clipboard,
+        Permission::Window,
+    ];
+
+    pub fn parse(name: &str) -> Option<Permission> {
+        Permission::ALL.into_iter().find(|permission| permission.name() == name)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Permission::Filesystem => "filesystem",
+            Permission::Process => "process",
+            Permission::Network => "network",
+            Permission::Clipboard => "clipboard",
+            Permission::Window => "window",
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            Permission::Filesystem => "Read and write files outside its own folder",
+            Permission::Process => "Run other programs on this computer",
+            Permission::Network => "Connect to the internet",
+            Permission::Clipboard => "Copy text to the clipboard",
+            Permission::Window => "Control the game window (title, size, fullscreen)",
+        }
+    }
+}
+
+/// Permissions the user has approved, persisted in `CONFIG/permissions.xml`.
+#[derive(Default)]
+pub struct Grants {
+    path: PathBuf,
+    plugins: BTreeMap<String, Vec<Permission>>,
+}
+
+impl Grants {
+    pub fn load(config_dir: &Path) -> Grants {
+        let path = config_dir.join("permissions.xml");
+        let mut plugins: BTreeMap<String, Vec<Permission>> = BTreeMap::new();
+        if let Ok(xml_content) = fs::read_to_string(&path) {
+            let mut reader = Reader::from_str(&xml_content);
+            reader.trim_text(true);
+
+            let mut buf = Vec::new();
+            let mut current_plugin = None;
+            let mut current_element = String::new();
+            loop {
+                match reader.read_event_into(&mut buf) {
+                    Ok(Event::Start(ref e)) => {
+                        current_element = String::from_utf8_lossy(e.name().as_ref()).to_string();
+                        if current_element == "plugin" {
+                            current_plugin = e.try_get_attribute("name").ok().flatten()
+                                .and_then(|a| a.unescape_value().ok().map(|v| v.to_string()));
+                        }
+                    },
+                    Ok(Event::Text(ref e)) if current_element == "permission" => {
+                        let text = e.unescape().unwrap_or_default().to_string();
+                        if let (Some(plugin), Some(permission)) = (&current_plugin, Permission::parse(&text)) {
+                            plugins.entry(plugin.clone()).or_default().push(permission);
+                        }
+                    },
+                    Ok(Event::Eof) => break,
+                    Err(_) => break,
+                    _ => {}
+                }
+                buf.clear();
+            }
+        }
+        Grants { path, plugins }
+    }
+
+    pub fn granted(&self, plugin: &str) -> &[Permission] {
+        self.plugins.get(plugin).map(|p| p.as_slice()).unwrap_or(&[])
+    }
+
+    pub fn is_granted(&self, plugin: &str, permission: Permission) -> bool {
+        self.granted(plugin).contains(&permission)
+    }
+
+    /// Requested permissions that have not been approved yet.
+    pub fn missing(&self, plugin: &str, requested: &[Permission]) -> Vec<Permission> {
+        requested.iter().copied().filter(|p| !self.is_granted(plugin, *p)).collect()
+    }
+
+    pub fn grant(&mut self, plugin: &str, permissions: &[Permission]) -> Result<(), String> {
+        let previous = self.plugins.get(plugin).cloned();
+        let granted = self.plugins.entry(plugin.to_string()).or_default();
+        for permission in permissions {
+            if !granted.contains(permission) {
+                granted.push(*permission);
+            }
+        }
+        granted.sort();
+        self.save_or_restore(plugin, previous)
+    }
+
+    pub fn revoke_all(&mut self, plugin: &str) -> Result<(), String> {
+        let previous = self.plugins.remove(plugin);
+        self.save_or_restore(plugin, previous)
+    }
+
+    /// Saves a change to `plugin`'s grants, putting `previous` back when that fails so
+    /// the session does not run with grants that were never written.
+    fn save_or_restore(&mut self, plugin: &str, previous: Option<Vec<Permission>>) -> Result<(), String> {
+        let saved = self.save();
+        if saved.is_err() {
+            match previous {
+                Some(previous) => self.plugins.insert(plugin.to_string(), previous),
+                None => self.plugins.remove(plugin),
+            };
+        }
+        saved
+    }
+
+    fn save(&self) -> Result<(), String> {
+        let mut xml = String::from("<grants>\n");
+        for (plugin, permissions) in &self.plugins {
+            xml.push_str(&format!("    <plugin name=\"{}\">\n", escape(plugin)));
+            for permission in permissions {
+                xml.push_str(&format!("        <permission>{}</permission>\n", permission.name()));
+            }
+            xml.push_str("    </plugin>\n");
+        }
+        xml.push_str("</grants>\n");
+        fs::write(&self.path, xml)
+            .map_err(|e| format!("Failed to save permissions to {:?}: {}", self.path, e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_dir::TestDir;
+
+    #[test]
+    fn grants_are_saved_and_loaded() {
+        let dir = TestDir::new("grants");
+        let mut grants = Grants::load(dir.path());
+        grants.grant("snake", &[Permission::Network, Permission::Clipboard]).unwrap();
+        grants.grant("snake", &[Permission::Clipboard]).unwrap();
+        let loaded = Grants::load(dir.path());
+        assert_eq!(loaded.granted("snake"), [Permission::Network, Permission::Clipboard]);
+        assert_eq!(loaded.missing("snake", &[Permission::Process, Permission::Network]), [Permission::Process]);
+    }
+
+    #[test]
+    fn failed_saves_leave_grants_unchanged() {
+        let dir = TestDir::new("grants-unsaved");
+        let mut grants = Grants::load(dir.path());
+        grants.grant("snake", &[Permission::Network]).unwrap();
+        fs::remove_dir_all(dir.path()).unwrap();
+        assert!(grants.grant("snake", &[Permission::Process]).is_err());
+        assert_eq!(grants.granted("snake"), [Permission::Network]);
+        assert!(grants.grant("lib", &[Permission::Process]).is_err());
+        assert!(grants.granted("lib").is_empty());
+        assert!(grants.revoke_all("snake").is_err());
+        assert!(grants.is_granted("snake", Permission::Network));
+    }
+}
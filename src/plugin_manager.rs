@@ -1,204 +1,1736 @@
-use eframe::egui; // TODO: actually implement the plugin autoupdate
-use std::path::PathBuf;
+use eframe::egui;
+use std::path::{Path, PathBuf};
 use std::fs;
-use quick_xml::reader::Reader;
-use quick_xml::events::Event;
+use orbit::conflicts::{self, Conflict, Kind};
+use orbit::load_order::LoadOrder;
+use orbit::manifest::{Dependency, Manifest, UpdateSource};
+use orbit::package::{self, PackagePreview, Verification};
+use orbit::permissions::{Grants, Permission};
+use orbit::plugin_state::PluginStates;
+use orbit::profiles::{LockedPlugin, Profile, Profiles};
+use orbit::plugin_settings::{PluginSettings, SettingSpec, SettingValue};
+use orbit::registry::{self, Catalog, RegistryEntry, Registries};
+use orbit::signing::TrustedKeys;
+use orbit::trash::{self, TrashEntry};
+use crate::check::{self, Finding, Severity};
+use crate::markdown;
+use crate::settings_screen::{setting_widget, SettingInput};
+use orbit::updater::{self, Job, Preference, UpdateCheck, UpdateEvent, UpdateOutcome, UpdatePrefs, UpdateTarget};
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::sync::mpsc;
+use std::time::SystemTime;
 enum Action {
-    DeletePlugin(usize),
+    DeletePlugins(Vec<usize>),
     ConfirmDelete(String),
     CancelDelete,
+    ConfirmBulkDelete(bool),
     TogglePlugin(usize),
+    SetEnabled(Vec<usize>, bool),
+    ReviewPermissions(usize),
+    ApprovePermissions(usize),
+    DenyPermissions,
+    InstallFromFile,
+    ConfirmInstall,
+    CancelInstall,
+    TrustSigner(String),
+    CheckUpdates(Vec<usize>),
+    Update(Vec<usize>),
+    Revert(usize),
+    SetBeta(usize, bool),
+    Pin(usize),
+    Unpin(usize),
+    LoadRegistries,
+    AddRegistry,
+    RemoveRegistry(String),
+    InstallFromRegistry(String),
+    OpenSettings(usize),
+    OpenPluginPage(String, String),
+    CheckPlugin(usize),
+    CloseCheck,
+    CloseSettings,
+    /// A value being dragged or typed, shown but not saved until `SetSetting`.
+    EditSetting(String, SettingValue),
+    SetSetting(String, SettingValue),
+    ResetSettings,
+    CaptureKey(String),
+    SelectPlugin(usize),
+    MoveInLoadOrder(String, bool),
+    PreferPlugin(String, Vec<String>),
+    SaveProfile,
+    SwitchProfile(String),
+    RemoveProfile(String),
+    ExportLockfile,
+    ImportLockfile,
+    CloseDetails,
+    RestoreFromTrash(usize),
+    DeleteFromTrash(usize),
+    ConfirmDeleteFromTrash(Option<PathBuf>),
+    ConfirmEmptyTrash(bool),
+    EmptyTrash,
+    /// The retention being edited; saved by `SaveRetention` once the edit ends.
+    SetRetention(u64),
+    SaveRetention,
+}
+#[derive(Clone, Copy, PartialEq)]
+enum Filter {
+    All,
+    Enabled,
+    Disabled,
+    HasUpdate,
+    Errored,
+}
+impl Filter {
+    const ALL: [Filter; 5] = [Filter::All, Filter::Enabled, Filter::Disabled, Filter::HasUpdate, Filter::Errored];
+    fn label(self) -> &'static str {
+        match self {
+            Filter::All => "All",
+            Filter::Enabled => "Enabled",
+            Filter::Disabled => "Disabled",
+            Filter::HasUpdate => "Has update",
+            Filter::Errored => "Errored",
+        }
+    }
+}
+#[derive(Clone, Copy, PartialEq)]
+enum SortBy {
+    Name,
+    Date,
+    Size,
+    LoadOrder,
+}
+impl SortBy {
+    const ALL: [SortBy; 4] = [SortBy::Name, SortBy::Date, SortBy::Size, SortBy::LoadOrder];
+    fn label(self) -> &'static str {
+        match self {
+            SortBy::Name => "Name",
+            SortBy::Date => "Last modified",
+            SortBy::Size => "Largest first",
+            SortBy::LoadOrder => "Load order",
+        }
+    }
+}
+#[derive(PartialEq)]
+enum Tab {
+    Installed,
+    Browse,
+    Trash,
+}
+enum UpdateStatus {
+    Queued,
+    Running(Option<(usize, usize)>),
+    Available(UpdateCheck),
+    Finished(String),
+    Failed(String),
 }
 pub struct PluginManager {
     pub show: bool,
     plugins: Vec<Plugin>,
     config_dir: PathBuf,
-    selected_plugin: Option<usize>,
+    selected_plugin: Option<(usize, PluginDetails)>,
     confirm_delete: Option<String>,
+    search: String,
+    filter: Filter,
+    sort_by: SortBy,
+    /// Plugins ticked for bulk actions, by folder name.
+    selected: BTreeSet<String>,
+    confirm_bulk_delete: bool,
+    profiles: Profiles,
+    new_profile: String,
+    /// Profile waiting for its plugins to be installed at the locked revisions.
+    pending_profile: Option<Profile>,
+    load_order: LoadOrder,
+    /// Modules and assets provided by more than one enabled plugin.
+    /// Found when the load order section is opened; `None` until then or after a change.
+    conflicts: Option<Vec<Conflict>>,
+    grants: Grants,
+    states: PluginStates,
+    permission_prompt: Option<usize>,
+    install_preview: Option<PackagePreview>,
+    allow_tampered: bool,
+    /// The user confirmed an unknown signer's fingerprint came from its author.
+    signer_checked: bool,
+    trusted_keys: TrustedKeys,
+    /// Last verification of each installed plugin, redone only when its folder changes.
+    verifications: HashMap<PathBuf, ((usize, SystemTime), Verification)>,
+    error: Option<String>,
+    update_receiver: Option<mpsc::Receiver<UpdateEvent>>,
+    update_status: HashMap<String, UpdateStatus>,
+    update_prefs: UpdatePrefs,
+    pin_edits: HashMap<String, String>,
+    tab: Tab,
+    registries: Registries,
+    new_registry: String,
+    catalog: Option<Catalog>,
+    catalog_receiver: Option<mpsc::Receiver<Catalog>>,
+    registry_logos: HashMap<String, egui::TextureHandle>,
+    download_receiver: Option<(String, registry::Downloads)>,
+    settings_page: Option<(usize, PluginSettings)>,
+    capturing_key: Option<String>,
+    editing_setting: Option<(String, SettingValue)>,
+    setting_changes: Vec<(String, String, SettingValue)>,
+    /// Settings pages running plugins added with `orbit.menu.add_settings_page`, by plugin.
+    plugin_pages: BTreeMap<String, Vec<String>>,
+    opened_page: Option<(String, String)>,
+    /// Result of the last Check: plugin display name and what was found.
+    check_report: Option<(String, Vec<Finding>)>,
+    trash: Vec<TrashEntry>,
+    trash_retention: u64,
+    trash_save_data: bool,
+    confirm_empty_trash: bool,
+    /// Trash entry waiting for "Delete forever" to be confirmed.
+    confirm_trash_delete: Option<PathBuf>,
 }
 struct Plugin {
     name: String,
     display_name: String,
     description: String,
+    version: String,
+    author: String,
+    homepage: String,
+    tags: Vec<String>,
     logo_path: Option<PathBuf>,
     path: PathBuf,
+    /// When the plugin folder itself last changed.
+    modified: SystemTime,
+    /// Bytes on disk, measured only once the list is sorted by size.
+    size: Option<u64>,
+    /// Why `index.xml` could not be read, if it could not.
+    load_error: Option<String>,
     enabled: bool,
+    permissions: Vec<Permission>,
+    verification: Verification,
+    update: Option<UpdateSource>,
+    settings: Vec<SettingSpec>,
+    dependencies: Vec<Dependency>,
     texture: Option<egui::TextureHandle>,
 }
+/// What the details pane shows beyond the manifest, read from disk when it opens.
+struct PluginDetails {
+    readme: Option<String>,
+    changelog: Option<String>,
+    revision: Option<String>,
+    files: usize,
+    disk_size: u64,
+    data_size: u64,
+}
+impl PluginDetails {
+    fn load(config_dir: &Path, plugin: &Plugin) -> PluginDetails {
+        let (files, disk_size) = dir_usage(&plugin.path);
+        let (_, data_size) = dir_usage(&config_dir.join("data").join(&plugin.name));
+        PluginDetails {
+            readme: read_doc(&plugin.path, "readme"),
+            changelog: read_doc(&plugin.path, "changelog"),
+            revision: updater::installed_revision(&plugin.path),
+            files,
+            disk_size,
+            data_size,
+        }
+    }
+}
+/// Reads `README.md`, `readme.txt`, `README` and the like from a plugin folder.
+fn read_doc(dir: &Path, stem: &str) -> Option<String> {
+    fs::read_dir(dir).ok()?.flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file())
+        .filter(|path| path.file_stem().is_some_and(|s| s.to_string_lossy().eq_ignore_ascii_case(stem)))
+        .filter(|path| path.extension().is_none_or(|ext| ext == "md" || ext == "txt"))
+        .find_map(|path| fs::read_to_string(path).ok())
+}
+/// Number of files under `dir` and their total size in bytes.
+fn dir_usage(dir: &Path) -> (usize, u64) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return (0, 0);
+    };
+    let mut usage = (0, 0);
+    for entry in entries.flatten() {
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
+        if file_type.is_dir() {
+            let (files, size) = dir_usage(&entry.path());
+            usage.0 += files;
+            usage.1 += size;
+        } else if let Ok(metadata) = entry.metadata() {
+            usage.0 += 1;
+            usage.1 += metadata.len();
+        }
+    }
+    usage
+}
+fn format_size(bytes: u64) -> String {
+    match bytes {
+        0..1024 => format!("{} B", bytes),
+        1024..1048576 => format!("{:.1} KB", bytes as f64 / 1024.0),
+        _ => format!("{:.1} MB", bytes as f64 / 1048576.0),
+    }
+}
 impl Plugin {
-    fn load_metadata(path: &PathBuf) -> (String, String, Option<PathBuf>) {
-        let index_path = path.join("index.xml");
-        let mut display_name = String::new();
-        let mut description = String::new();
-        let mut logo_path = None;
-        if let Ok(xml_content) = fs::read_to_string(index_path) {
-            let mut reader = Reader::from_str(&xml_content);
-            reader.trim_text(true);
-            
-            let mut buf = Vec::new();
-            let mut current_element = String::new();
-            
-            loop {
-                match reader.read_event_into(&mut buf) {
-                    Ok(Event::Start(ref e)) => {
-                        current_element = String::from_utf8_lossy(e.name().as_ref()).to_string();
-                    },
-                    Ok(Event::Text(ref e)) => {
-                        let text = e.unescape().unwrap_or_default().to_string();
-                        match current_element.as_str() {
-                            "name" => display_name = text,
-                            "description" => description = text,
-                            "logo" => logo_path = Some(PathBuf::from(text)),
-                            _ => {}
-                        }
-                    },
-                    Ok(Event::Eof) => break,
-                    Err(_) => break,
-                    _ => {}
-                }
-            }
+    fn load_metadata(path: &PathBuf) -> (Manifest, Option<String>) {
+        match Manifest::load(path) {
+            Ok(manifest) => (manifest, None),
+            Err(e) => (Manifest::default(), Some(e)),
         }
-        
-        (
-            display_name.trim().to_string(),
-            description.trim().to_string(),
-            logo_path
-        )
     }
     fn load_logo(&mut self, ctx: &egui::Context) {
         if self.texture.is_none() && self.logo_path.is_some() {
             let logo_path = self.path.join(self.logo_path.as_ref().unwrap());
             if let Ok(image_data) = fs::read(&logo_path) {
-                if let Ok(image) = image::load_from_memory(&image_data) {
-                    let size = [image.width() as _, image.height() as _];
-                    let image_buffer = image.to_rgba8();
-                    let pixels = image_buffer.as_flat_samples();
-                    let color_image = egui::ColorImage::from_rgba_unmultiplied(
-                        size,
-                        pixels.as_slice(),
-                    );
-                    self.texture = Some(ctx.load_texture(
-                        &self.name,
-                        color_image,
-                        egui::TextureOptions::default(),
-                    ));
-                }
+                self.texture = logo_texture(ctx, &self.name, &image_data);
             }
         }
     }
 }
+fn logo_texture(ctx: &egui::Context, name: &str, image_data: &[u8]) -> Option<egui::TextureHandle> {
+    let image = image::load_from_memory(image_data).ok()?;
+    let size = [image.width() as _, image.height() as _];
+    let image_buffer = image.to_rgba8();
+    let pixels = image_buffer.as_flat_samples();
+    let color_image = egui::ColorImage::from_rgba_unmultiplied(
+        size,
+        pixels.as_slice(),
+    );
+    Some(ctx.load_texture(
+        name,
+        color_image,
+        egui::TextureOptions::default(),
+    ))
+}
+/// A public key in groups of four hex digits, for comparing by eye.
+fn fingerprint(key: &str) -> String {
+    let groups: Vec<&str> = key.as_bytes().chunks(4).map(|chunk| std::str::from_utf8(chunk).unwrap_or("")).collect();
+    groups.join(" ")
+}
+fn verification_label(ui: &mut egui::Ui, verification: &Verification) {
+    match verification {
+        Verification::Verified(signer) => {
+            ui.colored_label(egui::Color32::GREEN, format!("Verified (signed by {})", signer));
+        },
+        Verification::Unverified(Some(_)) => {
+            ui.colored_label(egui::Color32::GRAY, "Unverified (signed by an untrusted key)");
+        },
+        Verification::Unverified(None) => {
+            ui.colored_label(egui::Color32::GRAY, "Unverified");
+        },
+        Verification::Tampered(reason) => {
+            ui.colored_label(egui::Color32::RED, format!("Tampered: {}", reason));
+        },
+    }
+}
 impl Default for PluginManager {
     fn default() -> Self {
         let config_dir = PathBuf::from(std::env::var("CONFIG").unwrap_or_default());
         Self {
             show: false,
             plugins: Vec::new(),
-            config_dir,
             selected_plugin: None,
             confirm_delete: None,
+            search: String::new(),
+            filter: Filter::All,
+            sort_by: SortBy::Name,
+            selected: BTreeSet::new(),
+            confirm_bulk_delete: false,
+            profiles: Profiles::load(&config_dir),
+            new_profile: String::new(),
+            pending_profile: None,
+            load_order: LoadOrder::load(&config_dir),
+            conflicts: None,
+            grants: Grants::load(&config_dir),
+            states: PluginStates::load(&config_dir),
+            permission_prompt: None,
+            install_preview: None,
+            allow_tampered: false,
+            signer_checked: false,
+            trusted_keys: TrustedKeys::load(&config_dir),
+            verifications: HashMap::new(),
+            error: None,
+            update_receiver: None,
+            update_status: HashMap::new(),
+            update_prefs: UpdatePrefs::load(&config_dir),
+            pin_edits: HashMap::new(),
+            tab: Tab::Installed,
+            registries: Registries::load(&config_dir),
+            new_registry: String::new(),
+            catalog: None,
+            catalog_receiver: None,
+            registry_logos: HashMap::new(),
+            download_receiver: None,
+            settings_page: None,
+            capturing_key: None,
+            editing_setting: None,
+            setting_changes: Vec::new(),
+            plugin_pages: BTreeMap::new(),
+            opened_page: None,
+            check_report: None,
+            trash: Vec::new(),
+            trash_retention: trash::load_retention(&config_dir),
+            trash_save_data: false,
+            confirm_empty_trash: false,
+            confirm_trash_delete: None,
+            config_dir,
         }
     }
 }
 impl PluginManager {
     pub fn refresh_plugins(&mut self) {
         self.plugins.clear();
+        self.selected_plugin = None;
+        self.grants = Grants::load(&self.config_dir);
+        self.trusted_keys = TrustedKeys::load(&self.config_dir);
+        self.states = PluginStates::load(&self.config_dir);
         let plugins_dir = self.config_dir.join("plugins");
-        let wastebasket_dir = self.config_dir.join("wastebasket");
         
-        if let Err(_) = fs::create_dir_all(&plugins_dir) {
+        if let Err(e) = fs::create_dir_all(&plugins_dir) {
+            self.error = Some(format!("Failed to create {:?}: {}", plugins_dir, e));
             return;
         }
-        if let Err(_) = fs::create_dir_all(&wastebasket_dir) {
-            return;
+        if let Err(e) = self.states.migrate_wastebasket(&self.config_dir) {
+            self.error = Some(e);
+        }
+        if let Err(e) = trash::purge_expired(&self.config_dir, self.trash_retention) {
+            self.error = Some(e);
         }
+        self.trash = trash::list(&self.config_dir);
         if let Ok(entries) = fs::read_dir(&plugins_dir) {
             for entry in entries.flatten() {
                 if entry.path().is_dir() {
                     if let Some(name) = entry.path().file_name() {
                         let name = name.to_string_lossy().into_owned();
-                        let (display_name, description, logo_path) = Plugin::load_metadata(&entry.path());
+                        if name.starts_with('.') {
+                            continue;
+                        }
+                        let (manifest, load_error) = Plugin::load_metadata(&entry.path());
+                        let verification = self.verify(&entry.path());
                         self.plugins.push(Plugin {
                             name: name.clone(),
-                            display_name: if display_name.is_empty() { name } else { display_name },
-                            description,
-                            logo_path,
+                            display_name: if manifest.name.is_empty() { name.clone() } else { manifest.name },
+                            description: manifest.description,
+                            version: manifest.version,
+                            author: manifest.author,
+                            homepage: manifest.homepage,
+                            tags: manifest.tags,
+                            logo_path: manifest.logo,
                             path: entry.path(),
-                            enabled: true,
+                            modified: entry.metadata().and_then(|m| m.modified()).unwrap_or(SystemTime::UNIX_EPOCH),
+                            size: None,
+                            load_error,
+                            enabled: self.states.is_enabled(&name),
+                            permissions: manifest.permissions,
+                            verification,
+                            update: manifest.update,
+                            settings: manifest.settings,
+                            dependencies: manifest.dependencies,
                             texture: None,
                         });
                     }
                 }
             }
         }
-        if let Ok(entries) = fs::read_dir(&wastebasket_dir) {
-            for entry in entries.flatten() {
-                if entry.path().is_dir() {
-                    if let Some(name) = entry.path().file_name() {
-                        let name = name.to_string_lossy().into_owned();
-                        let (display_name, description, logo_path) = Plugin::load_metadata(&entry.path());
-                        self.plugins.push(Plugin {
-                            name: name.clone(),
-                            display_name: if display_name.is_empty() { name } else { display_name },
-                            description,
-                            logo_path,
-                            path: entry.path(),
-                            enabled: false,
-                            texture: None,
+        self.selected.retain(|name| self.plugins.iter().any(|plugin| plugin.name == *name));
+        self.forget_conflicts();
+    }
+    /// Verifies an installed plugin, hashing its files only when they changed since last time.
+    fn verify(&mut self, dir: &Path) -> Verification {
+        let stamp = package::folder_stamp(dir);
+        if let Some((_, verification)) = self.verifications.get(dir).filter(|(cached, _)| *cached == stamp) {
+            return verification.clone();
+        }
+        let verification = package::verify_installed(dir, &self.trusted_keys);
+        self.verifications.insert(dir.to_path_buf(), (stamp, verification.clone()));
+        verification
+    }
+    fn toggle_plugin(&mut self, index: usize) {
+        if let Some(plugin) = self.plugins.get(index) {
+            if !plugin.enabled && !self.grants.missing(&plugin.name, &plugin.permissions).is_empty() {
+                self.permission_prompt = Some(index);
+                return;
+            }
+        }
+        if let Some(plugin) = self.plugins.get_mut(index) {
+            match self.states.set_enabled(&plugin.name, !plugin.enabled) {
+                Ok(()) => plugin.enabled = !plugin.enabled,
+                Err(e) => self.error = Some(e),
+            }
+        }
+        self.forget_conflicts();
+    }
+    fn approve_permissions(&mut self, index: usize) {
+        self.permission_prompt = None;
+        if let Some(plugin) = self.plugins.get(index) {
+            if let Err(e) = self.grants.grant(&plugin.name, &plugin.permissions) {
+                self.error = Some(e);
+                return;
+            }
+            if !plugin.enabled {
+                self.toggle_plugin(index);
+            }
+        }
+    }
+    fn draw_permission_prompt(&mut self, ctx: &egui::Context, pending_actions: &mut Vec<Action>) {
+        let Some(index) = self.permission_prompt else {
+            return;
+        };
+        let Some(plugin) = self.plugins.get(index) else {
+            self.permission_prompt = None;
+            return;
+        };
+        egui::Window::new("Permissions")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+            .show(ctx, |ui| {
+                ui.label(format!("{} wants to:", plugin.display_name));
+                ui.add_space(5.0);
+                for permission in &plugin.permissions {
+                    let granted = self.grants.is_granted(&plugin.name, *permission);
+                    let text = format!("{} ({})", permission.description(), permission.name());
+                    if granted {
+                        ui.label(text);
+                    } else {
+                        ui.label(egui::RichText::new(text).strong());
+                    }
+                }
+                ui.add_space(10.0);
+                ui.horizontal(|ui| {
+                    let allow_text = if plugin.enabled { "Allow" } else { "Allow and enable" };
+                    if ui.button(allow_text).clicked() {
+                        pending_actions.push(Action::ApprovePermissions(index));
+                    }
+                    if ui.button("Don't allow").clicked() {
+                        pending_actions.push(Action::DenyPermissions);
+                    }
+                });
+            });
+    }
+    fn preview_archive(&mut self, path: PathBuf) {
+        let search_dirs = [self.config_dir.join("plugins")];
+        match package::inspect(&path, &search_dirs, &self.trusted_keys) {
+            Ok(preview) => {
+                self.install_preview = Some(preview);
+                self.allow_tampered = false;
+                self.signer_checked = false;
+                self.error = None;
+            },
+            Err(e) => self.error = Some(e),
+        }
+    }
+    /// Records the installed plugins as they are now.
+    fn snapshot_profile(&self, name: &str) -> Profile {
+        let plugins = self.plugins.iter()
+            .map(|plugin| {
+                let settings = PluginSettings::load(&self.config_dir, &plugin.name, &plugin.settings);
+                LockedPlugin {
+                    name: plugin.name.clone(),
+                    enabled: plugin.enabled,
+                    version: plugin.version.clone(),
+                    revision: updater::installed_commit(&plugin.path),
+                    source: plugin.update.as_ref().map(|source| source.url.clone()),
+                    preference: self.update_prefs.get(&plugin.name),
+                    settings: plugin.settings.iter()
+                        .filter_map(|spec| settings.get(&spec.key).map(|value| (spec.key.clone(), value.to_string())))
+                        .collect(),
+                }
+            })
+            .collect();
+        Profile { name: name.to_string(), plugins }
+    }
+    fn save_profile(&mut self) {
+        let name = self.new_profile.trim().to_string();
+        let profile = self.snapshot_profile(&name);
+        let saved = self.profiles.save_profile(profile).and_then(|_| self.profiles.set_active(&name));
+        match saved {
+            Ok(()) => self.new_profile.clear(),
+            Err(e) => self.error = Some(e),
+        }
+    }
+    /// Switches to a profile. With `exact`, plugins that are missing or checked out at
+    /// another revision are first fetched from their source at the locked revision.
+    fn apply_profile(&mut self, profile: Profile, exact: bool) {
+        if self.update_receiver.is_some() {
+            self.error = Some("Wait for the running updates to finish before switching profiles".to_string());
+            return;
+        }
+        let plugins_dir = self.config_dir.join("plugins");
+        let targets: Vec<UpdateTarget> = profile.plugins.iter()
+            .filter(|_| exact)
+            .filter_map(|locked| {
+                let (source, revision) = (locked.source.as_ref()?, locked.revision.as_ref()?);
+                let path = plugins_dir.join(&locked.name);
+                if updater::installed_commit(&path).as_ref() == Some(revision) {
+                    return None;
+                }
+                Some(UpdateTarget {
+                    name: locked.name.clone(),
+                    path,
+                    source: UpdateSource { url: source.clone(), ..Default::default() },
+                    preference: Preference { pin: Some(revision.clone()), beta: false },
+                    backup: self.backup_path(&locked.name),
+                })
+            })
+            .collect();
+        if targets.is_empty() {
+            self.finish_profile(&profile);
+            return;
+        }
+        for target in &targets {
+            self.update_status.insert(target.name.clone(), UpdateStatus::Queued);
+        }
+        self.update_receiver = Some(updater::spawn(targets, Job::Apply));
+        self.pending_profile = Some(profile);
+    }
+    /// Applies a profile's enabled states, pins and settings to the installed plugins.
+    /// Installed plugins the profile does not list are disabled. The profile only
+    /// becomes the active one when all of it applied.
+    fn finish_profile(&mut self, profile: &Profile) {
+        let mut enable = Vec::new();
+        let mut disable = Vec::new();
+        for (index, plugin) in self.plugins.iter().enumerate() {
+            match profile.get(&plugin.name) {
+                Some(locked) if locked.enabled => enable.push(index),
+                _ => disable.push(index),
+            }
+        }
+        self.set_enabled(disable, false);
+        self.set_enabled(enable, true);
+        let mut problems = Vec::new();
+        for locked in &profile.plugins {
+            let Some(plugin) = self.plugins.iter().find(|plugin| plugin.name == locked.name) else {
+                let reason = match self.update_status.get(&locked.name) {
+                    Some(UpdateStatus::Failed(e)) => e.clone(),
+                    _ => "not installed".to_string(),
+                };
+                problems.push(format!("{} ({})", locked.name, reason));
+                continue;
+            };
+            if let Some(UpdateStatus::Failed(e)) = self.update_status.get(&locked.name) {
+                problems.push(format!("{} ({})", locked.name, e));
+            }
+            if let Err(e) = self.update_prefs.set(&locked.name, locked.preference.clone()) {
+                problems.push(format!("{} ({})", locked.name, e));
+            }
+            let mut settings = PluginSettings::load(&self.config_dir, &plugin.name, &plugin.settings);
+            for (key, text) in &locked.settings {
+                let Some(value) = plugin.settings.iter().find(|spec| spec.key == *key).and_then(|spec| spec.parse(text)) else {
+                    continue;
+                };
+                if settings.get(key) == Some(&value) {
+                    continue;
+                }
+                match settings.set(key, value.clone()) {
+                    Ok(()) => self.setting_changes.push((plugin.name.clone(), key.clone(), value)),
+                    Err(e) => problems.push(format!("{} ({})", locked.name, e)),
+                }
+            }
+        }
+        if !problems.is_empty() {
+            self.error = Some(format!("Profile {} was not fully applied: {}", profile.name, problems.join(", ")));
+        } else if let Err(e) = self.profiles.set_active(&profile.name) {
+            self.error = Some(e);
+        }
+    }
+    fn export_lockfile(&mut self) {
+        let name = self.profiles.active.clone().unwrap_or_else(|| "default".to_string());
+        let picked = rfd::FileDialog::new()
+            .set_title("Export lockfile")
+            .add_filter("Orbit lockfile", &["xml"])
+            .set_file_name(&format!("{}.lock.xml", name))
+            .save_file();
+        if let Some(path) = picked {
+            if let Err(e) = self.snapshot_profile(&name).export(&path) {
+                self.error = Some(e);
+            }
+        }
+    }
+    fn import_lockfile(&mut self) {
+        let picked = rfd::FileDialog::new()
+            .set_title("Import lockfile")
+            .add_filter("Orbit lockfile", &["xml"])
+            .pick_file();
+        let Some(path) = picked else {
+            return;
+        };
+        match Profile::import(&path) {
+            Ok(profile) => {
+                if let Err(e) = self.profiles.save_profile(profile.clone()) {
+                    self.error = Some(e);
+                }
+                self.apply_profile(profile, true);
+            },
+            Err(e) => self.error = Some(e),
+        }
+    }
+    fn draw_profiles(&mut self, ui: &mut egui::Ui, pending_actions: &mut Vec<Action>) {
+        let title = match &self.profiles.active {
+            Some(active) => format!("Profile: {}", active),
+            None => "Profiles".to_string(),
+        };
+        egui::CollapsingHeader::new(title).id_salt("profiles").show(ui, |ui| {
+            for profile in &self.profiles.profiles {
+                ui.horizontal(|ui| {
+                    let active = self.profiles.active.as_ref() == Some(&profile.name);
+                    let enabled = profile.plugins.iter().filter(|locked| locked.enabled).count();
+                    ui.label(format!("{} ({} enabled)", profile.name, enabled));
+                    if ui.add_enabled(!active, egui::Button::new("Switch").small()).clicked() {
+                        pending_actions.push(Action::SwitchProfile(profile.name.clone()));
+                    }
+                    if ui.small_button("Remove").clicked() {
+                        pending_actions.push(Action::RemoveProfile(profile.name.clone()));
+                    }
+                });
+            }
+            ui.horizontal(|ui| {
+                ui.add(egui::TextEdit::singleline(&mut self.new_profile).hint_text("profile name"));
+                if ui.add_enabled(!self.new_profile.trim().is_empty(), egui::Button::new("Save current setup")).clicked() {
+                    pending_actions.push(Action::SaveProfile);
+                }
+            });
+            ui.horizontal(|ui| {
+                if ui.button("Export lockfile...").clicked() {
+                    pending_actions.push(Action::ExportLockfile);
+                }
+                if ui.button("Import lockfile...").clicked() {
+                    pending_actions.push(Action::ImportLockfile);
+                }
+            });
+        });
+    }
+    fn install_from_file(&mut self) {
+        let picked = rfd::FileDialog::new()
+            .set_title("Install plugin")
+            .add_filter("Plugin archive", &["zip"])
+            .pick_file();
+        if let Some(path) = picked {
+            self.preview_archive(path);
+        }
+    }
+    fn confirm_install(&mut self) {
+        let Some(preview) = self.install_preview.take() else {
+            return;
+        };
+        match package::install(&preview, &self.config_dir.join("plugins"), self.allow_tampered) {
+            Ok(path) => {
+                self.refresh_plugins();
+                let index = self.plugins.iter().position(|p| p.path == path);
+                if let Some(index) = index {
+                    let plugin = &self.plugins[index];
+                    if plugin.enabled && !self.grants.missing(&plugin.name, &plugin.permissions).is_empty() {
+                        self.permission_prompt = Some(index);
+                    }
+                }
+            },
+            Err(e) => self.error = Some(format!("Failed to install {}: {}", preview.name, e)),
+        }
+    }
+    /// Archives dropped anywhere on the window open the install preview.
+    pub fn handle_dropped_files(&mut self, ctx: &egui::Context) {
+        let dropped = ctx.input(|i| i.raw.dropped_files.iter().find_map(|f| f.path.clone()));
+        if let Some(path) = dropped {
+            if !self.show {
+                self.show = true;
+                self.refresh_plugins();
+            }
+            self.preview_archive(path);
+        }
+    }
+    fn trust_signer(&mut self, key: String) {
+        let name = format!("key {}", &key[..8.min(key.len())]);
+        if let Err(e) = self.trusted_keys.trust(&self.config_dir, &name, &key) {
+            self.error = Some(e);
+            return;
+        }
+        self.verifications.clear();
+        if let Some(preview) = self.install_preview.take() {
+            self.preview_archive(preview.archive);
+        }
+        self.refresh_plugins();
+    }
+    fn draw_install_preview(&mut self, ctx: &egui::Context, pending_actions: &mut Vec<Action>) {
+        let Some(preview) = &self.install_preview else {
+            return;
+        };
+        let allow_tampered = &mut self.allow_tampered;
+        let signer_checked = &mut self.signer_checked;
+        egui::Window::new("Install plugin")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+            .show(ctx, |ui| {
+                ui.heading(&preview.manifest.name);
+                if !preview.manifest.version.is_empty() {
+                    ui.label(format!("Version {}", preview.manifest.version));
+                }
+                ui.label(&preview.manifest.description);
+                ui.add_space(5.0);
+                ui.label(format!("{} files, {:.1} KB", preview.files.len(), preview.total_size as f64 / 1024.0));
+                egui::CollapsingHeader::new("Files").show(ui, |ui| {
+                    egui::ScrollArea::vertical().max_height(150.0).show(ui, |ui| {
+                        for file in &preview.files {
+                            ui.monospace(file);
+                        }
+                    });
+                });
+                if !preview.manifest.permissions.is_empty() {
+                    let names: Vec<_> = preview.manifest.permissions.iter().map(|p| p.name()).collect();
+                    ui.label(format!("Requests permissions: {}", names.join(", ")));
+                }
+                ui.add_space(5.0);
+                let install_text = match &preview.existing {
+                    Some((_, installed)) => {
+                        let installed_version = if installed.version.is_empty() { "unknown" } else { &installed.version };
+                        ui.colored_label(egui::Color32::YELLOW, format!(
+                            "'{}' is already installed (version {}). Installing replaces it.",
+                            preview.name, installed_version
+                        ));
+                        "Replace"
+                    },
+                    None => "Install",
+                };
+                verification_label(ui, &preview.verification);
+                match &preview.verification {
+                    Verification::Unverified(Some(key)) => {
+                        egui::CollapsingHeader::new("Trust this signer...").default_open(false).show(ui, |ui| {
+                            ui.label("Signer fingerprint:");
+                            ui.monospace(fingerprint(key));
+                            ui.colored_label(egui::Color32::YELLOW, "This key came from the package itself, so it does not \
+                                prove who made it. Only trust it if the fingerprint matches one the author \
+                                published somewhere you trust. Every package signed with it will then install \
+                                as verified.");
+                            ui.checkbox(signer_checked, "I compared the full fingerprint with the author's");
+                            if ui.add_enabled(*signer_checked, egui::Button::new("Trust this signer")).clicked() {
+                                pending_actions.push(Action::TrustSigner(key.clone()));
+                            }
                         });
+                    },
+                    Verification::Tampered(_) => {
+                        ui.checkbox(allow_tampered, "Install anyway (not recommended)");
+                    },
+                    _ => {}
+                }
+                ui.add_space(10.0);
+                ui.horizontal(|ui| {
+                    let can_install = !preview.verification.is_tampered() || *allow_tampered;
+                    if ui.add_enabled(can_install, egui::Button::new(install_text)).clicked() {
+                        pending_actions.push(Action::ConfirmInstall);
+                    }
+                    if ui.button("Cancel").clicked() {
+                        pending_actions.push(Action::CancelInstall);
                     }
+                });
+            });
+    }
+    fn start_updates(&mut self, indices: Vec<usize>, job: Job) {
+        if self.update_receiver.is_some() {
+            return;
+        }
+        let mut targets = Vec::new();
+        for index in indices {
+            if let Some(plugin) = self.plugins.get(index) {
+                if let Some(source) = &plugin.update {
+                    self.update_status.insert(plugin.name.clone(), UpdateStatus::Queued);
+                    targets.push(UpdateTarget {
+                        name: plugin.name.clone(),
+                        path: plugin.path.clone(),
+                        source: source.clone(),
+                        preference: self.update_prefs.get(&plugin.name),
+                        backup: self.backup_path(&plugin.name),
+                    });
+                }
+            }
+        }
+        if !targets.is_empty() {
+            self.update_receiver = Some(updater::spawn(targets, job));
+        }
+    }
+    fn set_preference(&mut self, index: usize, change: impl FnOnce(&mut Preference)) {
+        let Some(plugin) = self.plugins.get(index) else {
+            return;
+        };
+        let name = plugin.name.clone();
+        let mut preference = self.update_prefs.get(&name);
+        change(&mut preference);
+        if let Err(e) = self.update_prefs.set(&name, preference) {
+            self.error = Some(e);
+        }
+        // whatever was checked before was for the old channel
+        self.update_status.remove(&name);
+        self.pin_edits.remove(&name);
+    }
+    fn backup_path(&self, name: &str) -> PathBuf {
+        self.config_dir.join("backups").join(name)
+    }
+    fn revert_plugin(&mut self, index: usize) {
+        let Some(plugin) = self.plugins.get(index) else {
+            return;
+        };
+        let name = plugin.name.clone();
+        match updater::revert(&plugin.path, &self.backup_path(&name)) {
+            Ok(()) => {
+                self.update_status.insert(name, UpdateStatus::Finished("Reverted to the previous version".to_string()));
+                self.refresh_plugins();
+            },
+            Err(e) => self.error = Some(format!("Failed to revert {}: {}", name, e)),
+        }
+    }
+    fn poll_updates(&mut self, ctx: &egui::Context) {
+        let Some(receiver) = &self.update_receiver else {
+            return;
+        };
+        let mut done = false;
+        while let Ok(event) = receiver.try_recv() {
+            match event {
+                UpdateEvent::Started(plugin) => {
+                    self.update_status.insert(plugin, UpdateStatus::Running(None));
+                },
+                UpdateEvent::Progress { plugin, received, total } => {
+                    self.update_status.insert(plugin, UpdateStatus::Running(Some((received, total))));
+                },
+                UpdateEvent::Checked { plugin, result } => {
+                    let status = match result {
+                        Ok(check) if check.has_update() => UpdateStatus::Available(check),
+                        Ok(check) => UpdateStatus::Finished(format!(
+                            "Up to date: {} ({}) on {}",
+                            check.installed_version, check.available_revision, check.channel
+                        )),
+                        Err(e) => UpdateStatus::Failed(e),
+                    };
+                    self.update_status.insert(plugin, status);
+                },
+                UpdateEvent::Finished { plugin, result } => {
+                    let status = match result {
+                        Ok(UpdateOutcome::UpToDate(revision)) => UpdateStatus::Finished(format!("Up to date ({})", revision)),
+                        Ok(UpdateOutcome::Updated { from, to }) => UpdateStatus::Finished(format!("Updated {} -> {}", from, to)),
+                        Ok(UpdateOutcome::Reinstalled(revision)) => UpdateStatus::Finished(format!("Reinstalled at {}", revision)),
+                        Err(e) => UpdateStatus::Failed(e),
+                    };
+                    self.update_status.insert(plugin, status);
+                },
+                UpdateEvent::Done => done = true,
+            }
+        }
+        if done {
+            self.update_receiver = None;
+            self.refresh_plugins();
+            if let Some(profile) = self.pending_profile.take() {
+                self.finish_profile(&profile);
+            }
+        } else {
+            ctx.request_repaint();
+        }
+    }
+    fn load_registries(&mut self) {
+        if self.catalog_receiver.is_none() {
+            self.registry_logos.clear();
+            self.catalog_receiver = Some(registry::spawn_load(self.registries.sources.clone()));
+        }
+    }
+    fn install_from_registry(&mut self, id: String) {
+        let Some(catalog) = &self.catalog else {
+            return;
+        };
+        if self.download_receiver.is_some() {
+            return;
+        }
+        let installed: HashMap<String, String> = self.plugins.iter()
+            .map(|plugin| (plugin.name.clone(), plugin.version.clone()))
+            .collect();
+        match registry::plan_install(&id, catalog, &installed) {
+            Ok(plan) => {
+                let receiver = registry::spawn_download(plan, self.config_dir.join("downloads"));
+                self.download_receiver = Some((id, receiver));
+                self.error = None;
+            },
+            Err(e) => self.error = Some(e),
+        }
+    }
+    /// Installs downloaded archives in dependency order, stopping at the first failure.
+    fn install_downloads(&mut self, root: &str, archives: Vec<(RegistryEntry, PathBuf)>) {
+        let plugins_dir = self.config_dir.join("plugins");
+        let downloads_dir = self.config_dir.join("downloads");
+        let search_dirs = [plugins_dir.clone()];
+        for (entry, archive) in &archives {
+            let result = package::inspect(archive, &search_dirs, &self.trusted_keys).and_then(|preview| {
+                if preview.name != entry.id {
+                    return Err(format!("the archive contains {} instead", preview.name));
                 }
+                package::install(&preview, &plugins_dir, false)
+            });
+            if let Err(e) = result {
+                self.error = Some(format!("Failed to install {} {}: {}", entry.name, entry.version, e));
+                break;
+            }
+        }
+        for (_, archive) in &archives {
+            if archive.starts_with(&downloads_dir) {
+                let _ = fs::remove_file(archive);
+            }
+        }
+        self.refresh_plugins();
+        if let Some(index) = self.plugins.iter().position(|plugin| plugin.name == root) {
+            let plugin = &self.plugins[index];
+            if !self.grants.missing(&plugin.name, &plugin.permissions).is_empty() {
+                self.permission_prompt = Some(index);
             }
         }
     }
-    fn toggle_plugin(&mut self, index: usize) {
-        if let Some(plugin) = self.plugins.get_mut(index) {
-            let plugins_dir = self.config_dir.join("plugins");
-            let wastebasket_dir = self.config_dir.join("wastebasket");
-            
-            let target_dir = if plugin.enabled {
-                &wastebasket_dir
-            } else {
-                &plugins_dir
-            };
-
-            let new_path = target_dir.join(&plugin.name);
-            if let Ok(_) = fs::rename(&plugin.path, &new_path) {
-                plugin.enabled = !plugin.enabled;
-                plugin.path = new_path;
+    fn poll_browse(&mut self, ctx: &egui::Context) {
+        if let Some(receiver) = &self.catalog_receiver {
+            match receiver.try_recv() {
+                Ok(catalog) => {
+                    self.catalog = Some(catalog);
+                    self.catalog_receiver = None;
+                },
+                Err(mpsc::TryRecvError::Empty) => ctx.request_repaint(),
+                Err(mpsc::TryRecvError::Disconnected) => self.catalog_receiver = None,
+            }
+        }
+        if let Some((root, receiver)) = &self.download_receiver {
+            match receiver.try_recv() {
+                Ok(result) => {
+                    let root = root.clone();
+                    self.download_receiver = None;
+                    match result {
+                        Ok(archives) => self.install_downloads(&root, archives),
+                        Err(e) => self.error = Some(e),
+                    }
+                },
+                Err(mpsc::TryRecvError::Empty) => ctx.request_repaint(),
+                Err(mpsc::TryRecvError::Disconnected) => self.download_receiver = None,
             }
         }
     }
-    fn delete_plugin(&mut self, index: usize) {
+    fn draw_browse(&mut self, ctx: &egui::Context, ui: &mut egui::Ui, pending_actions: &mut Vec<Action>) {
+        let busy = self.catalog_receiver.is_some() || self.download_receiver.is_some();
+        egui::CollapsingHeader::new("Registries")
+            .default_open(self.registries.sources.is_empty())
+            .show(ui, |ui| {
+                for source in &self.registries.sources {
+                    ui.horizontal(|ui| {
+                        ui.label(source);
+                        if ui.small_button("Remove").clicked() {
+                            pending_actions.push(Action::RemoveRegistry(source.clone()));
+                        }
+                    });
+                }
+                ui.horizontal(|ui| {
+                    ui.add(egui::TextEdit::singleline(&mut self.new_registry).hint_text("registry file or URL"));
+                    if ui.add_enabled(!self.new_registry.trim().is_empty(), egui::Button::new("Add")).clicked() {
+                        pending_actions.push(Action::AddRegistry);
+                    }
+                    if ui.add_enabled(!busy, egui::Button::new("Reload")).clicked() {
+                        pending_actions.push(Action::LoadRegistries);
+                    }
+                });
+            });
+        if self.catalog_receiver.is_some() {
+            ui.label("Loading registries...");
+        }
+        if self.download_receiver.is_some() {
+            ui.label("Downloading...");
+        }
+        let Some(catalog) = &self.catalog else {
+            return;
+        };
+        for error in &catalog.errors {
+            ui.colored_label(egui::Color32::RED, error);
+        }
+        if catalog.entries.is_empty() && catalog.errors.is_empty() {
+            ui.label("No plugins listed. Add a registry to browse its plugins.");
+        }
+        ui.separator();
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            for entry in catalog.latest() {
+                ui.horizontal(|ui| {
+                    if !self.registry_logos.contains_key(&entry.id) {
+                        if let Some(texture) = catalog.logos.get(&entry.id)
+                            .and_then(|data| logo_texture(ctx, &format!("registry-{}", entry.id), data)) {
+                            self.registry_logos.insert(entry.id.clone(), texture);
+                        }
+                    }
+                    if let Some(texture) = self.registry_logos.get(&entry.id) {
+                        let size = 48.0;
+                        ui.add(egui::Image::new(texture).fit_to_exact_size(egui::vec2(size, size)));
+                        ui.add_space(10.0);
+                    }
+                    ui.vertical(|ui| {
+                        ui.heading(&entry.name);
+                        ui.label(&entry.description);
+                        ui.label(format!("Version {} from {}", entry.version, entry.registry));
+                        if !entry.dependencies.is_empty() {
+                            let names: Vec<_> = entry.dependencies.iter().map(|d| d.name.as_str()).collect();
+                            ui.label(format!("Requires: {}", names.join(", ")));
+                        }
+                        match self.plugins.iter().find(|plugin| plugin.name == entry.id) {
+                            None => {
+                                if ui.add_enabled(!busy, egui::Button::new("Install")).clicked() {
+                                    pending_actions.push(Action::InstallFromRegistry(entry.id.clone()));
+                                }
+                            },
+                            Some(plugin) if updater::compare_versions(&plugin.version, &entry.version) == Ordering::Less => {
+                                ui.horizontal(|ui| {
+                                    ui.label(format!("Installed: {}", plugin.version));
+                                    if ui.add_enabled(!busy, egui::Button::new(format!("Update to {}", entry.version))).clicked() {
+                                        pending_actions.push(Action::InstallFromRegistry(entry.id.clone()));
+                                    }
+                                });
+                            },
+                            Some(plugin) => {
+                                ui.label(format!("Installed ({})", plugin.version));
+                            },
+                        }
+                    });
+                });
+                ui.separator();
+            }
+        });
+    }
+    fn open_settings(&mut self, index: usize) {
         if let Some(plugin) = self.plugins.get(index) {
-            if let Ok(_) = fs::remove_dir_all(&plugin.path) {
-                self.plugins.remove(index);
+            let settings = PluginSettings::load(&self.config_dir, &plugin.name, &plugin.settings);
+            self.settings_page = Some((index, settings));
+            self.capturing_key = None;
+            self.editing_setting = None;
+        }
+    }
+    fn set_setting(&mut self, key: String, value: SettingValue) {
+        self.capturing_key = None;
+        self.editing_setting = None;
+        let Some((index, settings)) = &mut self.settings_page else {
+            return;
+        };
+        if settings.get(&key) == Some(&value) {
+            return;
+        }
+        let Some(plugin) = self.plugins.get(*index) else {
+            return;
+        };
+        match settings.set(&key, value.clone()) {
+            Ok(()) => self.setting_changes.push((plugin.name.clone(), key, value)),
+            Err(e) => self.error = Some(e),
+        }
+    }
+    fn reset_settings(&mut self) {
+        let Some((_, settings)) = &self.settings_page else {
+            return;
+        };
+        let defaults: Vec<_> = settings.specs.iter()
+            .filter(|spec| settings.get(&spec.key) != Some(&spec.default))
+            .map(|spec| (spec.key.clone(), spec.default.clone()))
+            .collect();
+        for (key, value) in defaults {
+            self.set_setting(key, value);
+        }
+    }
+    /// Settings edited since the last call, as (plugin, key, new value), so running
+    /// plugins can be told about them.
+    pub fn take_setting_changes(&mut self) -> Vec<(String, String, SettingValue)> {
+        std::mem::take(&mut self.setting_changes)
+    }
+
+    /// Turns a plugin off from outside the plugin manager, e.g. after it was stopped for
+    /// going over its execution budget.
+    pub fn disable_plugin(&mut self, name: &str) -> Result<(), String> {
+        self.states.set_enabled(name, false)?;
+        self.refresh_plugins();
+        Ok(())
+    }
+
+    pub fn set_plugin_pages(&mut self, pages: BTreeMap<String, Vec<String>>) {
+        self.plugin_pages = pages;
+    }
+
+    /// The plugin settings page the user asked to open, as (plugin, title).
+    pub fn take_opened_page(&mut self) -> Option<(String, String)> {
+        self.opened_page.take()
+    }
+    fn draw_settings_page(&mut self, ctx: &egui::Context, pending_actions: &mut Vec<Action>) {
+        let Some((index, settings)) = &self.settings_page else {
+            return;
+        };
+        let Some(plugin) = self.plugins.get(*index) else {
+            return;
+        };
+        let capturing_key = &self.capturing_key;
+        let editing = &self.editing_setting;
+        egui::Window::new(format!("{} settings", plugin.display_name))
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+            .show(ctx, |ui| {
+                egui::Grid::new("plugin_settings").num_columns(2).spacing([20.0, 8.0]).show(ui, |ui| {
+                    for spec in &settings.specs {
+                        ui.label(&spec.label);
+                        let current = match editing {
+                            Some((key, value)) if *key == spec.key => value.clone(),
+                            _ => settings.get(&spec.key).cloned().unwrap_or_else(|| spec.default.clone()),
+                        };
+                        let capturing = capturing_key.as_deref() == Some(spec.key.as_str());
+                        match setting_widget(ui, spec, current, capturing) {
+                            Some(SettingInput::Editing(value)) => pending_actions.push(Action::EditSetting(spec.key.clone(), value)),
+                            Some(SettingInput::Changed(value)) => pending_actions.push(Action::SetSetting(spec.key.clone(), value)),
+                            Some(SettingInput::CaptureKey) => pending_actions.push(Action::CaptureKey(spec.key.clone())),
+                            None => {},
+                        }
+                        ui.end_row();
+                    }
+                });
+                ui.add_space(10.0);
+                ui.horizontal(|ui| {
+                    if ui.button("Reset to defaults").clicked() {
+                        pending_actions.push(Action::ResetSettings);
+                    }
+                    if ui.button("Close").clicked() {
+                        pending_actions.push(Action::CloseSettings);
+                    }
+                });
+            });
+    }
+    fn draw_check_report(&mut self, ctx: &egui::Context, pending_actions: &mut Vec<Action>) {
+        let Some((name, findings)) = &self.check_report else {
+            return;
+        };
+        egui::Window::new(format!("Check: {}", name))
+            .collapsible(false)
+            .default_size([500.0, 300.0])
+            .show(ctx, |ui| {
+                if findings.is_empty() {
+                    ui.label("No problems found.");
+                }
+                egui::ScrollArea::vertical().max_height(400.0).show(ui, |ui| {
+                    for finding in findings {
+                        let color = match finding.severity {
+                            Severity::Error => egui::Color32::RED,
+                            Severity::Warning => egui::Color32::YELLOW,
+                        };
+                        ui.colored_label(color, finding.to_string());
+                    }
+                });
+                ui.separator();
+                if ui.button("Close").clicked() {
+                    pending_actions.push(Action::CloseCheck);
+                }
+            });
+    }
+
+    fn draw_details(&mut self, ctx: &egui::Context, pending_actions: &mut Vec<Action>) {
+        let Some((index, details)) = &self.selected_plugin else {
+            return;
+        };
+        let Some(plugin) = self.plugins.get(*index) else {
+            return;
+        };
+        let dependents: Vec<&str> = self.plugins.iter()
+            .filter(|other| other.dependencies.iter().any(|dependency| dependency.name == plugin.name))
+            .map(|other| other.display_name.as_str())
+            .collect();
+        let mut open = true;
+        egui::Window::new(format!("{} details", plugin.display_name))
+            .open(&mut open)
+            .resizable(true)
+            .default_size([500.0, 450.0])
+            .show(ctx, |ui| {
+                egui::Grid::new("plugin_details").num_columns(2).spacing([20.0, 4.0]).show(ui, |ui| {
+                    let row = |ui: &mut egui::Ui, label: &str, value: String| {
+                        ui.label(label);
+                        ui.label(value);
+                        ui.end_row();
+                    };
+                    row(ui, "Folder", plugin.name.clone());
+                    if !plugin.version.is_empty() {
+                        row(ui, "Version", plugin.version.clone());
+                    }
+                    if let Some(revision) = &details.revision {
+                        row(ui, "Revision", revision.clone());
+                    }
+                    if !plugin.author.is_empty() {
+                        row(ui, "Author", plugin.author.clone());
+                    }
+                    if !plugin.homepage.is_empty() {
+                        ui.label("Homepage");
+                        ui.hyperlink(&plugin.homepage);
+                        ui.end_row();
+                    }
+                    if let Some(source) = &plugin.update {
+                        row(ui, "Updates from", source.url.clone());
+                    }
+                    row(ui, "Status", if plugin.enabled { "Enabled" } else { "Disabled" }.to_string());
+                    if !plugin.permissions.is_empty() {
+                        let names: Vec<_> = plugin.permissions.iter().map(|p| p.name()).collect();
+                        row(ui, "Permissions", names.join(", "));
+                    }
+                    let dependencies: Vec<String> = plugin.dependencies.iter()
+                        .map(|dependency| match &dependency.version {
+                            Some(version) => format!("{} {}+", dependency.name, version),
+                            None => dependency.name.clone(),
+                        })
+                        .collect();
+                    row(ui, "Depends on", if dependencies.is_empty() { "Nothing".to_string() } else { dependencies.join(", ") });
+                    row(ui, "Needed by", if dependents.is_empty() { "Nothing".to_string() } else { dependents.join(", ") });
+                    row(ui, "Size on disk", format!("{} in {} files", format_size(details.disk_size), details.files));
+                    row(ui, "Save data", format_size(details.data_size));
+                });
+                ui.label(&plugin.description);
+                ui.separator();
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    if let Some(readme) = &details.readme {
+                        egui::CollapsingHeader::new("README").default_open(true).show(ui, |ui| markdown::show(ui, readme));
+                    }
+                    if let Some(changelog) = &details.changelog {
+                        egui::CollapsingHeader::new("Changelog").show(ui, |ui| markdown::show(ui, changelog));
+                    }
+                    if details.readme.is_none() && details.changelog.is_none() {
+                        ui.label("This plugin has no README or changelog.");
+                    }
+                });
+            });
+        if !open {
+            pending_actions.push(Action::CloseDetails);
+        }
+    }
+    fn delete_plugins(&mut self, mut indices: Vec<usize>) {
+        // remove from the back so the remaining indices stay valid
+        indices.sort_unstable_by(|a, b| b.cmp(a));
+        indices.dedup();
+        for index in indices {
+            let Some(plugin) = self.plugins.get(index) else {
+                continue;
+            };
+            match trash::move_to_trash(&self.config_dir, &plugin.path, &plugin.name, self.trash_save_data) {
+                Ok(()) => {
+                    // the plugin is gone either way; what it leaves behind only gets reported
+                    if let Err(e) = self.grants.revoke_all(&plugin.name) {
+                        self.error = Some(e);
+                    }
+                    if let Err(e) = self.states.forget(&plugin.name) {
+                        self.error = Some(e);
+                    }
+                    self.selected.remove(&plugin.name);
+                    self.plugins.remove(index);
+                    self.selected_plugin = None;
+                },
+                Err(e) => self.error = Some(e),
             }
         }
+        self.trash = trash::list(&self.config_dir);
         self.confirm_delete = None;
+        self.confirm_bulk_delete = false;
+        self.trash_save_data = false;
+    }
+    /// Enables or disables several plugins at once. Plugins that still need permissions
+    /// are left off; the prompt opens for the first of them.
+    fn set_enabled(&mut self, indices: Vec<usize>, enabled: bool) {
+        let mut needs_review = Vec::new();
+        for index in indices {
+            let Some(plugin) = self.plugins.get_mut(index) else {
+                continue;
+            };
+            if plugin.enabled == enabled {
+                continue;
+            }
+            if enabled && !self.grants.missing(&plugin.name, &plugin.permissions).is_empty() {
+                needs_review.push(index);
+                continue;
+            }
+            match self.states.set_enabled(&plugin.name, enabled) {
+                Ok(()) => plugin.enabled = enabled,
+                Err(e) => self.error = Some(e),
+            }
+        }
+        self.forget_conflicts();
+        if let Some(&first) = needs_review.first() {
+            self.permission_prompt = Some(first);
+            if needs_review.len() > 1 {
+                let names: Vec<_> = needs_review.iter().map(|&index| self.plugins[index].display_name.as_str()).collect();
+                self.error = Some(format!("Review permissions to enable {}", names.join(", ")));
+            }
+        }
+    }
+    /// Every installed plugin's name in load order.
+    fn arranged(&self) -> Vec<String> {
+        let plugins: Vec<(String, Vec<String>)> = self.plugins.iter()
+            .map(|plugin| (plugin.name.clone(), plugin.dependencies.iter().map(|d| d.name.clone()).collect()))
+            .collect();
+        self.load_order.arrange(&plugins)
+    }
+    /// Saves `order` as the user's load order; conflicts are looked for again.
+    fn set_load_order(&mut self, order: Vec<String>) {
+        if let Err(e) = self.load_order.set(order) {
+            self.error = Some(e);
+        }
+        self.forget_conflicts();
+    }
+    fn forget_conflicts(&mut self) {
+        self.conflicts = None;
+    }
+    fn move_in_load_order(&mut self, name: &str, earlier: bool) {
+        let mut order = self.arranged();
+        let Some(position) = order.iter().position(|plugin| plugin == name) else {
+            return;
+        };
+        let other = if earlier { position.checked_sub(1) } else { Some(position + 1).filter(|&p| p < order.len()) };
+        if let Some(other) = other {
+            order.swap(position, other);
+            self.set_load_order(order);
+        }
+    }
+    /// Moves `name` to load after `others`, so it wins the conflict between them.
+    fn prefer_plugin(&mut self, name: &str, others: &[String]) {
+        let mut order = self.arranged();
+        order.retain(|plugin| plugin != name);
+        let after = order.iter().rposition(|plugin| others.contains(plugin)).map_or(0, |p| p + 1);
+        order.insert(after, name.to_string());
+        self.set_load_order(order);
+    }
+    fn draw_load_order(&mut self, ui: &mut egui::Ui, pending_actions: &mut Vec<Action>) {
+        let open = egui::CollapsingHeader::new("Load order").id_salt("load_order").show(ui, |ui| {
+            ui.label("Plugins lower in the list load later and override modules and assets of the ones above. Dependencies always load first.");
+            let order = self.arranged();
+            for (position, name) in order.iter().enumerate() {
+                let Some(plugin) = self.plugins.iter().find(|plugin| plugin.name == *name) else {
+                    continue;
+                };
+                ui.horizontal(|ui| {
+                    if ui.add_enabled(position > 0, egui::Button::new("⬆").small()).clicked() {
+                        pending_actions.push(Action::MoveInLoadOrder(name.clone(), true));
+                    }
+                    if ui.add_enabled(position + 1 < order.len(), egui::Button::new("⬇").small()).clicked() {
+                        pending_actions.push(Action::MoveInLoadOrder(name.clone(), false));
+                    }
+                    let label = format!("{}. {}", position + 1, plugin.display_name);
+                    if plugin.enabled {
+                        ui.label(label);
+                    } else {
+                        ui.weak(format!("{} (disabled)", label));
+                    }
+                });
+            }
+        }).body_returned.is_some();
+        if !open {
+            return;
+        }
+        let conflicts = self.conflicts.get_or_insert_with(|| conflicts::detect(&self.config_dir));
+        if conflicts.is_empty() {
+            return;
+        }
+        let title = egui::RichText::new(format!("Conflicts ({})", conflicts.len())).color(egui::Color32::YELLOW);
+        egui::CollapsingHeader::new(title).id_salt("conflicts").show(ui, |ui| {
+            for conflict in conflicts.iter() {
+                let kind = match conflict.kind {
+                    Kind::Module => "Module",
+                    Kind::Asset => "Asset",
+                };
+                ui.label(egui::RichText::new(format!("{} {}", kind, conflict.name)).strong());
+                let winner = &conflict.winner().plugin;
+                for provider in &conflict.providers {
+                    ui.horizontal(|ui| {
+                        ui.add_space(10.0);
+                        ui.label(format!("{}: {}", provider.plugin, provider.file.display()));
+                        if provider.plugin == *winner {
+                            ui.colored_label(egui::Color32::GREEN, "used");
+                        } else if ui.small_button("Prefer").clicked() {
+                            let others = conflict.providers.iter()
+                                .map(|other| other.plugin.clone())
+                                .filter(|other| *other != provider.plugin)
+                                .collect();
+                            pending_actions.push(Action::PreferPlugin(provider.plugin.clone(), others));
+                        }
+                    });
+                }
+            }
+        });
+    }
+    fn measure_sizes(&mut self) {
+        for plugin in self.plugins.iter_mut().filter(|plugin| plugin.size.is_none()) {
+            plugin.size = Some(dir_usage(&plugin.path).1);
+        }
+    }
+    /// Indices of the plugins matching the search box and filter, in the chosen order.
+    fn visible_plugins(&self) -> Vec<usize> {
+        let query = self.search.trim().to_lowercase();
+        let mut visible: Vec<usize> = self.plugins.iter().enumerate()
+            .filter(|(_, plugin)| {
+                query.is_empty()
+                    || plugin.name.to_lowercase().contains(&query)
+                    || plugin.display_name.to_lowercase().contains(&query)
+                    || plugin.description.to_lowercase().contains(&query)
+                    || plugin.tags.iter().any(|tag| tag.to_lowercase().contains(&query))
+            })
+            .filter(|(_, plugin)| match self.filter {
+                Filter::All => true,
+                Filter::Enabled => plugin.enabled,
+                Filter::Disabled => !plugin.enabled,
+                Filter::HasUpdate => matches!(self.update_status.get(&plugin.name), Some(UpdateStatus::Available(_))),
+                Filter::Errored => {
+                    plugin.load_error.is_some()
+                        || matches!(plugin.verification, Verification::Tampered(_))
+                        || matches!(self.update_status.get(&plugin.name), Some(UpdateStatus::Failed(_)))
+                },
+            })
+            .map(|(index, _)| index)
+            .collect();
+        match self.sort_by {
+            SortBy::Name => visible.sort_by_key(|&index| self.plugins[index].display_name.to_lowercase()),
+            SortBy::Date => visible.sort_by_key(|&index| std::cmp::Reverse(self.plugins[index].modified)),
+            SortBy::Size => visible.sort_by_key(|&index| std::cmp::Reverse(self.plugins[index].size.unwrap_or(0))),
+            SortBy::LoadOrder => {
+                let order = self.arranged();
+                visible.sort_by_key(|&index| order.iter().position(|name| *name == self.plugins[index].name));
+            },
+        }
+        visible
+    }
+    /// Restores a trashed plugin. Its grants were revoked when it was deleted, so a plugin
+    /// that asks for permissions comes back disabled with the permission prompt open.
+    fn restore_from_trash(&mut self, index: usize) {
+        let Some(entry) = self.trash.get(index) else {
+            return;
+        };
+        let name = entry.name.clone();
+        if let Err(e) = trash::restore(&self.config_dir, entry) {
+            self.error = Some(e);
+            return;
+        }
+        let permissions = Manifest::load(&self.config_dir.join("plugins").join(&name))
+            .map(|manifest| manifest.permissions)
+            .unwrap_or_default();
+        let needs_grants = !self.grants.missing(&name, &permissions).is_empty();
+        if needs_grants {
+            if let Err(e) = self.states.set_enabled(&name, false) {
+                self.error = Some(e);
+            }
+        }
+        self.refresh_plugins();
+        if needs_grants {
+            self.permission_prompt = self.plugins.iter().position(|plugin| plugin.name == name);
+        }
+    }
+    fn delete_from_trash(&mut self, index: usize) {
+        if let Some(entry) = self.trash.get(index) {
+            if let Err(e) = trash::delete(entry) {
+                self.error = Some(e);
+            }
+        }
+        self.trash = trash::list(&self.config_dir);
+        self.confirm_trash_delete = None;
+    }
+    fn draw_trash(&mut self, ui: &mut egui::Ui, pending_actions: &mut Vec<Action>) {
+        ui.horizontal(|ui| {
+            ui.label("Keep deleted plugins for");
+            let mut retention = self.trash_retention;
+            let response = ui.add(egui::DragValue::new(&mut retention).range(0..=3650).suffix(" days"));
+            if response.changed() {
+                pending_actions.push(Action::SetRetention(retention));
+            }
+            if response.drag_stopped() || response.lost_focus() {
+                pending_actions.push(Action::SaveRetention);
+            }
+            if self.trash_retention == 0 {
+                ui.label("(forever)");
+            }
+            ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                if self.confirm_empty_trash {
+                    if ui.button("Cancel").clicked() {
+                        pending_actions.push(Action::ConfirmEmptyTrash(false));
+                    }
+                    if ui.button(egui::RichText::new("Delete everything permanently").color(egui::Color32::RED)).clicked() {
+                        pending_actions.push(Action::EmptyTrash);
+                    }
+                } else if ui.add_enabled(!self.trash.is_empty(), egui::Button::new("Empty trash")).clicked() {
+                    pending_actions.push(Action::ConfirmEmptyTrash(true));
+                }
+            });
+        });
+        ui.separator();
+        if self.trash.is_empty() {
+            ui.label("The trash is empty.");
+            return;
+        }
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            for (index, entry) in self.trash.iter().enumerate() {
+                ui.horizontal(|ui| {
+                    ui.vertical(|ui| {
+                        let name = if entry.manifest.name.is_empty() { &entry.name } else { &entry.manifest.name };
+                        if entry.manifest.version.is_empty() {
+                            ui.heading(name);
+                        } else {
+                            ui.heading(format!("{} {}", name, entry.manifest.version));
+                        }
+                        let days = entry.age().as_secs() / (24 * 60 * 60);
+                        let deleted = match days {
+                            0 => "Deleted today".to_string(),
+                            1 => "Deleted yesterday".to_string(),
+                            days => format!("Deleted {} days ago", days),
+                        };
+                        if entry.has_data {
+                            ui.label(format!("{}, together with its save data", deleted));
+                        } else {
+                            ui.label(deleted);
+                        }
+                    });
+                    ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                        if self.confirm_trash_delete.as_ref() == Some(&entry.path) {
+                            if ui.button("Cancel").clicked() {
+                                pending_actions.push(Action::ConfirmDeleteFromTrash(None));
+                            }
+                            if ui.button(egui::RichText::new("Delete permanently").color(egui::Color32::RED)).clicked() {
+                                pending_actions.push(Action::DeleteFromTrash(index));
+                            }
+                        } else if ui.button("Delete forever").clicked() {
+                            pending_actions.push(Action::ConfirmDeleteFromTrash(Some(entry.path.clone())));
+                        }
+                        if ui.button("Restore").clicked() {
+                            pending_actions.push(Action::RestoreFromTrash(index));
+                        }
+                    });
+                });
+                ui.separator();
+            }
+        });
     }
     pub fn draw(&mut self, ctx: &egui::Context) {
         if !self.show {
             return;
         }
+        self.poll_updates(ctx);
+        self.poll_browse(ctx);
+        let updating = self.update_receiver.is_some();
         let mut pending_actions: Vec<Action> = Vec::new();
         egui::Window::new("Plugins")
             .resizable(true)
             .default_size([600.0, 500.0])
             .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.selectable_value(&mut self.tab, Tab::Installed, "Installed");
+                    if ui.selectable_value(&mut self.tab, Tab::Browse, "Browse").clicked() && self.catalog.is_none() {
+                        pending_actions.push(Action::LoadRegistries);
+                    }
+                    ui.selectable_value(&mut self.tab, Tab::Trash, format!("Trash ({})", self.trash.len()));
+                });
                 ui.horizontal(|ui| {
                     if ui.button("Refresh").clicked() {
                         self.refresh_plugins();
                     }
+                    if ui.button("Install from file...").clicked() {
+                        pending_actions.push(Action::InstallFromFile);
+                    }
+                    if self.tab == Tab::Installed && ui.add_enabled(!updating, egui::Button::new("Check for updates")).clicked() {
+                        pending_actions.push(Action::CheckUpdates((0..self.plugins.len()).collect()));
+                    }
+                    let available: Vec<usize> = self.plugins.iter().enumerate()
+                        .filter(|(_, plugin)| matches!(self.update_status.get(&plugin.name), Some(UpdateStatus::Available(_))))
+                        .map(|(index, _)| index)
+                        .collect();
+                    if !available.is_empty() && ui.add_enabled(!updating, egui::Button::new("Apply all updates")).clicked() {
+                        pending_actions.push(Action::Update(available));
+                    }
                     ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
                         if ui.button("Done").clicked() {
                             self.show = false;
                         }
                     });
                 });
+                if let Some(error) = self.error.clone() {
+                    ui.horizontal(|ui| {
+                        ui.colored_label(egui::Color32::RED, error);
+                        if ui.small_button("Dismiss").clicked() {
+                            self.error = None;
+                        }
+                    });
+                }
+                if ctx.input(|i| !i.raw.hovered_files.is_empty()) {
+                    ui.label("Drop a plugin archive to install it.");
+                }
+                ui.separator();
+                if self.tab == Tab::Browse {
+                    self.draw_browse(ctx, ui, &mut pending_actions);
+                    return;
+                }
+                if self.tab == Tab::Trash {
+                    self.draw_trash(ui, &mut pending_actions);
+                    return;
+                }
+                self.draw_profiles(ui, &mut pending_actions);
+                self.draw_load_order(ui, &mut pending_actions);
+                ui.horizontal(|ui| {
+                    ui.add(egui::TextEdit::singleline(&mut self.search).hint_text("Search name, description or tags"));
+                    egui::ComboBox::from_id_salt("plugin_filter")
+                        .selected_text(self.filter.label())
+                        .show_ui(ui, |ui| {
+                            for filter in Filter::ALL {
+                                ui.selectable_value(&mut self.filter, filter, filter.label());
+                            }
+                        });
+                    egui::ComboBox::from_id_salt("plugin_sort")
+                        .selected_text(self.sort_by.label())
+                        .show_ui(ui, |ui| {
+                            for sort_by in SortBy::ALL {
+                                ui.selectable_value(&mut self.sort_by, sort_by, sort_by.label());
+                            }
+                        });
+                });
+                if self.sort_by == SortBy::Size {
+                    self.measure_sizes();
+                }
+                let visible = self.visible_plugins();
+                ui.horizontal(|ui| {
+                    let all_selected = !visible.is_empty() && visible.iter().all(|&index| self.selected.contains(&self.plugins[index].name));
+                    let mut select_all = all_selected;
+                    if ui.checkbox(&mut select_all, "").on_hover_text("Select all shown").changed() {
+                        for &index in &visible {
+                            let name = &self.plugins[index].name;
+                            if select_all {
+                                self.selected.insert(name.clone());
+                            } else {
+                                self.selected.remove(name);
+                            }
+                        }
+                    }
+                    if self.selected.is_empty() {
+                        ui.label(format!("{} of {} plugins", visible.len(), self.plugins.len()));
+                        return;
+                    }
+                    let chosen: Vec<usize> = self.plugins.iter().enumerate()
+                        .filter(|(_, plugin)| self.selected.contains(&plugin.name))
+                        .map(|(index, _)| index)
+                        .collect();
+                    ui.label(format!("{} selected", chosen.len()));
+                    if ui.button("Enable").clicked() {
+                        pending_actions.push(Action::SetEnabled(chosen.clone(), true));
+                    }
+                    if ui.button("Disable").clicked() {
+                        pending_actions.push(Action::SetEnabled(chosen.clone(), false));
+                    }
+                    if ui.add_enabled(!updating, egui::Button::new("Update")).clicked() {
+                        pending_actions.push(Action::Update(chosen.clone()));
+                    }
+                    if self.confirm_bulk_delete {
+                        if ui.button(egui::RichText::new(format!("Move {} to trash", chosen.len())).color(egui::Color32::RED)).clicked() {
+                            pending_actions.push(Action::DeletePlugins(chosen));
+                        }
+                        ui.checkbox(&mut self.trash_save_data, "Trash save data too");
+                        if ui.button("Cancel").clicked() {
+                            pending_actions.push(Action::ConfirmBulkDelete(false));
+                        }
+                    } else if ui.button("Delete").clicked() {
+                        pending_actions.push(Action::ConfirmBulkDelete(true));
+                    }
+                    if ui.button("Clear selection").clicked() {
+                        self.selected.clear();
+                        self.confirm_bulk_delete = false;
+                    }
+                });
                 ui.separator();
                 egui::ScrollArea::vertical().show(ui, |ui| {
-                    for (index, plugin) in self.plugins.iter_mut().enumerate() {
+                    for index in visible {
+                        let plugin = &mut self.plugins[index];
                         ui.horizontal(|ui| {
+                            let mut selected = self.selected.contains(&plugin.name);
+                            if ui.checkbox(&mut selected, "").changed() {
+                                if selected {
+                                    self.selected.insert(plugin.name.clone());
+                                } else {
+                                    self.selected.remove(&plugin.name);
+                                }
+                            }
                             plugin.load_logo(ctx);
                             if let Some(texture) = &plugin.texture {
                                 let size = 48.0;
@@ -206,8 +1738,78 @@ impl PluginManager {
                                 ui.add_space(10.0);
                             }
                             ui.vertical(|ui| {
-                                ui.heading(&plugin.display_name);
+                                let heading = egui::Label::new(egui::RichText::new(&plugin.display_name).heading())
+                                    .sense(egui::Sense::click());
+                                if ui.add(heading).on_hover_text("Show details").clicked() {
+                                    pending_actions.push(Action::SelectPlugin(index));
+                                }
                                 ui.label(&plugin.description);
+                                verification_label(ui, &plugin.verification);
+                                if let Some(e) = &plugin.load_error {
+                                    ui.colored_label(egui::Color32::RED, e);
+                                }
+                                match self.update_status.get(&plugin.name) {
+                                    Some(UpdateStatus::Queued) => { ui.label("Waiting..."); },
+                                    Some(UpdateStatus::Running(None)) => { ui.label("Checking for updates..."); },
+                                    Some(UpdateStatus::Running(Some((received, total)))) => {
+                                        let fraction = if *total == 0 { 0.0 } else { *received as f32 / *total as f32 };
+                                        ui.add(egui::ProgressBar::new(fraction).text(format!("Downloading {}/{}", received, total)));
+                                    },
+                                    Some(UpdateStatus::Available(check)) => {
+                                        ui.horizontal(|ui| {
+                                            let installed = match &check.installed_revision {
+                                                Some(revision) => format!("{} ({})", check.installed_version, revision),
+                                                None => check.installed_version.clone(),
+                                            };
+                                            ui.label(format!(
+                                                "Update available: {} -> {} ({}) on {}",
+                                                installed, check.available_version, check.available_revision, check.channel
+                                            ));
+                                            if ui.add_enabled(!updating, egui::Button::new("Apply").small()).clicked() {
+                                                pending_actions.push(Action::Update(vec![index]));
+                                            }
+                                        });
+                                    },
+                                    Some(UpdateStatus::Finished(message)) => { ui.label(message); },
+                                    Some(UpdateStatus::Failed(e)) => { ui.colored_label(egui::Color32::RED, format!("Update failed: {}", e)); },
+                                    None => {},
+                                }
+                                if let Some(source) = &plugin.update {
+                                    let preference = self.update_prefs.get(&plugin.name);
+                                    ui.horizontal(|ui| {
+                                        match &preference.pin {
+                                            Some(pin) => {
+                                                ui.label(format!("Pinned to {}", pin));
+                                                if ui.small_button("Unpin").clicked() {
+                                                    pending_actions.push(Action::Unpin(index));
+                                                }
+                                            },
+                                            None => {
+                                                let edit = self.pin_edits.entry(plugin.name.clone()).or_default();
+                                                ui.add(egui::TextEdit::singleline(edit).hint_text("tag or commit").desired_width(100.0));
+                                                if ui.add_enabled(!edit.trim().is_empty(), egui::Button::new("Pin").small()).clicked() {
+                                                    pending_actions.push(Action::Pin(index));
+                                                }
+                                            },
+                                        }
+                                        if source.beta.is_some() {
+                                            let mut beta = preference.beta;
+                                            if ui.checkbox(&mut beta, "Beta channel").changed() {
+                                                pending_actions.push(Action::SetBeta(index, beta));
+                                            }
+                                        }
+                                    });
+                                }
+                                if !plugin.permissions.is_empty() {
+                                    let missing = self.grants.missing(&plugin.name, &plugin.permissions);
+                                    ui.horizontal(|ui| {
+                                        let names: Vec<_> = plugin.permissions.iter().map(|p| p.name()).collect();
+                                        ui.label(format!("Permissions: {}", names.join(", ")));
+                                        if !missing.is_empty() && ui.small_button("Review").clicked() {
+                                            pending_actions.push(Action::ReviewPermissions(index));
+                                        }
+                                    });
+                                }
                                 
                                 ui.horizontal(|ui| {
                                     ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
@@ -216,8 +1818,11 @@ impl PluginManager {
                                                 if ui.button("Cancel").clicked() {
                                                     pending_actions.push(Action::CancelDelete);
                                                 }
-                                                if ui.button(egui::RichText::new("Deletion is irreversible.").color(egui::Color32::RED)).clicked() {
-                                                    pending_actions.push(Action::DeletePlugin(index));
+                                                if ui.button(egui::RichText::new("Move to trash").color(egui::Color32::RED)).clicked() {
+                                                    pending_actions.push(Action::DeletePlugins(vec![index]));
+                                                }
+                                                if self.config_dir.join("data").join(&plugin.name).is_dir() {
+                                                    ui.checkbox(&mut self.trash_save_data, "Trash save data too");
                                                 }
                                             });
                                         } else {
@@ -226,6 +1831,24 @@ impl PluginManager {
                                             }
                                         }
                                         
+                                        if ui.button("Check").clicked() {
+                                            pending_actions.push(Action::CheckPlugin(index));
+                                        }
+                                        if !plugin.settings.is_empty() && ui.button("Settings").clicked() {
+                                            pending_actions.push(Action::OpenSettings(index));
+                                        }
+                                        for title in self.plugin_pages.get(&plugin.name).into_iter().flatten() {
+                                            if ui.button(title).clicked() {
+                                                pending_actions.push(Action::OpenPluginPage(plugin.name.clone(), title.clone()));
+                                            }
+                                        }
+                                        if plugin.update.is_some() && ui.add_enabled(!updating, egui::Button::new("Check for updates")).clicked() {
+                                            pending_actions.push(Action::CheckUpdates(vec![index]));
+                                        }
+                                        if self.config_dir.join("backups").join(&plugin.name).is_dir()
+                                            && ui.add_enabled(!updating, egui::Button::new("Revert to previous version")).clicked() {
+                                            pending_actions.push(Action::Revert(index));
+                                        }
                                         let toggle_text = if plugin.enabled { "Disable" } else { "Enable" };
                                         if ui.button(toggle_text).clicked() {
                                             pending_actions.push(Action::TogglePlugin(index));
@@ -238,12 +1861,112 @@ impl PluginManager {
                     }
                 });
             });
+        self.draw_permission_prompt(ctx, &mut pending_actions);
+        self.draw_install_preview(ctx, &mut pending_actions);
+        self.draw_settings_page(ctx, &mut pending_actions);
+        self.draw_details(ctx, &mut pending_actions);
+        self.draw_check_report(ctx, &mut pending_actions);
         for action in pending_actions {
             match action {
-                Action::DeletePlugin(idx) => self.delete_plugin(idx),
+                Action::DeletePlugins(indices) => self.delete_plugins(indices),
+                Action::ConfirmBulkDelete(confirm) => self.confirm_bulk_delete = confirm,
+                Action::SetEnabled(indices, enabled) => self.set_enabled(indices, enabled),
                 Action::ConfirmDelete(name) => self.confirm_delete = Some(name),
-                Action::CancelDelete => self.confirm_delete = None,
+                Action::CancelDelete => {
+                    self.confirm_delete = None;
+                    self.trash_save_data = false;
+                },
                 Action::TogglePlugin(idx) => self.toggle_plugin(idx),
+                Action::ReviewPermissions(idx) => self.permission_prompt = Some(idx),
+                Action::ApprovePermissions(idx) => self.approve_permissions(idx),
+                Action::DenyPermissions => self.permission_prompt = None,
+                Action::InstallFromFile => self.install_from_file(),
+                Action::ConfirmInstall => self.confirm_install(),
+                Action::CancelInstall => self.install_preview = None,
+                Action::TrustSigner(key) => self.trust_signer(key),
+                Action::CheckUpdates(indices) => self.start_updates(indices, Job::Check),
+                Action::Update(indices) => self.start_updates(indices, Job::Apply),
+                Action::Revert(idx) => self.revert_plugin(idx),
+                Action::SetBeta(idx, beta) => self.set_preference(idx, |preference| preference.beta = beta),
+                Action::Pin(idx) => {
+                    let pin = self.plugins.get(idx)
+                        .and_then(|plugin| self.pin_edits.get(&plugin.name))
+                        .map(|pin| pin.trim().to_string());
+                    self.set_preference(idx, |preference| preference.pin = pin);
+                },
+                Action::Unpin(idx) => self.set_preference(idx, |preference| preference.pin = None),
+                Action::LoadRegistries => self.load_registries(),
+                Action::AddRegistry => {
+                    let source = std::mem::take(&mut self.new_registry);
+                    if let Err(e) = self.registries.add(&source) {
+                        self.error = Some(e);
+                    }
+                    self.load_registries();
+                },
+                Action::RemoveRegistry(source) => {
+                    if let Err(e) = self.registries.remove(&source) {
+                        self.error = Some(e);
+                    }
+                    self.load_registries();
+                },
+                Action::InstallFromRegistry(id) => self.install_from_registry(id),
+                Action::OpenSettings(idx) => self.open_settings(idx),
+                Action::OpenPluginPage(plugin, title) => {
+                    self.opened_page = Some((plugin, title));
+                    self.show = false;
+                },
+                Action::SelectPlugin(idx) => {
+                    self.selected_plugin = self.plugins.get(idx)
+                        .map(|plugin| (idx, PluginDetails::load(&self.config_dir, plugin)));
+                },
+                Action::CloseDetails => self.selected_plugin = None,
+                Action::CheckPlugin(idx) => {
+                    self.check_report = self.plugins.get(idx).map(|plugin| {
+                        (plugin.display_name.clone(), check::check(&plugin.path, &self.config_dir))
+                    });
+                },
+                Action::CloseCheck => self.check_report = None,
+                Action::MoveInLoadOrder(name, earlier) => self.move_in_load_order(&name, earlier),
+                Action::PreferPlugin(name, others) => self.prefer_plugin(&name, &others),
+                Action::SaveProfile => self.save_profile(),
+                Action::SwitchProfile(name) => {
+                    if let Some(profile) = self.profiles.get(&name).cloned() {
+                        self.apply_profile(profile, false);
+                    }
+                },
+                Action::RemoveProfile(name) => {
+                    if let Err(e) = self.profiles.remove(&name) {
+                        self.error = Some(e);
+                    }
+                },
+                Action::ExportLockfile => self.export_lockfile(),
+                Action::ImportLockfile => self.import_lockfile(),
+                Action::RestoreFromTrash(idx) => self.restore_from_trash(idx),
+                Action::DeleteFromTrash(idx) => self.delete_from_trash(idx),
+                Action::ConfirmDeleteFromTrash(path) => self.confirm_trash_delete = path,
+                Action::ConfirmEmptyTrash(confirm) => self.confirm_empty_trash = confirm,
+                Action::EmptyTrash => {
+                    if let Err(e) = trash::empty(&self.config_dir) {
+                        self.error = Some(e);
+                    }
+                    self.trash = trash::list(&self.config_dir);
+                    self.confirm_empty_trash = false;
+                },
+                Action::SetRetention(days) => self.trash_retention = days,
+                Action::SaveRetention => {
+                    if let Err(e) = trash::save_retention(&self.config_dir, self.trash_retention) {
+                        self.error = Some(e);
+                    }
+                },
+                Action::CloseSettings => {
+                    self.settings_page = None;
+                    self.capturing_key = None;
+                    self.editing_setting = None;
+                },
+                Action::EditSetting(key, value) => self.editing_setting = Some((key, value)),
+                Action::SetSetting(key, value) => self.set_setting(key, value),
+                Action::ResetSettings => self.reset_settings(),
+                Action::CaptureKey(key) => self.capturing_key = Some(key),
             }
         }
     }
@@ -0,0 +1,301 @@
+use crate::manifest::attribute;
+use eframe::egui;
+use quick_xml::escape::escape;
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::reader::Reader;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum SettingKind {
+    Bool,
+    Int { min: Option<i64>, max: Option<i64> },
+    Float { min: Option<f64>, max: Option<f64> },
+    Enum(Vec<String>),
+    String,
+    /// The name of an egui key, e.g. "Space" or "A".
+    Keybind,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum SettingValue {
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    Text(String),
+}
+
+impl std::fmt::Display for SettingValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SettingValue::Bool(value) => write!(f, "{}", value),
+            SettingValue::Int(value) => write!(f, "{}", value),
+            SettingValue::Float(value) => write!(f, "{}", value),
+            SettingValue::Text(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+/// An option a plugin declares in `index.xml`:
+///
+/// ```xml
+/// <settings>
+///     <setting key="music" type="bool" default="true">Play music</setting>
+///     <setting key="lives" type="int" min="1" max="9" default="3">Lives</setting>
+///     <setting key="speed" type="float" default="1.0">Speed</setting>
+///     <setting key="mode" type="enum" options="easy,normal,hard" default="normal">Difficulty</setting>
+///     <setting key="player" type="string" default="">Player name</setting>
+///     <setting key="jump" type="keybind" default="Space">Jump</setting>
+/// </settings>
+/// ```
+#[derive(Clone, Debug)]
+pub struct SettingSpec {
+    pub key: String,
+    pub label: String,
+    pub kind: SettingKind,
+    pub default: SettingValue,
+}
+
+impl SettingSpec {
+    /// Reads the attributes of a `<setting>` element; the label comes from its text.
+    pub fn from_element(e: &BytesStart) -> Result<SettingSpec, String> {
+        let key = attribute(e, "key").ok_or("A setting in index.xml has no key")?;
+        let kind_name = attribute(e, "type").unwrap_or_else(|| "string".to_string());
+        let number = |name: &str| -> Result<Option<f64>, String> {
+            attribute(e, name)
+                .map(|v| v.parse::<f64>().map_err(|_| format!("Setting '{}' has an invalid {} '{}'", key, name, v)))
+                .transpose()
+        };
+        let kind = match kind_name.as_str() {
+            "bool" => SettingKind::Bool,
+            "int" => SettingKind::Int {
+                min: number("min")?.map(|v| v as i64),
+                max: number("max")?.map(|v| v as i64),
+            },
+            "float" => SettingKind::Float { min: number("min")?, max: number("max")? },
+            "enum" => {
+                let options: Vec<String> = attribute(e, "options")
+                    .unwrap_or_default()
+                    .split(',')
+                    .map(|option| option.trim().to_string())
+                    .filter(|option| !option.is_empty())
+                    .collect();
+                if options.is_empty() {
+                    return Err(format!("Enum setting '{}' has no options", key));
+                }
+                SettingKind::Enum(options)
+            },
+            "string" => SettingKind::String,
+            "keybind" => SettingKind::Keybind,
+            other => return Err(format!("Setting '{}' has unknown type '{}'", key, other)),
+        };
+        let mut spec = SettingSpec { label: key.clone(), key, kind, default: SettingValue::Bool(false) };
+        spec.default = spec.fallback();
+        if let Some(default) = attribute(e, "default") {
+            spec.default = spec.parse(&default)
+                .ok_or_else(|| format!("Setting '{}' has an invalid default '{}'", spec.key, default))?;
+        }
+        Ok(spec)
+    }
+
+    /// The default when the manifest gives none.
+    fn fallback(&self) -> SettingValue {
+        match &self.kind {
+            SettingKind::Bool => SettingValue::Bool(false),
+            SettingKind::Int { min, .. } => SettingValue::Int(min.unwrap_or(0).max(0)),
+            SettingKind::Float { min, .. } => SettingValue::Float(min.unwrap_or(0.0).max(0.0)),
+            SettingKind::Enum(options) => SettingValue::Text(options[0].clone()),
+            SettingKind::String => SettingValue::Text(String::new()),
+            SettingKind::Keybind => SettingValue::Text("Space".to_string()),
+        }
+    }
+
+    /// Parses a stored value, rejecting anything outside the declared type or range.
+    pub fn parse(&self, text: &str) -> Option<SettingValue> {
+        let text = text.trim();
+        let value = match &self.kind {
+            SettingKind::Bool => SettingValue::Bool(text.parse().ok()?),
+            SettingKind::Int { .. } => SettingValue::Int(text.parse().ok()?),
+            SettingKind::Float { .. } => SettingValue::Float(text.parse().ok()?),
+            SettingKind::Enum(options) => {
+                options.iter().find(|option| *option == text)?;
+                SettingValue::Text(text.to_string())
+            },
+            SettingKind::String => SettingValue::Text(text.to_string()),
+            SettingKind::Keybind => {
+                egui::Key::from_name(text)?;
+                SettingValue::Text(text.to_string())
+            },
+        };
+        self.is_valid(&value).then_some(value)
+    }
+
+    pub fn is_valid(&self, value: &SettingValue) -> bool {
+        match (&self.kind, value) {
+            (SettingKind::Bool, SettingValue::Bool(_)) => true,
+            (SettingKind::Int { min, max }, SettingValue::Int(v)) => {
+                min.is_none_or(|min| *v >= min) && max.is_none_or(|max| *v <= max)
+            },
+            (SettingKind::Float { min, max }, SettingValue::Float(v)) => {
+                v.is_finite() && min.is_none_or(|min| *v >= min) && max.is_none_or(|max| *v <= max)
+            },
+            (SettingKind::Enum(options), SettingValue::Text(v)) => options.contains(v),
+            (SettingKind::String, SettingValue::Text(_)) => true,
+            (SettingKind::Keybind, SettingValue::Text(v)) => egui::Key::from_name(v).is_some(),
+            _ => false,
+        }
+    }
+}
+
+/// A plugin's setting values, persisted in `CONFIG/settings/<plugin>.xml`:
+/// `<settings><value key="lives">5</value></settings>`. Declared settings that were
+/// never changed, or whose stored value no longer fits, read as their default.
+#[derive(Clone, Default)]
+pub struct PluginSettings {
+    path: PathBuf,
+    pub specs: Vec<SettingSpec>,
+    values: BTreeMap<String, SettingValue>,
+}
+
+impl PluginSettings {
+    pub fn load(config_dir: &Path, plugin: &str, specs: &[SettingSpec]) -> PluginSettings {
+        PluginSettings::load_file(config_dir.join("settings").join(format!("{}.xml", plugin)), specs)
+    }
+
+    /// Reads values for `specs` from any file in the same format.
+    pub fn load_file(path: PathBuf, specs: &[SettingSpec]) -> PluginSettings {
+        let mut values = BTreeMap::new();
+        if let Ok(xml_content) = fs::read_to_string(&path) {
+            let mut reader = Reader::from_str(&xml_content);
+            reader.trim_text(true);
+
+            let mut buf = Vec::new();
+            let mut current_key = None;
+            loop {
+                match reader.read_event_into(&mut buf) {
+                    Ok(Event::Start(ref e)) if e.name().as_ref() == b"value" => {
+                        current_key = attribute(e, "key");
+                    },
+                    Ok(Event::Text(ref e)) => {
+                        let text = e.unescape().unwrap_or_default().to_string();
+                        let spec = current_key.take().and_then(|key| specs.iter().find(|spec| spec.key == key));
+                        if let Some(spec) = spec {
+                            if let Some(value) = spec.parse(&text) {
+                                values.insert(spec.key.clone(), value);
+                            }
+                        }
+                    },
+                    Ok(Event::Eof) => break,
+                    Err(_) => break,
+                    _ => {}
+                }
+                buf.clear();
+            }
+        }
+        PluginSettings { path, specs: specs.to_vec(), values }
+    }
+
+    pub fn get(&self, key: &str) -> Option<&SettingValue> {
+        self.values.get(key).or_else(|| self.specs.iter().find(|spec| spec.key == key).map(|spec| &spec.default))
+    }
+
+    pub fn set(&mut self, key: &str, value: SettingValue) -> Result<(), String> {
+        let spec = self.specs.iter().find(|spec| spec.key == key)
+            .ok_or_else(|| format!("Unknown setting '{}'", key))?;
+        if !spec.is_valid(&value) {
+            return Err(format!("'{}' is not a valid value for {}", value, spec.label));
+        }
+        if value == spec.default {
+            self.values.remove(key);
+        } else {
+            self.values.insert(key.to_string(), value);
+        }
+        self.save()
+    }
+
+    fn save(&self) -> Result<(), String> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent).map_err(|e| format!("Failed to create {:?}: {}", parent, e))?;
+        }
+        let mut xml = String::from("<settings>\n");
+        for (key, value) in &self.values {
+            xml.push_str(&format!("    <value key=\"{}\">{}</value>\n", escape(key), escape(&value.to_string())));
+        }
+        xml.push_str("</settings>\n");
+        fs::write(&self.path, xml)
+            .map_err(|e| format!("Failed to save settings to {:?}: {}", self.path, e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manifest::Manifest;
+    use crate::test_dir::TestDir;
+
+    fn specs() -> Vec<SettingSpec> {
+        Manifest::parse(r#"<plugin><name>Snake</name><settings>
+            <setting key="music" type="bool" default="true">Play music</setting>
+            <setting key="lives" type="int" min="1" max="9" default="3">Lives</setting>
+            <setting key="speed" type="float" default="1.5">Speed</setting>
+            <setting key="mode" type="enum" options="easy, normal, hard" default="normal">Difficulty</setting>
+            <setting key="player" type="string">Player name</setting>
+            <setting key="jump" type="keybind" default="Space">Jump</setting>
+        </settings></plugin>"#).unwrap().settings
+    }
+
+    #[test]
+    fn specs_come_from_the_manifest() {
+        let specs = specs();
+        assert_eq!(specs.len(), 6);
+        assert_eq!(specs[1].kind, SettingKind::Int { min: Some(1), max: Some(9) });
+        assert_eq!(specs[1].label, "Lives");
+        assert_eq!(specs[3].kind, SettingKind::Enum(vec!["easy".into(), "normal".into(), "hard".into()]));
+        assert_eq!(specs[4].default, SettingValue::Text(String::new()));
+        assert!(Manifest::parse(r#"<plugin><setting key="x" type="int" default="many"/></plugin>"#).is_err());
+        assert!(Manifest::parse(r#"<plugin><setting key="x" type="colour"/></plugin>"#).is_err());
+    }
+
+    #[test]
+    fn values_survive_saving_and_loading() {
+        let dir = TestDir::new("plugin-settings");
+        let mut settings = PluginSettings::load(dir.path(), "snake", &specs());
+        settings.set("music", SettingValue::Bool(false)).unwrap();
+        settings.set("lives", SettingValue::Int(7)).unwrap();
+        settings.set("speed", SettingValue::Float(2.25)).unwrap();
+        settings.set("mode", SettingValue::Text("hard".into())).unwrap();
+        settings.set("player", SettingValue::Text("Tom & <Jerry>".into())).unwrap();
+        settings.set("jump", SettingValue::Text("W".into())).unwrap();
+
+        let loaded = PluginSettings::load(dir.path(), "snake", &specs());
+        assert_eq!(loaded.get("music"), Some(&SettingValue::Bool(false)));
+        assert_eq!(loaded.get("lives"), Some(&SettingValue::Int(7)));
+        assert_eq!(loaded.get("speed"), Some(&SettingValue::Float(2.25)));
+        assert_eq!(loaded.get("mode"), Some(&SettingValue::Text("hard".into())));
+        assert_eq!(loaded.get("player"), Some(&SettingValue::Text("Tom & <Jerry>".into())));
+        assert_eq!(loaded.get("jump"), Some(&SettingValue::Text("W".into())));
+        assert_eq!(loaded.get("missing"), None);
+    }
+
+    #[test]
+    fn invalid_values_are_refused_and_stale_ones_read_as_default() {
+        let dir = TestDir::new("plugin-settings-invalid");
+        let mut settings = PluginSettings::load(dir.path(), "snake", &specs());
+        assert!(settings.set("lives", SettingValue::Int(10)).is_err());
+        assert!(settings.set("lives", SettingValue::Text("5".into())).is_err());
+        assert!(settings.set("mode", SettingValue::Text("insane".into())).is_err());
+        assert!(settings.set("jump", SettingValue::Text("NotAKey".into())).is_err());
+        assert!(settings.set("unknown", SettingValue::Bool(true)).is_err());
+
+        dir.write("settings/snake.xml", r#"<settings><value key="lives">42</value><value key="speed">fast</value></settings>"#);
+        let stale = PluginSettings::load(dir.path(), "snake", &specs());
+        assert_eq!(stale.get("lives"), Some(&SettingValue::Int(3)));
+        assert_eq!(stale.get("speed"), Some(&SettingValue::Float(1.5)));
+
+        // going back to the default drops the stored value
+        settings.set("lives", SettingValue::Int(5)).unwrap();
+        settings.set("lives", SettingValue::Int(3)).unwrap();
+        assert!(!fs::read_to_string(dir.path().join("settings/snake.xml")).unwrap().contains("lives"));
+    }
+}
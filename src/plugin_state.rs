@@ -0,0 +1,172 @@
+use crate::manifest::attribute;
+use quick_xml::escape::escape;
+use quick_xml::events::Event;
+use quick_xml::reader::Reader;
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Which installed plugins are switched off, persisted in `CONFIG/plugins.xml`:
+/// `<plugins><plugin name="snake" enabled="false"/></plugins>`. Plugins not listed
+/// are enabled; every plugin stays in `CONFIG/plugins` either way.
+#[derive(Default)]
+pub struct PluginStates {
+    path: PathBuf,
+    disabled: BTreeSet<String>,
+}
+
+impl PluginStates {
+    pub fn load(config_dir: &Path) -> PluginStates {
+        let path = config_dir.join("plugins.xml");
+        let mut disabled = BTreeSet::new();
+        if let Ok(xml_content) = fs::read_to_string(&path) {
+            let mut reader = Reader::from_str(&xml_content);
+            reader.trim_text(true);
+
+            let mut buf = Vec::new();
+            loop {
+                match reader.read_event_into(&mut buf) {
+                    Ok(Event::Empty(ref e)) | Ok(Event::Start(ref e)) if e.name().as_ref() == b"plugin" => {
+                        if let (Some(name), Some("false")) = (attribute(e, "name"), attribute(e, "enabled").as_deref()) {
+                            disabled.insert(name);
+                        }
+                    },
+                    Ok(Event::Eof) => break,
+                    Err(_) => break,
+                    _ => {}
+                }
+                buf.clear();
+            }
+        }
+        PluginStates { path, disabled }
+    }
+
+    pub fn is_enabled(&self, plugin: &str) -> bool {
+        !self.disabled.contains(plugin)
+    }
+
+    pub fn set_enabled(&mut self, plugin: &str, enabled: bool) -> Result<(), String> {
+        if enabled {
+            self.disabled.remove(plugin);
+        } else {
+            self.disabled.insert(plugin.to_string());
+        }
+        self.save()
+    }
+
+    /// Forgets a plugin that was removed.
+    pub fn forget(&mut self, plugin: &str) -> Result<(), String> {
+        if self.disabled.remove(plugin) {
+            self.save()?;
+        }
+        Ok(())
+    }
+
+    fn save(&self) -> Result<(), String> {
+        let mut xml = String::from("<plugins>\n");
+        for plugin in &self.disabled {
+            xml.push_str(&format!("    <plugin name=\"{}\" enabled=\"false\"/>\n", escape(plugin)));
+        }
+        xml.push_str("</plugins>\n");
+        fs::write(&self.path, xml)
+            .map_err(|e| format!("Failed to save plugin states to {:?}: {}", self.path, e))
+    }
+
+    /// Older versions disabled plugins by moving them to `CONFIG/wastebasket`. Moves them
+    /// back into `CONFIG/plugins` as disabled plugins and removes the folder once empty.
+    pub fn migrate_wastebasket(&mut self, config_dir: &Path) -> Result<(), String> {
+        let wastebasket_dir = config_dir.join("wastebasket");
+        let Ok(entries) = fs::read_dir(&wastebasket_dir) else {
+            return Ok(());
+        };
+        let plugins_dir = config_dir.join("plugins");
+        fs::create_dir_all(&plugins_dir).map_err(|e| format!("Failed to create {:?}: {}", plugins_dir, e))?;
+        let mut failures = Vec::new();
+        for entry in entries.flatten() {
+            if !entry.path().is_dir() {
+                continue;
+            }
+            let name = entry.file_name().to_string_lossy().to_string();
+            let target = plugins_dir.join(&name);
+            if target.exists() {
+                failures.push(format!("{} (a plugin with that name is already installed)", name));
+                continue;
+            }
+            match fs::rename(entry.path(), &target) {
+                Ok(()) => {
+                    self.disabled.insert(name);
+                },
+                Err(e) => failures.push(format!("{} ({})", name, e)),
+            }
+        }
+        self.save()?;
+        let _ = fs::remove_dir(&wastebasket_dir);
+        if failures.is_empty() {
+            Ok(())
+        } else {
+            Err(format!(
+                "Could not move these disabled plugins out of {:?}: {}",
+                wastebasket_dir,
+                failures.join(", ")
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_dir::TestDir;
+
+    #[test]
+    fn disabled_plugins_survive_saving_and_loading() {
+        let config = TestDir::new("plugin-state");
+        let mut states = PluginStates::load(config.path());
+        assert!(states.is_enabled("snake"));
+        states.set_enabled("snake", false).unwrap();
+        states.set_enabled("a \"quoted\" <name>", false).unwrap();
+
+        let mut states = PluginStates::load(config.path());
+        assert!(!states.is_enabled("snake"));
+        assert!(!states.is_enabled("a \"quoted\" <name>"));
+        assert!(states.is_enabled("game"));
+        states.set_enabled("snake", true).unwrap();
+        states.forget("a \"quoted\" <name>").unwrap();
+        let states = PluginStates::load(config.path());
+        assert!(states.is_enabled("snake"));
+        assert!(states.is_enabled("a \"quoted\" <name>"));
+    }
+
+    #[test]
+    fn wastebasket_plugins_come_back_disabled() {
+        let config = TestDir::new("plugin-state-wastebasket");
+        config.write("wastebasket/snake/index.lua", "return {}");
+        config.write("wastebasket/clash/index.lua", "return 'old'");
+        config.write("wastebasket/notes.txt", "not a plugin");
+        config.write("plugins/clash/index.lua", "return 'new'");
+
+        let mut states = PluginStates::load(config.path());
+        let error = states.migrate_wastebasket(config.path()).unwrap_err();
+        assert!(error.contains("clash"), "{}", error);
+        assert!(config.path().join("plugins/snake/index.lua").is_file());
+        assert_eq!(fs::read_to_string(config.path().join("plugins/clash/index.lua")).unwrap(), "return 'new'");
+        // the clashing plugin keeps the folder around
+        assert!(config.path().join("wastebasket/clash").is_dir());
+
+        let states = PluginStates::load(config.path());
+        assert!(!states.is_enabled("snake"));
+        assert!(states.is_enabled("clash"));
+    }
+
+    #[test]
+    fn migrating_removes_the_empty_wastebasket() {
+        let config = TestDir::new("plugin-state-empty");
+        config.write("wastebasket/snake/index.lua", "return {}");
+        let mut states = PluginStates::load(config.path());
+        states.migrate_wastebasket(config.path()).unwrap();
+        assert!(!config.path().join("wastebasket").exists());
+        assert!(!states.is_enabled("snake"));
+        // nothing left to migrate
+        states.migrate_wastebasket(config.path()).unwrap();
+    }
+}
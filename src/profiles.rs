@@ -0,0 +1,294 @@
+use crate::manifest::attribute;
+use crate::updater::Preference;
+use quick_xml::escape::escape;
+use quick_xml::events::Event;
+use quick_xml::reader::Reader;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+/// A plugin as a profile recorded it.
+#[derive(Clone, Default, PartialEq)]
+pub struct LockedPlugin {
+    pub name: String,
+    pub enabled: bool,
+    pub version: String,
+    /// Full commit id of the installed copy, for plugins installed from git.
+    pub revision: Option<String>,
+    /// The manifest's update url, used to fetch the plugin on another machine.
+    pub source: Option<String>,
+    pub preference: Preference,
+    /// Setting key -> value as written in `CONFIG/settings/<plugin>.xml`.
+    pub settings: BTreeMap<String, String>,
+}
+
+/// A named set of plugins with their enabled state, pins, revisions and settings.
+/// Exported on its own it doubles as a lockfile:
+/// `<profile name="modded"><plugin name="snake" enabled="true" revision="..."
+/// source="..."><setting key="lives">5</setting></plugin></profile>`.
+#[derive(Clone, Default)]
+pub struct Profile {
+    pub name: String,
+    pub plugins: Vec<LockedPlugin>,
+}
+
+impl Profile {
+    pub fn get(&self, plugin: &str) -> Option<&LockedPlugin> {
+        self.plugins.iter().find(|locked| locked.name == plugin)
+    }
+
+    fn write(&self, xml: &mut String, indent: &str) {
+        xml.push_str(&format!("{}<profile name=\"{}\">\n", indent, escape(&self.name)));
+        for plugin in &self.plugins {
+            xml.push_str(&format!(
+                "{}    <plugin name=\"{}\" enabled=\"{}\"",
+                indent, escape(&plugin.name), plugin.enabled
+            ));
+            if !plugin.version.is_empty() {
+                xml.push_str(&format!(" version=\"{}\"", escape(&plugin.version)));
+            }
+            if let Some(revision) = &plugin.revision {
+                xml.push_str(&format!(" revision=\"{}\"", escape(revision)));
+            }
+            if let Some(source) = &plugin.source {
+                xml.push_str(&format!(" source=\"{}\"", escape(source)));
+            }
+            if let Some(pin) = &plugin.preference.pin {
+                xml.push_str(&format!(" pin=\"{}\"", escape(pin)));
+            }
+            if plugin.preference.beta {
+                xml.push_str(" beta=\"true\"");
+            }
+            if plugin.settings.is_empty() {
+                xml.push_str("/>\n");
+                continue;
+            }
+            xml.push_str(">\n");
+            for (key, value) in &plugin.settings {
+                xml.push_str(&format!(
+                    "{}        <setting key=\"{}\">{}</setting>\n",
+                    indent, escape(key), escape(value)
+                ));
+            }
+            xml.push_str(&format!("{}    </plugin>\n", indent));
+        }
+        xml.push_str(&format!("{}</profile>\n", indent));
+    }
+
+    /// Writes the profile as a standalone lockfile.
+    pub fn export(&self, path: &Path) -> Result<(), String> {
+        let mut xml = String::new();
+        self.write(&mut xml, "");
+        fs::write(path, xml).map_err(|e| format!("Failed to write {:?}: {}", path, e))
+    }
+
+    /// Reads a lockfile written by [`Profile::export`].
+    pub fn import(path: &Path) -> Result<Profile, String> {
+        let xml_content = fs::read_to_string(path).map_err(|e| format!("Failed to read {:?}: {}", path, e))?;
+        let (_, mut profiles) = parse(&xml_content)?;
+        if profiles.len() != 1 {
+            return Err(format!("{:?} is not a profile lockfile", path));
+        }
+        Ok(profiles.remove(0))
+    }
+}
+
+/// Locked plugin names become folder names under `CONFIG/plugins`, so a lockfile from
+/// elsewhere must not be able to point outside of it.
+fn is_folder_name(name: &str) -> bool {
+    let mut components = Path::new(name).components();
+    matches!((components.next(), components.next()), (Some(Component::Normal(_)), None))
+        && !name.contains(['/', '\\'])
+}
+
+/// Returns the `active` attribute of `<profiles>` and every `<profile>` in the document.
+fn parse(xml_content: &str) -> Result<(Option<String>, Vec<Profile>), String> {
+    let mut reader = Reader::from_str(xml_content);
+    reader.trim_text(true);
+
+    let mut active = None;
+    let mut profiles: Vec<Profile> = Vec::new();
+    let mut setting_key = None;
+    let mut buf = Vec::new();
+    loop {
+        let event = reader.read_event_into(&mut buf);
+        let empty = matches!(event, Ok(Event::Empty(_)));
+        match event {
+            Ok(Event::Start(ref e)) | Ok(Event::Empty(ref e)) => match e.name().as_ref() {
+                b"profiles" => active = attribute(e, "active"),
+                b"profile" => {
+                    let name = attribute(e, "name").ok_or("A profile has no name")?;
+                    profiles.push(Profile { name, plugins: Vec::new() });
+                },
+                b"plugin" => {
+                    let profile = profiles.last_mut().ok_or("<plugin> outside of a <profile>")?;
+                    let name = attribute(e, "name").ok_or("A locked plugin has no name")?;
+                    if !is_folder_name(&name) {
+                        return Err(format!("'{}' is not a valid plugin name", name));
+                    }
+                    profile.plugins.push(LockedPlugin {
+                        name,
+                        enabled: attribute(e, "enabled").as_deref() != Some("false"),
+                        version: attribute(e, "version").unwrap_or_default(),
+                        revision: attribute(e, "revision"),
+                        source: attribute(e, "source"),
+                        preference: Preference {
+                            pin: attribute(e, "pin"),
+                            beta: attribute(e, "beta").as_deref() == Some("true"),
+                        },
+                        settings: BTreeMap::new(),
+                    });
+                },
+                b"setting" if !empty => {
+                    setting_key = attribute(e, "key");
+                    let plugin = profiles.last_mut().and_then(|profile| profile.plugins.last_mut());
+                    if let (Some(key), Some(plugin)) = (&setting_key, plugin) {
+                        plugin.settings.insert(key.clone(), String::new());
+                    }
+                },
+                _ => {},
+            },
+            Ok(Event::Text(ref e)) => {
+                let plugin = profiles.last_mut().and_then(|profile| profile.plugins.last_mut());
+                if let (Some(key), Some(plugin)) = (setting_key.take(), plugin) {
+                    plugin.settings.insert(key, e.unescape().unwrap_or_default().to_string());
+                }
+            },
+            Ok(Event::End(_)) => setting_key = None,
+            Ok(Event::Eof) => break,
+            Err(e) => return Err(format!("Invalid profile XML at position {}: {}", reader.buffer_position(), e)),
+            _ => {}
+        }
+        buf.clear();
+    }
+    Ok((active, profiles))
+}
+
+/// Saved profiles and which one was applied last, persisted in `CONFIG/profiles.xml`.
+#[derive(Default)]
+pub struct Profiles {
+    path: PathBuf,
+    pub profiles: Vec<Profile>,
+    pub active: Option<String>,
+}
+
+impl Profiles {
+    pub fn load(config_dir: &Path) -> Profiles {
+        let path = config_dir.join("profiles.xml");
+        let (active, profiles) = fs::read_to_string(&path).ok()
+            .and_then(|xml_content| parse(&xml_content).ok())
+            .unwrap_or_default();
+        Profiles { path, profiles, active }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Profile> {
+        self.profiles.iter().find(|profile| profile.name == name)
+    }
+
+    /// Adds the profile, replacing a saved profile with the same name.
+    pub fn save_profile(&mut self, profile: Profile) -> Result<(), String> {
+        match self.profiles.iter_mut().find(|saved| saved.name == profile.name) {
+            Some(saved) => *saved = profile,
+            None => self.profiles.push(profile),
+        }
+        self.save()
+    }
+
+    pub fn remove(&mut self, name: &str) -> Result<(), String> {
+        self.profiles.retain(|profile| profile.name != name);
+        if self.active.as_deref() == Some(name) {
+            self.active = None;
+        }
+        self.save()
+    }
+
+    pub fn set_active(&mut self, name: &str) -> Result<(), String> {
+        self.active = Some(name.to_string());
+        self.save()
+    }
+
+    fn save(&self) -> Result<(), String> {
+        let mut xml = match &self.active {
+            Some(active) => format!("<profiles active=\"{}\">\n", escape(active)),
+            None => String::from("<profiles>\n"),
+        };
+        for profile in &self.profiles {
+            profile.write(&mut xml, "    ");
+        }
+        xml.push_str("</profiles>\n");
+        fs::write(&self.path, xml)
+            .map_err(|e| format!("Failed to save profiles to {:?}: {}", self.path, e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_dir::TestDir;
+
+    fn modded() -> Profile {
+        let mut settings = BTreeMap::new();
+        settings.insert("lives".to_string(), "5".to_string());
+        settings.insert("title".to_string(), "Tom & \"Jerry\" <3".to_string());
+        settings.insert("empty".to_string(), String::new());
+        Profile {
+            name: "modded & co".to_string(),
+            plugins: vec![
+                LockedPlugin {
+                    name: "snake".to_string(),
+                    enabled: true,
+                    version: "1.2.0".to_string(),
+                    revision: Some("0123456789abcdef0123456789abcdef01234567".to_string()),
+                    source: Some("https://example.com/snake.git?a=1&b=2".to_string()),
+                    preference: Preference { pin: Some("v1.2.0".to_string()), beta: true },
+                    settings,
+                },
+                LockedPlugin { name: "lib".to_string(), enabled: false, ..Default::default() },
+            ],
+        }
+    }
+
+    fn assert_same(read: &Profile, written: &Profile) {
+        assert_eq!(read.name, written.name);
+        assert_eq!(read.plugins.len(), written.plugins.len());
+        for (read, written) in read.plugins.iter().zip(&written.plugins) {
+            assert!(read == written, "{} changed on the way through XML", written.name);
+        }
+    }
+
+    #[test]
+    fn profiles_survive_saving_and_loading() {
+        let dir = TestDir::new("profiles");
+        let mut profiles = Profiles::load(dir.path());
+        profiles.save_profile(modded()).unwrap();
+        profiles.save_profile(Profile { name: "vanilla".to_string(), plugins: Vec::new() }).unwrap();
+        profiles.set_active("modded & co").unwrap();
+
+        let loaded = Profiles::load(dir.path());
+        assert_eq!(loaded.active.as_deref(), Some("modded & co"));
+        assert_eq!(loaded.profiles.len(), 2);
+        assert_same(loaded.get("modded & co").unwrap(), &modded());
+        assert!(loaded.get("vanilla").unwrap().plugins.is_empty());
+    }
+
+    #[test]
+    fn lockfile_survives_export_and_import() {
+        let dir = TestDir::new("lockfile");
+        let path = dir.path().join("modded.xml");
+        modded().export(&path).unwrap();
+        assert_same(&Profile::import(&path).unwrap(), &modded());
+
+        fs::write(&path, "<profiles/>").unwrap();
+        assert!(Profile::import(&path).is_err());
+    }
+
+    #[test]
+    fn rejects_plugin_names_that_are_not_a_single_folder() {
+        for name in ["..", "../outside", "a/b", "a\\b", "/etc", ".", "", "snake/"] {
+            let xml = format!(r#"<profile name="p"><plugin name="{}" enabled="true"/></profile>"#, name);
+            assert!(parse(&xml).is_err(), "{:?} was accepted", name);
+        }
+        let (_, profiles) = parse(r#"<profile name="p"><plugin name="snake" enabled="true"/></profile>"#).unwrap();
+        assert_eq!(profiles[0].plugins[0].name, "snake");
+    }
+}
@@ -0,0 +1,452 @@
+use crate::manifest::{attribute, Dependency};
+use crate::package::{valid_plugin_name, valid_version};
+use crate::updater::compare_versions;
+use quick_xml::escape::escape;
+use quick_xml::events::Event;
+use quick_xml::reader::Reader;
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::thread;
+
+/// Registry files and downloads larger than this are refused.
+const MAX_DOWNLOAD: u64 = 64 * 1024 * 1024;
+
+/// One plugin version listed in a registry index:
+///
+/// ```xml
+/// <registry name="Community">
+///     <plugin id="snake">
+///         <name>Snake</name>
+///         <description>...</description>
+///         <version>1.2.0</version>
+///         <logo>snake.png</logo>
+///         <download>snake-1.2.0.zip</download>
+///         <dependencies><depends version="0.1.0">lib</depends></dependencies>
+///     </plugin>
+/// </registry>
+/// ```
+///
+/// Relative logo and download locations are resolved against the index itself.
+#[derive(Clone, Debug, Default)]
+pub struct RegistryEntry {
+    pub id: String,
+    pub name: String,
+    pub description: String,
+    pub version: String,
+    pub logo: Option<String>,
+    pub download: String,
+    pub dependencies: Vec<Dependency>,
+    /// Name of the registry this entry came from.
+    pub registry: String,
+}
+
+/// Everything the configured registries offer, with the newest version of each plugin first.
+#[derive(Default)]
+pub struct Catalog {
+    pub entries: Vec<RegistryEntry>,
+    pub logos: HashMap<String, Vec<u8>>,
+    pub errors: Vec<String>,
+}
+
+impl Catalog {
+    /// The newest listed version of a plugin that is at least `minimum`.
+    pub fn find(&self, id: &str, minimum: Option<&str>) -> Option<&RegistryEntry> {
+        self.entries.iter()
+            .filter(|entry| entry.id == id)
+            .filter(|entry| minimum.is_none_or(|min| compare_versions(&entry.version, min) != Ordering::Less))
+            .max_by(|a, b| compare_versions(&a.version, &b.version))
+    }
+
+    /// One entry per plugin, the newest version, sorted by name.
+    pub fn latest(&self) -> Vec<&RegistryEntry> {
+        let mut seen = HashSet::new();
+        let mut latest: Vec<_> = self.entries.iter()
+            .filter(|entry| seen.insert(entry.id.as_str()))
+            .filter_map(|entry| self.find(&entry.id, None))
+            .collect();
+        latest.sort_by_key(|entry| entry.name.to_lowercase());
+        latest
+    }
+}
+
+/// Registry index locations from `CONFIG/registries.xml`:
+/// `<registries><registry>path or url</registry></registries>`.
+#[derive(Default)]
+pub struct Registries {
+    path: PathBuf,
+    pub sources: Vec<String>,
+}
+
+impl Registries {
+    pub fn load(config_dir: &Path) -> Registries {
+        let path = config_dir.join("registries.xml");
+        let mut sources = Vec::new();
+        if let Ok(xml_content) = fs::read_to_string(&path) {
+            let mut reader = Reader::from_str(&xml_content);
+            reader.trim_text(true);
+
+            let mut buf = Vec::new();
+            loop {
+                match reader.read_event_into(&mut buf) {
+                    Ok(Event::Text(ref e)) => {
+                        let source = e.unescape().unwrap_or_default().trim().to_string();
+                        if !source.is_empty() {
+                            // relative paths are relative to the config folder
+                            sources.push(resolve_location(&path.to_string_lossy(), &source));
+                        }
+                    },
+                    Ok(Event::Eof) => break,
+                    Err(_) => break,
+                    _ => {}
+                }
+                buf.clear();
+            }
+        }
+        Registries { path, sources }
+    }
+
+    pub fn add(&mut self, source: &str) -> Result<(), String> {
+        let source = source.trim();
+        if !source.is_empty() && !self.sources.iter().any(|s| s == source) {
+            self.sources.push(source.to_string());
+        }
+        self.save()
+    }
+
+    pub fn remove(&mut self, source: &str) -> Result<(), String> {
+        self.sources.retain(|s| s != source);
+        self.save()
+    }
+
+    fn save(&self) -> Result<(), String> {
+        let mut xml = String::from("<registries>\n");
+        for source in &self.sources {
+            xml.push_str(&format!("    <registry>{}</registry>\n", escape(source)));
+        }
+        xml.push_str("</registries>\n");
+        fs::write(&self.path, xml)
+            .map_err(|e| format!("Failed to save registries to {:?}: {}", self.path, e))
+    }
+}
+
+fn is_url(location: &str) -> bool {
+    location.starts_with("http://") || location.starts_with("https://")
+}
+
+fn resolve_location(base: &str, location: &str) -> String {
+    if is_url(location) || Path::new(location).is_absolute() {
+        return location.to_string();
+    }
+    if is_url(base) {
+        let directory = &base[..base.rfind('/').map(|i| i + 1).unwrap_or(base.len())];
+        return format!("{}{}", directory, location);
+    }
+    Path::new(base)
+        .parent()
+        .unwrap_or(Path::new(""))
+        .join(location)
+        .to_string_lossy()
+        .to_string()
+}
+
+/// Reads a local file or downloads a URL.
+pub fn read_location(location: &str) -> Result<Vec<u8>, String> {
+    if !is_url(location) {
+        return fs::read(location).map_err(|e| format!("Failed to read {}: {}", location, e));
+    }
+    let response = ureq::get(location)
+        .call()
+        .map_err(|e| format!("Failed to download {}: {}", location, e))?;
+    let mut data = Vec::new();
+    response.into_reader()
+        .take(MAX_DOWNLOAD + 1)
+        .read_to_end(&mut data)
+        .map_err(|e| format!("Failed to download {}: {}", location, e))?;
+    if data.len() as u64 > MAX_DOWNLOAD {
+        return Err(format!("{} is too large", location));
+    }
+    Ok(data)
+}
+
+pub fn parse_index(source: &str, xml_content: &str) -> Result<Vec<RegistryEntry>, String> {
+    let mut reader = Reader::from_str(xml_content);
+    reader.trim_text(true);
+
+    let mut entries = Vec::new();
+    let mut registry = source.to_string();
+    let mut current: Option<RegistryEntry> = None;
+    let mut dependency_version = None;
+    let mut buf = Vec::new();
+    let mut current_element = String::new();
+    loop {
+        match reader.read_event_into(&mut buf) {
+            Ok(Event::Start(ref e)) => {
+                current_element = String::from_utf8_lossy(e.name().as_ref()).to_string();
+                match current_element.as_str() {
+                    "registry" => {
+                        if let Some(name) = attribute(e, "name") {
+                            registry = name;
+                        }
+                    },
+                    "plugin" => {
+                        let id = attribute(e, "id")
+                            .ok_or_else(|| format!("A plugin in {} has no id", source))?;
+                        current = Some(RegistryEntry { id, registry: registry.clone(), ..Default::default() });
+                    },
+                    "depends" => dependency_version = attribute(e, "version"),
+                    _ => {}
+                }
+            },
+            Ok(Event::Text(ref e)) => {
+                let Some(entry) = current.as_mut() else {
+                    buf.clear();
+                    continue;
+                };
+                let text = e.unescape().unwrap_or_default().trim().to_string();
+                match current_element.as_str() {
+                    "name" => entry.name = text,
+                    "description" => entry.description = text,
+                    "version" => entry.version = text,
+                    "logo" => entry.logo = Some(resolve_location(source, &text)),
+                    "download" => entry.download = resolve_location(source, &text),
+                    "depends" => entry.dependencies.push(Dependency { name: text, version: dependency_version.take() }),
+                    _ => {}
+                }
+            },
+            Ok(Event::End(ref e)) if e.name().as_ref() == b"plugin" => {
+                if let Some(mut entry) = current.take() {
+                    // both end up in file and folder names
+                    if !valid_plugin_name(&entry.id) {
+                        return Err(format!("'{}' in {} is not a valid plugin id", entry.id, source));
+                    }
+                    if !entry.version.is_empty() && !valid_version(&entry.version) {
+                        return Err(format!("{} in {} has an invalid version '{}'", entry.id, source, entry.version));
+                    }
+                    if entry.download.is_empty() {
+                        return Err(format!("{} in {} has no download location", entry.id, source));
+                    }
+                    if entry.name.is_empty() {
+                        entry.name = entry.id.clone();
+                    }
+                    entries.push(entry);
+                }
+            },
+            Ok(Event::Eof) => break,
+            Err(e) => return Err(format!("Invalid registry {} at position {}: {}", source, reader.buffer_position(), e)),
+            _ => {}
+        }
+        buf.clear();
+    }
+    Ok(entries)
+}
+
+/// Reads every registry, and the logos they list, on a background thread.
+pub fn spawn_load(sources: Vec<String>) -> mpsc::Receiver<Catalog> {
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let mut catalog = Catalog::default();
+        for source in sources {
+            let entries = read_location(&source)
+                .map(|data| String::from_utf8_lossy(&data).to_string())
+                .and_then(|xml| parse_index(&source, &xml));
+            match entries {
+                Ok(entries) => catalog.entries.extend(entries),
+                Err(e) => catalog.errors.push(e),
+            }
+        }
+        for entry in catalog.latest().into_iter().cloned().collect::<Vec<_>>() {
+            if let Some(logo) = &entry.logo {
+                if let Ok(data) = read_location(logo) {
+                    catalog.logos.insert(entry.id.clone(), data);
+                }
+            }
+        }
+        let _ = sender.send(catalog);
+    });
+    receiver
+}
+
+/// Entries to install for `id`, dependencies first. Dependencies that are already
+/// installed at a new enough version are left alone; `id` itself is always included.
+pub fn plan_install(id: &str, catalog: &Catalog, installed: &HashMap<String, String>) -> Result<Vec<RegistryEntry>, String> {
+    fn visit(
+        id: &str,
+        minimum: Option<&str>,
+        required_by: Option<&str>,
+        catalog: &Catalog,
+        installed: &HashMap<String, String>,
+        stack: &mut Vec<String>,
+        plan: &mut Vec<RegistryEntry>,
+    ) -> Result<(), String> {
+        if stack.iter().any(|s| s == id) {
+            stack.push(id.to_string());
+            return Err(format!("Dependency cycle: {}", stack.join(" -> ")));
+        }
+        if plan.iter().any(|entry| entry.id == id) {
+            return Ok(());
+        }
+        if required_by.is_some() {
+            let satisfied = installed.get(id).is_some_and(|version| {
+                minimum.is_none_or(|min| compare_versions(version, min) != Ordering::Less)
+            });
+            if satisfied {
+                return Ok(());
+            }
+        }
+        let entry = catalog.find(id, minimum).ok_or_else(|| match (required_by, minimum) {
+            (Some(parent), Some(min)) => format!("{} needs {} {} or newer, which no registry lists", parent, id, min),
+            (Some(parent), None) => format!("{} needs {}, which no registry lists", parent, id),
+            _ => format!("No registry lists {}", id),
+        })?;
+        stack.push(id.to_string());
+        for dependency in &entry.dependencies {
+            visit(&dependency.name, dependency.version.as_deref(), Some(id), catalog, installed, stack, plan)?;
+        }
+        stack.pop();
+        plan.push(entry.clone());
+        Ok(())
+    }
+
+    let mut plan = Vec::new();
+    visit(id, None, None, catalog, installed, &mut Vec::new(), &mut plan)?;
+    Ok(plan)
+}
+
+pub type Downloads = mpsc::Receiver<Result<Vec<(RegistryEntry, PathBuf)>, String>>;
+
+/// Fetches the archives for `plan` on a background thread. Local archives are used
+/// where they are; downloads are saved in `download_dir`.
+pub fn spawn_download(plan: Vec<RegistryEntry>, download_dir: PathBuf) -> Downloads {
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let result = plan.into_iter()
+            .map(|entry| {
+                if !is_url(&entry.download) {
+                    let path = PathBuf::from(&entry.download);
+                    return Ok((entry, path));
+                }
+                let data = read_location(&entry.download)?;
+                fs::create_dir_all(&download_dir).map_err(|e| format!("Failed to create {:?}: {}", download_dir, e))?;
+                let path = download_dir.join(format!("{}-{}.zip", entry.id, entry.version));
+                fs::write(&path, data).map_err(|e| format!("Failed to save {:?}: {}", path, e))?;
+                Ok((entry, path))
+            })
+            .collect();
+        let _ = sender.send(result);
+    });
+    receiver
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_dir::TestDir;
+
+    const INDEX: &str = r#"
+        <registry name="Community">
+            <plugin id="snake">
+                <name>Snake</name>
+                <version>1.2.0</version>
+                <download>snake-1.2.0.zip</download>
+                <dependencies><depends version="0.2">lib</depends></dependencies>
+            </plugin>
+            <plugin id="snake">
+                <version>1.0.0</version>
+                <download>https://example.com/snake-1.0.0.zip</download>
+            </plugin>
+            <plugin id="lib">
+                <version>0.1.0</version>
+                <download>lib-0.1.0.zip</download>
+            </plugin>
+            <plugin id="lib">
+                <version>0.3.0</version>
+                <download>lib-0.3.0.zip</download>
+                <dependencies><depends>core</depends></dependencies>
+            </plugin>
+            <plugin id="core">
+                <version>1.0</version>
+                <download>core.zip</download>
+            </plugin>
+        </registry>
+    "#;
+
+    fn catalog(xml: &str) -> (TestDir, Catalog) {
+        let dir = TestDir::new("registry");
+        let index = dir.write("index.xml", xml).to_string_lossy().to_string();
+        let data = read_location(&index).unwrap();
+        let entries = parse_index(&index, &String::from_utf8_lossy(&data)).unwrap();
+        (dir, Catalog { entries, ..Default::default() })
+    }
+
+    fn ids(plan: &[RegistryEntry]) -> Vec<String> {
+        plan.iter().map(|entry| format!("{}-{}", entry.id, entry.version)).collect()
+    }
+
+    #[test]
+    fn parses_entries_relative_to_the_index() {
+        let (dir, catalog) = catalog(INDEX);
+        assert_eq!(catalog.entries.len(), 5);
+        let snake = &catalog.entries[0];
+        assert_eq!(snake.registry, "Community");
+        assert_eq!(snake.name, "Snake");
+        assert_eq!(PathBuf::from(&snake.download), dir.path().join("snake-1.2.0.zip"));
+        assert_eq!(snake.dependencies, vec![Dependency { name: "lib".to_string(), version: Some("0.2".to_string()) }]);
+        // a plugin without a name goes by its id, and urls are kept as they are
+        assert_eq!(catalog.entries[1].name, "snake");
+        assert_eq!(catalog.entries[1].download, "https://example.com/snake-1.0.0.zip");
+        assert_eq!(catalog.find("snake", None).unwrap().version, "1.2.0");
+        assert_eq!(catalog.find("lib", Some("0.2")).unwrap().version, "0.3.0");
+        assert!(catalog.find("core", Some("2.0")).is_none());
+    }
+
+    #[test]
+    fn rejects_ids_and_versions_that_are_not_plain_names() {
+        for plugin in [
+            r#"<plugin id="../evil"><version>1.0</version><download>a.zip</download></plugin>"#,
+            r#"<plugin id="a/b"><version>1.0</version><download>a.zip</download></plugin>"#,
+            r#"<plugin id="ok"><version>../../1.0</version><download>a.zip</download></plugin>"#,
+            r#"<plugin id="ok"><version>1.0/x</version><download>a.zip</download></plugin>"#,
+            r#"<plugin id="ok"><version>1.0</version></plugin>"#,
+        ] {
+            let xml = format!("<registry>{}</registry>", plugin);
+            assert!(parse_index("index.xml", &xml).is_err(), "{}", plugin);
+        }
+    }
+
+    #[test]
+    fn plans_dependencies_first() {
+        let (_dir, catalog) = catalog(INDEX);
+        let plan = plan_install("snake", &catalog, &HashMap::new()).unwrap();
+        assert_eq!(ids(&plan), ["core-1.0", "lib-0.3.0", "snake-1.2.0"]);
+    }
+
+    #[test]
+    fn skips_dependencies_installed_at_a_new_enough_version() {
+        let (_dir, catalog) = catalog(INDEX);
+        let installed = HashMap::from([("lib".to_string(), "0.2.5".to_string())]);
+        assert_eq!(ids(&plan_install("snake", &catalog, &installed).unwrap()), ["snake-1.2.0"]);
+        let outdated = HashMap::from([("lib".to_string(), "0.1.0".to_string())]);
+        assert_eq!(ids(&plan_install("snake", &catalog, &outdated).unwrap()), ["core-1.0", "lib-0.3.0", "snake-1.2.0"]);
+        // the requested plugin itself is always reinstalled
+        let current = HashMap::from([("core".to_string(), "1.0".to_string())]);
+        assert_eq!(ids(&plan_install("core", &catalog, &current).unwrap()), ["core-1.0"]);
+    }
+
+    #[test]
+    fn reports_missing_dependencies_and_cycles() {
+        let (_dir, catalog) = catalog(r#"
+            <registry>
+                <plugin id="a"><version>1</version><download>a.zip</download><depends>b</depends></plugin>
+                <plugin id="b"><version>1</version><download>b.zip</download><depends>a</depends></plugin>
+                <plugin id="c"><version>1</version><download>c.zip</download><depends version="2">b</depends></plugin>
+            </registry>
+        "#);
+        assert_eq!(plan_install("a", &catalog, &HashMap::new()).unwrap_err(), "Dependency cycle: a -> b -> a");
+        assert_eq!(plan_install("c", &catalog, &HashMap::new()).unwrap_err(), "c needs b 2 or newer, which no registry lists");
+        assert_eq!(plan_install("d", &catalog, &HashMap::new()).unwrap_err(), "No registry lists d");
+    }
+}
@@ -0,0 +1,507 @@
+use mlua::prelude::*;
+use mlua::ChunkMode;
+use crate::budget;
+use crate::lua_bindings;
+use orbit::manifest::Manifest;
+use orbit::modules;
+use orbit::permissions::{Grants, Permission};
+use orbit::plugin_settings::PluginSettings;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+// Globals that stay owned by the host and are looked up live, so plugins see
+// per-frame refreshes (orbit_egui) but assigning to them only shadows locally.
+pub const SHARED_GLOBALS: [&str; 2] = ["orbit_egui", "CONFIG"];
+pub const SAFE_BASICS: [&str; 18] = [
+    "assert", "error", "ipairs", "next", "pairs", "pcall", "xpcall", "print", "select",
+    "tonumber", "tostring", "type", "rawequal", "rawget", "rawset", "rawlen", "setmetatable", "_VERSION",
+];
+pub const SAFE_LIBS: [&str; 5] = ["string", "table", "math", "utf8", "coroutine"];
+/// The rest of what `create_plugin_env` puts in a plugin's globals.
+pub const ENV_GLOBALS: [&str; 8] = ["_G", "getmetatable", "os", "io", "load", "require", "package", "orbit"];
+const SAFE_OS: [&str; 4] = ["clock", "time", "date", "difftime"];
+
+#[derive(Clone)]
+pub struct PluginContext {
+    pub name: String,
+    pub config_dir: PathBuf,
+    pub dir: PathBuf,
+    pub data_dir: PathBuf,
+    pub permissions: Vec<Permission>,
+    pub settings: PluginSettings,
+}
+
+impl PluginContext {
+    pub fn new(config_dir: &Path, name: &str) -> Self {
+        let dir = config_dir.join("plugins").join(name);
+        let manifest = Manifest::load(&dir).unwrap_or_default();
+        let grants = Grants::load(config_dir);
+        Self {
+            name: name.to_string(),
+            config_dir: config_dir.to_path_buf(),
+            data_dir: config_dir.join("data").join(name),
+            permissions: manifest.permissions.into_iter().filter(|p| grants.is_granted(name, *p)).collect(),
+            settings: PluginSettings::load(config_dir, name, &manifest.settings),
+            dir,
+        }
+    }
+
+    /// Fails with a message naming the missing permission unless it was granted.
+    pub fn check(&self, permission: Permission, what: &str) -> LuaResult<()> {
+        if self.permissions.contains(&permission) {
+            Ok(())
+        } else {
+            Err(LuaError::RuntimeError(format!(
+                "plugin '{}' needs the '{}' permission to use {}; declare it in index.xml and grant it in the plugin manager",
+                self.name, permission.name(), what
+            )))
+        }
+    }
+
+    /// Resolves a path handed to us by Lua. Relative paths are taken from the plugin
+    /// directory; anything outside the plugin or data directory, by `..` or through a
+    /// symlink, needs the filesystem permission.
+    pub fn resolve_path(&self, path: &str) -> LuaResult<PathBuf> {
+        let joined = self.dir.join(path);
+        let resolved = normalize(&joined);
+        let inside = |root: &Path| match (follow_links(&resolved), follow_links(root)) {
+            (Some(target), Some(root)) => target.starts_with(root),
+            _ => false,
+        };
+        if inside(&self.dir) || inside(&self.data_dir) {
+            return Ok(resolved);
+        }
+        self.check(Permission::Filesystem, &format!("'{}' outside its own directory", path))?;
+        Ok(resolved)
+    }
+}
+
+/// Symlinks followed while resolving one path before it counts as a loop.
+const MAX_LINKS: usize = 40;
+
+/// Where `path` really points, with every symlink along it resolved, including ones
+/// whose target does not exist yet: writing through those creates the target. `None`
+/// when the links loop or one cannot be read.
+fn follow_links(path: &Path) -> Option<PathBuf> {
+    let parts = |path: &Path| path.components().rev().map(|c| PathBuf::from(c.as_os_str())).collect::<Vec<_>>();
+    let mut pending = parts(path);
+    let mut resolved = PathBuf::new();
+    let mut links = 0;
+    while let Some(part) = pending.pop() {
+        match part.to_str() {
+            Some(".") => continue,
+            // `resolved` has no links left, so this is the real parent
+            Some("..") => {
+                resolved.pop();
+                continue;
+            },
+            _ => {},
+        }
+        let next = resolved.join(&part);
+        match fs::symlink_metadata(&next) {
+            Ok(metadata) if metadata.file_type().is_symlink() => {
+                links += 1;
+                if links > MAX_LINKS {
+                    return None;
+                }
+                // relative targets start from the link's folder, absolute ones replace it
+                pending.extend(parts(&fs::read_link(&next).ok()?));
+            },
+            _ => resolved = next,
+        }
+    }
+    Some(resolved)
+}
+
+fn normalize(path: &Path) -> PathBuf {
+    let mut out = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::ParentDir => { out.pop(); },
+            Component::CurDir => {},
+            other => out.push(other.as_os_str()),
+        }
+    }
+    out
+}
+
+fn copy_table<'lua>(lua: &'lua Lua, source: &LuaTable<'lua>, keys: Option<&[&str]>) -> LuaResult<LuaTable<'lua>> {
+    let copy = lua.create_table()?;
+    match keys {
+        Some(keys) => {
+            for key in keys {
+                copy.set(*key, source.get::<_, LuaValue>(*key)?)?;
+            }
+        },
+        None => {
+            for pair in source.clone().pairs::<LuaValue, LuaValue>() {
+                let (key, value) = pair?;
+                copy.set(key, value)?;
+            }
+        },
+    }
+    Ok(copy)
+}
+
+fn denied<'lua>(lua: &'lua Lua, name: &str, what: &'static str) -> LuaResult<LuaFunction<'lua>> {
+    let plugin = name.to_string();
+    lua.create_function(move |_, _: LuaMultiValue| -> LuaResult<()> {
+        Err(LuaError::RuntimeError(format!("plugin '{}' is not allowed to use {}", plugin, what)))
+    })
+}
+
+/// Wraps a real stdlib function so it only runs when `permission` was granted.
+fn gated<'lua>(lua: &'lua Lua, ctx: &PluginContext, real: LuaFunction<'lua>, permission: Permission, what: &'static str) -> LuaResult<LuaFunction<'lua>> {
+    let real_key = lua.create_registry_value(real)?;
+    let ctx = ctx.clone();
+    lua.create_function(move |lua, args: LuaMultiValue| {
+        ctx.check(permission, what)?;
+        lua.registry_value::<LuaFunction>(&real_key)?.call::<_, LuaMultiValue>(args)
+    })
+}
+
+/// `getmetatable` for tables, honouring `__metatable` like the real one. Strings and
+/// host userdata share their metatables with every plugin, so those stay hidden.
+fn getmetatable<'lua>(_: &'lua Lua, value: LuaValue<'lua>) -> LuaResult<LuaValue<'lua>> {
+    let LuaValue::Table(table) = value else {
+        return Ok(LuaValue::Nil);
+    };
+    let Some(metatable) = table.get_metatable() else {
+        return Ok(LuaValue::Nil);
+    };
+    match metatable.raw_get::<_, LuaValue>("__metatable")? {
+        LuaValue::Nil => Ok(LuaValue::Table(metatable)),
+        protected => Ok(protected),
+    }
+}
+
+/// The sandbox each plugin's code last ran in, kept in the Lua state's app data so a
+/// module other plugins require is the same instance its own plugin uses.
+#[derive(Default)]
+pub struct SharedModules {
+    envs: HashMap<String, LuaRegistryKey>,
+}
+
+fn register_env(lua: &Lua, plugin: &str, env: &LuaTable) -> LuaResult<()> {
+    let key = lua.create_registry_value(env.clone())?;
+    let previous = match lua.app_data_mut::<SharedModules>() {
+        Some(mut shared) => shared.envs.insert(plugin.to_string(), key),
+        None => Some(key),
+    };
+    if let Some(previous) = previous {
+        lua.remove_registry_value(previous)?;
+    }
+    Ok(())
+}
+
+/// The plugin's own sandbox, or a new one for a plugin whose code has not run yet.
+fn shared_env<'lua>(lua: &'lua Lua, config_dir: &Path, plugin: &str) -> LuaResult<LuaTable<'lua>> {
+    let existing = lua.app_data_ref::<SharedModules>()
+        .and_then(|shared| shared.envs.get(plugin).map(|key| lua.registry_value::<LuaTable>(key)));
+    match existing {
+        Some(env) => env,
+        None => create_plugin_env(lua, &PluginContext::new(config_dir, plugin)),
+    }
+}
+
+/// Drops the sandboxes of the plugins `forget` picks, so the next `require` of one of
+/// their modules loads it from disk again.
+pub fn forget_shared_modules(lua: &Lua, forget: impl Fn(&str) -> bool) {
+    let envs: Vec<LuaRegistryKey> = match lua.app_data_mut::<SharedModules>() {
+        Some(mut shared) => {
+            let plugins: Vec<String> = shared.envs.keys().filter(|plugin| forget(plugin)).cloned().collect();
+            plugins.iter().filter_map(|plugin| shared.envs.remove(plugin)).collect()
+        },
+        None => Vec::new(),
+    };
+    for key in envs {
+        let _ = lua.remove_registry_value(key);
+    }
+}
+
+/// Loads a Lua file from disk as a text chunk running inside `env`.
+pub fn load_file<'lua>(lua: &'lua Lua, env: &LuaTable<'lua>, path: &Path) -> LuaResult<LuaFunction<'lua>> {
+    let code = fs::read_to_string(path)
+        .map_err(|e| LuaError::RuntimeError(format!("Failed to read {:?}: {}", path, e)))?;
+    lua.load(&code)
+        .set_name(format!("@{}", path.display()))
+        .set_mode(ChunkMode::Text)
+        .set_environment(env.clone())
+        .into_function()
+}
+
+/// Builds the private global table a plugin's code runs in: a curated stdlib,
+/// `io`/`os` limited by the plugin's granted permissions, and a `require` over its own
+/// modules and those of its dependencies. Other plugins requiring its modules get
+/// them from this table from now on.
+pub fn create_plugin_env<'lua>(lua: &'lua Lua, ctx: &PluginContext) -> LuaResult<LuaTable<'lua>> {
+    fs::create_dir_all(&ctx.data_dir)
+        .map_err(|e| LuaError::RuntimeError(format!("Failed to create data directory for '{}': {}", ctx.name, e)))?;
+    let globals = lua.globals();
+    let env = copy_table(lua, &globals, Some(&SAFE_BASICS))?;
+    for lib in SAFE_LIBS {
+        env.set(lib, copy_table(lua, &globals.get::<_, LuaTable>(lib)?, None)?)?;
+    }
+    budget::hook_coroutines(lua, &env.get::<_, LuaTable>("coroutine")?)?;
+    env.set("_G", env.clone())?;
+    env.set("getmetatable", lua.create_function(getmetatable)?)?;
+    let env_key = lua.create_registry_value(env.clone())?;
+
+    // os: timekeeping, file operations through the path check, processes behind a permission
+    let real_os: LuaTable = globals.get("os")?;
+    let os = copy_table(lua, &real_os, Some(&SAFE_OS))?;
+    for (name, what) in [("exit", "os.exit"), ("tmpname", "os.tmpname")] {
+        os.set(name, denied(lua, &ctx.name, what)?)?;
+    }
+    for (name, what) in [("execute", "os.execute"), ("getenv", "os.getenv")] {
+        os.set(name, gated(lua, ctx, real_os.get(name)?, Permission::Process, what)?)?;
+    }
+    let remove_key = lua.create_registry_value(real_os.get::<_, LuaFunction>("remove")?)?;
+    let remove_ctx = ctx.clone();
+    os.set("remove", lua.create_function(move |lua, path: String| {
+        let path = remove_ctx.resolve_path(&path)?;
+        lua.registry_value::<LuaFunction>(&remove_key)?.call::<_, LuaMultiValue>(path.to_string_lossy().to_string())
+    })?)?;
+    let rename_key = lua.create_registry_value(real_os.get::<_, LuaFunction>("rename")?)?;
+    let rename_ctx = ctx.clone();
+    os.set("rename", lua.create_function(move |lua, (from, to): (String, String)| {
+        let from = rename_ctx.resolve_path(&from)?;
+        let to = rename_ctx.resolve_path(&to)?;
+        lua.registry_value::<LuaFunction>(&rename_key)?
+            .call::<_, LuaMultiValue>((from.to_string_lossy().to_string(), to.to_string_lossy().to_string()))
+    })?)?;
+    env.set("os", os)?;
+
+    // io: open/lines resolved against the plugin, stdout writes, popen behind the process permission
+    let real_io: LuaTable = globals.get("io")?;
+    let io = copy_table(lua, &real_io, Some(&["write", "type"]))?;
+    io.set("popen", gated(lua, ctx, real_io.get("popen")?, Permission::Process, "io.popen")?)?;
+    for name in ["open", "lines"] {
+        let real_key = lua.create_registry_value(real_io.get::<_, LuaFunction>(name)?)?;
+        let io_ctx = ctx.clone();
+        io.set(name, lua.create_function(move |lua, (path, rest): (String, LuaMultiValue)| {
+            let path = io_ctx.resolve_path(&path)?;
+            let mut args = rest;
+            args.push_front(LuaValue::String(lua.create_string(path.to_string_lossy().as_bytes())?));
+            lua.registry_value::<LuaFunction>(&real_key)?.call::<_, LuaMultiValue>(args)
+        })?)?;
+    }
+    env.set("io", io)?;
+
+    // load: text chunks only, defaulting to the plugin's environment
+    let load_env = lua.create_registry_value(env.clone())?;
+    env.set("load", lua.create_function(move |lua, (chunk, name, _mode, chunk_env): (String, Option<String>, Option<String>, Option<LuaTable>)| {
+        let chunk_env = match chunk_env {
+            Some(chunk_env) => chunk_env,
+            None => lua.registry_value::<LuaTable>(&load_env)?,
+        };
+        let result = lua.load(&chunk)
+            .set_name(name.unwrap_or_else(|| "=(load)".to_string()))
+            .set_mode(ChunkMode::Text)
+            .set_environment(chunk_env)
+            .into_function();
+        match result {
+            Ok(function) => Ok((Some(function), None)),
+            Err(e) => Ok((None, Some(e.to_string()))),
+        }
+    })?)?;
+
+    // require: modules from the plugin and its dependencies, cached per plugin
+    let search_path = modules::search_path(&ctx.config_dir, &ctx.name, &ctx.dir);
+    let loaded = lua.create_table()?;
+    let package = lua.create_table()?;
+    package.set("loaded", loaded.clone())?;
+    package.set("config", globals.get::<_, LuaTable>("package")?.get::<_, LuaValue>("config")?)?;
+    // informational only: `require` searches these itself
+    let templates: Vec<String> = search_path.iter()
+        .flat_map(|(_, dir)| modules::candidates(dir, "?").map(|candidate| candidate.display().to_string()))
+        .collect();
+    package.set("path", templates.join(";"))?;
+    env.set("package", package)?;
+    let loaded_key = lua.create_registry_value(loaded)?;
+    let require_ctx = ctx.clone();
+    env.set("require", lua.create_function(move |lua, module: String| {
+        let loaded = lua.registry_value::<LuaTable>(&loaded_key)?;
+        match loaded.get::<_, LuaValue>(module.as_str())? {
+            LuaValue::Nil => {},
+            LuaValue::Boolean(false) => {
+                return Err(LuaError::RuntimeError(format!("loop while loading module '{}'", module)));
+            },
+            value => return Ok(value),
+        }
+        let (provider, path) = modules::find(&search_path, &module).map_err(|e| {
+            LuaError::RuntimeError(modules::not_found(&require_ctx.config_dir, &search_path, &module, e))
+        })?;
+        if provider != require_ctx.name {
+            // the module runs in its own plugin's sandbox, with that plugin's permissions and budget
+            let provider_require = shared_env(lua, &require_ctx.config_dir, &provider)?.get::<_, LuaFunction>("require")?;
+            let value = budget::run(lua, &provider, || provider_require.call::<_, LuaValue>(module.as_str()))?;
+            loaded.set(module.as_str(), value.clone())?;
+            return Ok(value);
+        }
+        let env = lua.registry_value::<LuaTable>(&env_key)?;
+        loaded.set(module.as_str(), false)?;
+        let value = match load_file(lua, &env, &path).and_then(|chunk| chunk.call::<_, LuaValue>(module.as_str())) {
+            Ok(LuaValue::Nil) => LuaValue::Boolean(true),
+            Ok(value) => value,
+            Err(e) => {
+                loaded.set(module.as_str(), LuaValue::Nil)?;
+                return Err(e);
+            },
+        };
+        loaded.set(module.as_str(), value.clone())?;
+        Ok(value)
+    })?)?;
+
+    env.set("orbit", lua_bindings::create_plugin_api(lua, ctx)?)?;
+
+    let metatable = lua.create_table()?;
+    metatable.set("__index", lua.create_function(|lua, (_, key): (LuaTable, LuaValue)| {
+        match key {
+            LuaValue::String(name) if SHARED_GLOBALS.iter().any(|shared| name.as_bytes() == shared.as_bytes()) => {
+                lua.globals().get::<_, LuaValue>(name)
+            },
+            _ => Ok(LuaValue::Nil),
+        }
+    })?)?;
+    env.set_metatable(Some(metatable));
+    register_env(lua, &ctx.name, &env)?;
+    Ok(env)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_dir::TestDir;
+
+    fn plugin(config: &TestDir, name: &str, dependencies: &[&str], files: &[(&str, &str)]) -> PluginContext {
+        let depends: String = dependencies.iter().map(|d| format!("<depends>{}</depends>", d)).collect();
+        config.write(&format!("plugins/{}/index.xml", name), &format!("<plugin><name>{}</name>{}</plugin>", name, depends));
+        for (file, code) in files {
+            config.write(&format!("plugins/{}/{}", name, file), code);
+        }
+        PluginContext::new(config.path(), name)
+    }
+
+    fn lua() -> Lua {
+        let lua = Lua::new();
+        budget::install(&lua);
+        lua.set_app_data(SharedModules::default());
+        lua
+    }
+
+    fn eval<'lua, R: FromLua<'lua>>(lua: &'lua Lua, env: &LuaTable<'lua>, code: &str) -> LuaResult<R> {
+        lua.load(code).set_environment(env.clone()).eval()
+    }
+
+    #[test]
+    fn paths_stay_inside_the_plugin_and_data_directories() {
+        let config = TestDir::new("sandbox-paths");
+        let plugin = plugin(&config, "a", &[], &[]);
+        fs::create_dir_all(&plugin.data_dir).unwrap();
+        assert!(plugin.resolve_path("sub/../file.txt").is_ok());
+        assert!(plugin.resolve_path(&plugin.data_dir.join("save.txt").to_string_lossy()).is_ok());
+        assert!(plugin.resolve_path("../b/file.txt").is_err());
+        assert!(plugin.resolve_path("sub/../../../escape.txt").is_err());
+        assert!(plugin.resolve_path("/etc/passwd").is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinks_cannot_lead_outside() {
+        use std::os::unix::fs::symlink;
+        let config = TestDir::new("sandbox-symlinks");
+        let plugin = plugin(&config, "a", &[], &[]);
+        let outside = config.path().join("outside");
+        fs::create_dir_all(&outside).unwrap();
+        // a link to a folder elsewhere, and to one inside
+        symlink(&outside, plugin.dir.join("linked")).unwrap();
+        symlink(plugin.dir.join("."), plugin.dir.join("self")).unwrap();
+        assert!(plugin.resolve_path("linked").is_err());
+        assert!(plugin.resolve_path("linked/new.txt").is_err());
+        assert!(plugin.resolve_path("self/new.txt").is_ok());
+        // a dangling link would create its target on the first write
+        symlink(outside.join("created.txt"), plugin.dir.join("dangling")).unwrap();
+        assert!(plugin.resolve_path("dangling").is_err());
+        symlink(outside.join("missing/deeper"), plugin.dir.join("dangling_dir")).unwrap();
+        assert!(plugin.resolve_path("dangling_dir/new.txt").is_err());
+        // a symlinked parent; `..` is applied to the path before it is opened
+        fs::create_dir_all(outside.join("nested")).unwrap();
+        symlink(outside.join("nested"), plugin.dir.join("parent")).unwrap();
+        assert!(plugin.resolve_path("parent/file.txt").is_err());
+        assert_eq!(plugin.resolve_path("parent/../file.txt").unwrap(), plugin.dir.join("file.txt"));
+        // links that loop are refused rather than followed forever
+        symlink(plugin.dir.join("loop"), plugin.dir.join("loop")).unwrap();
+        assert!(plugin.resolve_path("loop").is_err());
+    }
+
+    #[test]
+    fn required_modules_are_the_instance_their_plugin_uses() {
+        let config = TestDir::new("sandbox-shared");
+        let counter = "local n = 0 return { bump = function() n = n + 1 return n end }";
+        let b = plugin(&config, "b", &[], &[("counter.lua", counter)]);
+        let a = plugin(&config, "a", &["b"], &[]);
+        let lua = lua();
+        let b_env = create_plugin_env(&lua, &b).unwrap();
+        assert_eq!(eval::<i64>(&lua, &b_env, "return require('counter').bump()").unwrap(), 1);
+        let a_env = create_plugin_env(&lua, &a).unwrap();
+        assert_eq!(eval::<i64>(&lua, &a_env, "return require('counter').bump()").unwrap(), 2);
+        assert_eq!(eval::<i64>(&lua, &b_env, "return require('counter').bump()").unwrap(), 3);
+    }
+
+    #[test]
+    fn required_modules_run_on_their_plugins_budget() {
+        let config = TestDir::new("sandbox-budget");
+        plugin(&config, "b", &[], &[("stuck.lua", "while true do end")]);
+        let a = plugin(&config, "a", &["b"], &[]);
+        let lua = lua();
+        let a_env = create_plugin_env(&lua, &a).unwrap();
+        let result = budget::run(&lua, "a", || eval::<LuaValue>(&lua, &a_env, "return require('stuck')"));
+        assert!(result.is_err());
+        let stopped: Vec<String> = budget::take_stopped(&lua).into_iter().map(|(plugin, _)| plugin).collect();
+        assert_eq!(stopped, ["b"]);
+    }
+
+    #[test]
+    fn required_modules_stay_inside_the_search_path() {
+        let config = TestDir::new("sandbox-require-escape");
+        let other = plugin(&config, "other", &[], &[("init.lua", "return 'escaped'"), ("evil.lua", "return 'escaped'")]);
+        let a = plugin(&config, "a", &[], &[]);
+        let lua = lua();
+        let env = create_plugin_env(&lua, &a).unwrap();
+        let absolute = other.dir.join("evil").display().to_string().replace('/', ".");
+        for module in ["x/../../other/init", &absolute] {
+            let error = eval::<LuaValue>(&lua, &env, &format!("return require('{}')", module)).unwrap_err();
+            assert!(error.to_string().contains("invalid module name"), "{}: {}", module, error);
+        }
+    }
+
+    #[test]
+    fn bundled_lib_loads_in_its_sandbox() {
+        let config = TestDir::new("sandbox-lib");
+        let source = Path::new(env!("CARGO_MANIFEST_DIR")).join("src/lib");
+        let mut pending = vec![PathBuf::new()];
+        while let Some(relative) = pending.pop() {
+            for entry in fs::read_dir(source.join(&relative)).unwrap().flatten() {
+                let relative = relative.join(entry.file_name());
+                if entry.path().is_dir() {
+                    pending.push(relative);
+                } else {
+                    let contents = fs::read_to_string(entry.path()).unwrap_or_default();
+                    config.write(&format!("plugins/lib/{}", relative.display()), &contents);
+                }
+            }
+        }
+        let manifest = Manifest::load(&config.path().join("plugins/lib")).unwrap();
+        Grants::load(config.path()).grant("lib", &manifest.permissions).unwrap();
+        let lua = lua();
+        let env = create_plugin_env(&lua, &PluginContext::new(config.path(), "lib")).unwrap();
+        for module in ["utiletc.json", "utiletc.xml", "utiletc.uuid", "utiletc.cache", "utiletc.async", "scheduler.scheduler", "datasave.save"] {
+            eval::<LuaValue>(&lua, &env, &format!("return require('{}')", module)).unwrap_or_else(|e| panic!("{}: {}", module, e));
+        }
+        let version: String = eval(&lua, &env, "return require('utiletc.os_info').get_version()").unwrap();
+        assert!(!version.is_empty());
+        eval::<Option<f64>>(&lua, &env, "return require('utiletc.os_info').get_uptime()").unwrap();
+    }
+}
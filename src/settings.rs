@@ -0,0 +1,190 @@
+use crate::plugin_settings::{PluginSettings, SettingKind, SettingSpec, SettingValue};
+use eframe::egui;
+use std::path::Path;
+
+pub const DISPLAY_MODES: [&str; 3] = ["windowed", "fullscreen", "borderless"];
+/// Languages a game can be asked to use; Orbit itself only speaks English.
+pub const LANGUAGES: [&str; 11] = ["en", "de", "es", "fr", "it", "ja", "ko", "pl", "pt", "ru", "zh"];
+/// Actions games can read key bindings for, as (action, label, default key).
+pub const ACTIONS: [(&str, &str, &str); 8] = [
+    ("up", "Move up", "W"),
+    ("down", "Move down", "S"),
+    ("left", "Move left", "A"),
+    ("right", "Move right", "D"),
+    ("jump", "Jump", "Space"),
+    ("interact", "Interact", "E"),
+    ("pause", "Pause", "Escape"),
+    ("confirm", "Confirm", "Enter"),
+];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DisplayMode {
+    Windowed,
+    Fullscreen,
+    /// A maximized window without decorations.
+    Borderless,
+}
+
+fn spec(key: &str, label: &str, kind: SettingKind, default: SettingValue) -> SettingSpec {
+    SettingSpec { key: key.to_string(), label: label.to_string(), kind, default }
+}
+
+fn text(value: &str) -> SettingValue {
+    SettingValue::Text(value.to_string())
+}
+
+/// Every setting in `CONFIG/settings.xml` with its type, range and default. Key
+/// bindings are `keybind.<action>`.
+pub fn specs() -> Vec<SettingSpec> {
+    let options = |values: &[&str]| SettingKind::Enum(values.iter().map(|value| value.to_string()).collect());
+    let mut specs = vec![
+        spec("display_mode", "Display mode", options(&DISPLAY_MODES), text("windowed")),
+        spec("ui_scale", "UI scale", SettingKind::Float { min: Some(0.5), max: Some(3.0) }, SettingValue::Float(1.0)),
+        spec("volume", "Volume", SettingKind::Int { min: Some(0), max: Some(100) }, SettingValue::Int(80)),
+        spec("language", "Language", options(&LANGUAGES), text("en")),
+    ];
+    for (action, label, key) in ACTIONS {
+        specs.push(spec(&format!("keybind.{}", action), label, SettingKind::Keybind, text(key)));
+    }
+    specs.push(spec("high_contrast", "High contrast", SettingKind::Bool, SettingValue::Bool(false)));
+    specs.push(spec("reduce_motion", "Reduce motion", SettingKind::Bool, SettingValue::Bool(false)));
+    specs
+}
+
+/// Orbit's own settings, persisted in `CONFIG/settings.xml` in the same format as a
+/// plugin's: `<settings><value key="volume">60</value></settings>`. Values that are
+/// missing or do not fit their type or range read as the default.
+#[derive(Clone, Default)]
+pub struct Settings {
+    values: PluginSettings,
+}
+
+impl Settings {
+    pub fn load(config_dir: &Path) -> Settings {
+        Settings { values: PluginSettings::load_file(config_dir.join("settings.xml"), &specs()) }
+    }
+
+    pub fn specs(&self) -> &[SettingSpec] {
+        &self.values.specs
+    }
+
+    pub fn get(&self, key: &str) -> Option<&SettingValue> {
+        self.values.get(key)
+    }
+
+    /// Checks `value` against the setting's type and range, then saves.
+    pub fn set(&mut self, key: &str, value: SettingValue) -> Result<(), String> {
+        self.values.set(key, value)
+    }
+
+    /// The stored value, or the default from [`specs`], which also covers settings that
+    /// were never loaded.
+    fn value(&self, key: &str) -> SettingValue {
+        match self.get(key) {
+            Some(value) => value.clone(),
+            None => specs().into_iter().find(|spec| spec.key == key).map(|spec| spec.default).unwrap_or(SettingValue::Bool(false)),
+        }
+    }
+
+    fn text(&self, key: &str) -> String {
+        match self.value(key) {
+            SettingValue::Text(value) => value,
+            _ => String::new(),
+        }
+    }
+
+    fn number(&self, key: &str) -> f64 {
+        match self.value(key) {
+            SettingValue::Int(value) => value as f64,
+            SettingValue::Float(value) => value,
+            _ => 0.0,
+        }
+    }
+
+    fn flag(&self, key: &str) -> bool {
+        self.value(key) == SettingValue::Bool(true)
+    }
+
+    pub fn display_mode(&self) -> DisplayMode {
+        match self.text("display_mode").as_str() {
+            "fullscreen" => DisplayMode::Fullscreen,
+            "borderless" => DisplayMode::Borderless,
+            _ => DisplayMode::Windowed,
+        }
+    }
+
+    pub fn ui_scale(&self) -> f32 {
+        self.number("ui_scale") as f32
+    }
+
+    /// Between 0.0 and 1.0.
+    pub fn volume(&self) -> f32 {
+        self.number("volume") as f32 / 100.0
+    }
+
+    pub fn language(&self) -> String {
+        self.text("language")
+    }
+
+    pub fn keybind(&self, action: &str) -> Option<egui::Key> {
+        egui::Key::from_name(&self.text(&format!("keybind.{}", action)))
+    }
+
+    pub fn high_contrast(&self) -> bool {
+        self.flag("high_contrast")
+    }
+
+    pub fn reduce_motion(&self) -> bool {
+        self.flag("reduce_motion")
+    }
+
+    /// Sets the window's display mode.
+    pub fn apply_display_mode(&self, ctx: &egui::Context) {
+        let mode = self.display_mode();
+        ctx.send_viewport_cmd(egui::ViewportCommand::Fullscreen(mode == DisplayMode::Fullscreen));
+        ctx.send_viewport_cmd(egui::ViewportCommand::Decorations(mode != DisplayMode::Borderless));
+        ctx.send_viewport_cmd(egui::ViewportCommand::Maximized(mode == DisplayMode::Borderless));
+    }
+
+    /// Sets the UI scale and the accessibility options on the egui style.
+    pub fn apply_style(&self, ctx: &egui::Context) {
+        ctx.set_zoom_factor(self.ui_scale());
+        let mut style = egui::Style::default();
+        if self.high_contrast() {
+            style.visuals.override_text_color = Some(egui::Color32::WHITE);
+            style.visuals.panel_fill = egui::Color32::BLACK;
+            style.visuals.window_fill = egui::Color32::BLACK;
+            style.visuals.window_stroke = egui::Stroke::new(2.0, egui::Color32::WHITE);
+            for widget in [&mut style.visuals.widgets.inactive, &mut style.visuals.widgets.hovered, &mut style.visuals.widgets.active] {
+                widget.bg_stroke = egui::Stroke::new(2.0, egui::Color32::WHITE);
+            }
+        }
+        if self.reduce_motion() {
+            style.animation_time = 0.0;
+            style.scroll_animation = egui::style::ScrollAnimation::none();
+        }
+        ctx.set_style(style);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_dir::TestDir;
+
+    #[test]
+    fn typed_getters_fall_back_to_the_spec_defaults() {
+        let unloaded = Settings::default();
+        assert_eq!(unloaded.volume(), 0.8);
+        assert_eq!(unloaded.ui_scale(), 1.0);
+        assert_eq!(unloaded.language(), "en");
+        assert_eq!(unloaded.keybind("jump"), Some(egui::Key::Space));
+
+        let config = TestDir::new("settings");
+        config.write("settings.xml", r#"<settings><value key="volume">250</value><value key="ui_scale">1.5</value></settings>"#);
+        let settings = Settings::load(config.path());
+        // out of range reads as the default
+        assert_eq!(settings.volume(), 0.8);
+        assert_eq!(settings.ui_scale(), 1.5);
+    }
+}
@@ -0,0 +1,235 @@
+use eframe::egui;
+use orbit::plugin_settings::{SettingKind, SettingSpec, SettingValue};
+use orbit::settings::Settings;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// What the user did with a setting's widget.
+pub enum SettingInput {
+    /// A slider, number or text field is still being dragged or typed in; show the
+    /// value but do not save it yet.
+    Editing(SettingValue),
+    Changed(SettingValue),
+    /// A keybind button was clicked; the next key pressed becomes the binding.
+    CaptureKey,
+}
+
+/// `Editing` while `response` is being dragged or has focus, `Changed` once that ends.
+fn edited(response: egui::Response, value: SettingValue) -> Option<SettingInput> {
+    if response.drag_stopped() || response.lost_focus() {
+        Some(SettingInput::Changed(value))
+    } else if response.changed() && (response.dragged() || response.has_focus()) {
+        Some(SettingInput::Editing(value))
+    } else {
+        response.changed().then_some(SettingInput::Changed(value))
+    }
+}
+
+/// Draws the widget editing one setting: a checkbox, slider, drop-down, text field or
+/// key button. While `capturing`, a keybind shows a prompt and takes the next key pressed.
+pub fn setting_widget(ui: &mut egui::Ui, spec: &SettingSpec, current: SettingValue, capturing: bool) -> Option<SettingInput> {
+    let changed = |value| Some(SettingInput::Changed(value));
+    match (&spec.kind, current) {
+        (SettingKind::Bool, SettingValue::Bool(mut value)) => {
+            ui.checkbox(&mut value, "").changed().then_some(SettingInput::Changed(SettingValue::Bool(value)))
+        },
+        (SettingKind::Int { min: Some(min), max: Some(max) }, SettingValue::Int(mut value)) => {
+            let response = ui.add(egui::Slider::new(&mut value, *min..=*max));
+            edited(response, SettingValue::Int(value))
+        },
+        (SettingKind::Int { min, max }, SettingValue::Int(mut value)) => {
+            let range = min.unwrap_or(i64::MIN)..=max.unwrap_or(i64::MAX);
+            let response = ui.add(egui::DragValue::new(&mut value).range(range));
+            edited(response, SettingValue::Int(value))
+        },
+        (SettingKind::Float { min: Some(min), max: Some(max) }, SettingValue::Float(mut value)) => {
+            let response = ui.add(egui::Slider::new(&mut value, *min..=*max));
+            edited(response, SettingValue::Float(value))
+        },
+        (SettingKind::Float { min, max }, SettingValue::Float(mut value)) => {
+            let range = min.unwrap_or(f64::MIN)..=max.unwrap_or(f64::MAX);
+            let response = ui.add(egui::DragValue::new(&mut value).speed(0.1).range(range));
+            edited(response, SettingValue::Float(value))
+        },
+        (SettingKind::Enum(options), SettingValue::Text(value)) => {
+            let mut picked = None;
+            egui::ComboBox::from_id_salt(&spec.key)
+                .selected_text(&value)
+                .show_ui(ui, |ui| {
+                    for option in options {
+                        if ui.selectable_label(*option == value, option).clicked() {
+                            picked = Some(SettingValue::Text(option.clone()));
+                        }
+                    }
+                });
+            picked.and_then(changed)
+        },
+        (SettingKind::String, SettingValue::Text(mut value)) => {
+            let response = ui.text_edit_singleline(&mut value);
+            edited(response, SettingValue::Text(value))
+        },
+        (SettingKind::Keybind, SettingValue::Text(value)) => {
+            if capturing {
+                ui.label("Press a key...");
+                let pressed = ui.input(|i| i.events.iter().find_map(|event| match event {
+                    egui::Event::Key { key, pressed: true, .. } => Some(*key),
+                    _ => None,
+                }));
+                pressed.and_then(|key| changed(SettingValue::Text(key.name().to_string())))
+            } else {
+                ui.button(&value).clicked().then_some(SettingInput::CaptureKey)
+            }
+        },
+        _ => None,
+    }
+}
+
+/// Sections of the settings window, as (heading, key prefix or keys).
+const SECTIONS: [(&str, &[&str]); 4] = [
+    ("Display", &["display_mode", "ui_scale"]),
+    ("Audio and language", &["volume", "language"]),
+    ("Controls", &["keybind."]),
+    ("Accessibility", &["high_contrast", "reduce_motion"]),
+];
+
+/// The Settings window opened from the main menu: Orbit's own settings, plus the
+/// settings pages plugins added with `orbit.menu.add_settings_page`.
+#[derive(Default)]
+pub struct SettingsScreen {
+    pub show: bool,
+    pub settings: Settings,
+    capturing_key: Option<String>,
+    /// A setting whose slider or field is being edited, with the value shown meanwhile.
+    editing: Option<(String, SettingValue)>,
+    error: Option<String>,
+    plugin_pages: BTreeMap<String, Vec<String>>,
+    /// Keys changed since the host last asked.
+    changed: Vec<String>,
+    opened_page: Option<(String, String)>,
+}
+
+impl SettingsScreen {
+    pub fn new(config_dir: &Path) -> Self {
+        Self { settings: Settings::load(config_dir), ..Default::default() }
+    }
+
+    pub fn set_plugin_pages(&mut self, pages: BTreeMap<String, Vec<String>>) {
+        self.plugin_pages = pages;
+    }
+
+    /// Keys of the settings changed since the last call.
+    pub fn take_changes(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.changed)
+    }
+
+    /// The plugin settings page the user asked to open, as (plugin, title).
+    pub fn take_opened_page(&mut self) -> Option<(String, String)> {
+        self.opened_page.take()
+    }
+
+    fn set(&mut self, key: String, value: SettingValue) {
+        self.capturing_key = None;
+        self.editing = None;
+        if self.settings.get(&key) == Some(&value) {
+            return;
+        }
+        match self.settings.set(&key, value) {
+            Ok(()) => self.changed.push(key),
+            Err(e) => self.error = Some(e),
+        }
+    }
+
+    fn reset(&mut self) {
+        let defaults: Vec<_> = self.settings.specs().iter()
+            .filter(|spec| self.settings.get(&spec.key) != Some(&spec.default))
+            .map(|spec| (spec.key.clone(), spec.default.clone()))
+            .collect();
+        for (key, value) in defaults {
+            self.set(key, value);
+        }
+    }
+
+    pub fn draw(&mut self, ctx: &egui::Context) {
+        if !self.show {
+            return;
+        }
+        let mut open = true;
+        let mut edits = Vec::new();
+        let mut editing = None;
+        let mut capture = None;
+        let mut reset = false;
+        let mut opened_page = None;
+        egui::Window::new("Settings")
+            .open(&mut open)
+            .collapsible(false)
+            .default_size([420.0, 480.0])
+            .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+            .show(ctx, |ui| {
+                if let Some(error) = &self.error {
+                    ui.colored_label(egui::Color32::RED, error);
+                }
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    for (heading, keys) in SECTIONS {
+                        ui.heading(heading);
+                        egui::Grid::new(heading).num_columns(2).spacing([20.0, 8.0]).show(ui, |ui| {
+                            let specs = self.settings.specs().iter()
+                                .filter(|spec| keys.iter().any(|key| spec.key == *key || (key.ends_with('.') && spec.key.starts_with(key))));
+                            for spec in specs {
+                                ui.label(&spec.label);
+                                let current = match &self.editing {
+                                    Some((key, value)) if *key == spec.key => value.clone(),
+                                    _ => self.settings.get(&spec.key).cloned().unwrap_or_else(|| spec.default.clone()),
+                                };
+                                let capturing = self.capturing_key.as_deref() == Some(spec.key.as_str());
+                                match setting_widget(ui, spec, current, capturing) {
+                                    Some(SettingInput::Editing(value)) => editing = Some((spec.key.clone(), value)),
+                                    Some(SettingInput::Changed(value)) => edits.push((spec.key.clone(), value)),
+                                    Some(SettingInput::CaptureKey) => capture = Some(spec.key.clone()),
+                                    None => {},
+                                }
+                                ui.end_row();
+                            }
+                        });
+                        ui.add_space(10.0);
+                    }
+                    if !self.plugin_pages.is_empty() {
+                        ui.heading("Plugins");
+                        for (plugin, titles) in &self.plugin_pages {
+                            for title in titles {
+                                if ui.button(format!("{}: {}", plugin, title)).clicked() {
+                                    opened_page = Some((plugin.clone(), title.clone()));
+                                }
+                            }
+                        }
+                        ui.add_space(10.0);
+                    }
+                });
+                ui.separator();
+                if ui.button("Reset to defaults").clicked() {
+                    reset = true;
+                }
+            });
+        if editing.is_some() {
+            self.editing = editing;
+        }
+        for (key, value) in edits {
+            self.set(key, value);
+        }
+        if capture.is_some() {
+            self.capturing_key = capture;
+        }
+        if reset {
+            self.reset();
+        }
+        if opened_page.is_some() {
+            self.opened_page = opened_page;
+            open = false;
+        }
+        if !open {
+            self.show = false;
+            self.capturing_key = None;
+            self.editing = None;
+            self.error = None;
+        }
+    }
+}
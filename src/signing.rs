@@ -0,0 +1,185 @@
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use quick_xml::escape::escape;
+use quick_xml::events::Event;
+use quick_xml::reader::Reader;
+use std::fs;
+use std::path::Path;
+
+/// Written at the root of signed archives: the signer's public key and an ed25519
+/// signature over the exact bytes of `package.xml`.
+pub const PACKAGE_SIGNATURE: &str = "package.sig";
+
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+pub fn from_hex(text: &str) -> Option<Vec<u8>> {
+    let text = text.trim();
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(text.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+fn parse_public_key(text: &str) -> Option<VerifyingKey> {
+    let bytes: [u8; 32] = from_hex(text)?.try_into().ok()?;
+    VerifyingKey::from_bytes(&bytes).ok()
+}
+
+/// Creates a new secret key file and returns the matching public key in hex.
+pub fn generate_key(path: &Path) -> Result<String, String> {
+    if path.exists() {
+        return Err(format!("{:?} already exists", path));
+    }
+    let mut secret = [0u8; 32];
+    getrandom::getrandom(&mut secret).map_err(|e| format!("Failed to generate a key: {}", e))?;
+    let key = SigningKey::from_bytes(&secret);
+    fs::write(path, to_hex(&secret)).map_err(|e| format!("Failed to write {:?}: {}", path, e))?;
+    Ok(to_hex(key.verifying_key().as_bytes()))
+}
+
+pub fn load_signing_key(path: &Path) -> Result<SigningKey, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("Failed to read key {:?}: {}", path, e))?;
+    let secret: [u8; 32] = from_hex(&text)
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| format!("{:?} is not an orbit signing key", path))?;
+    Ok(SigningKey::from_bytes(&secret))
+}
+
+/// Contents of `package.sig` for `message`.
+pub fn sign(key: &SigningKey, message: &[u8]) -> String {
+    let signature = key.sign(message);
+    format!(
+        "<signature key=\"{}\">{}</signature>\n",
+        to_hex(key.verifying_key().as_bytes()),
+        to_hex(&signature.to_bytes())
+    )
+}
+
+/// Checks a `package.sig` against `message`, returning the signer's public key in hex.
+pub fn verify(signature_xml: &str, message: &[u8]) -> Result<String, String> {
+    let mut reader = Reader::from_str(signature_xml);
+    reader.trim_text(true);
+
+    let mut buf = Vec::new();
+    let mut key = None;
+    let mut signature = None;
+    loop {
+        match reader.read_event_into(&mut buf) {
+            Ok(Event::Start(ref e)) if e.name().as_ref() == b"signature" => {
+                key = e.try_get_attribute("key").ok().flatten()
+                    .and_then(|a| a.unescape_value().ok().map(|v| v.to_string()));
+            },
+            Ok(Event::Text(ref e)) => {
+                signature = Some(e.unescape().unwrap_or_default().to_string());
+            },
+            Ok(Event::Eof) => break,
+            Err(e) => return Err(format!("Invalid {}: {}", PACKAGE_SIGNATURE, e)),
+            _ => {}
+        }
+        buf.clear();
+    }
+    let key_hex = key.ok_or_else(|| format!("{} does not name a key", PACKAGE_SIGNATURE))?;
+    let public_key = parse_public_key(&key_hex).ok_or("Signature names an invalid public key")?;
+    let signature: [u8; 64] = signature
+        .and_then(|s| from_hex(&s))
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or("Signature is malformed")?;
+    public_key
+        .verify(message, &Signature::from_bytes(&signature))
+        .map_err(|_| "Signature does not match the package contents".to_string())?;
+    Ok(to_hex(public_key.as_bytes()))
+}
+
+/// Public keys whose packages count as verified, from `CONFIG/trusted_keys.xml`:
+/// `<keys><key name="alice">hex public key</key></keys>`.
+#[derive(Default)]
+pub struct TrustedKeys {
+    keys: Vec<(String, String)>,
+}
+
+impl TrustedKeys {
+    pub fn load(config_dir: &Path) -> TrustedKeys {
+        let mut keys = Vec::new();
+        if let Ok(xml_content) = fs::read_to_string(config_dir.join("trusted_keys.xml")) {
+            let mut reader = Reader::from_str(&xml_content);
+            reader.trim_text(true);
+
+            let mut buf = Vec::new();
+            let mut current_name = None;
+            loop {
+                match reader.read_event_into(&mut buf) {
+                    Ok(Event::Start(ref e)) if e.name().as_ref() == b"key" => {
+                        current_name = e.try_get_attribute("name").ok().flatten()
+                            .and_then(|a| a.unescape_value().ok().map(|v| v.to_string()));
+                    },
+                    Ok(Event::Text(ref e)) => {
+                        let key = e.unescape().unwrap_or_default().trim().to_lowercase();
+                        if parse_public_key(&key).is_some() {
+                            keys.push((current_name.take().unwrap_or_else(|| key.clone()), key));
+                        }
+                    },
+                    Ok(Event::Eof) => break,
+                    Err(_) => break,
+                    _ => {}
+                }
+                buf.clear();
+            }
+        }
+        TrustedKeys { keys }
+    }
+
+    /// The name the user gave a trusted key.
+    pub fn name_of(&self, key_hex: &str) -> Option<&str> {
+        self.keys.iter().find(|(_, key)| key == key_hex).map(|(name, _)| name.as_str())
+    }
+
+    pub fn trust(&mut self, config_dir: &Path, name: &str, key_hex: &str) -> Result<(), String> {
+        if self.name_of(key_hex).is_none() {
+            self.keys.push((name.to_string(), key_hex.to_string()));
+        }
+        let mut xml = String::from("<keys>\n");
+        for (name, key) in &self.keys {
+            xml.push_str(&format!("    <key name=\"{}\">{}</key>\n", escape(name), key));
+        }
+        xml.push_str("</keys>\n");
+        let path = config_dir.join("trusted_keys.xml");
+        fs::write(&path, xml).map_err(|e| format!("Failed to save {:?}: {}", path, e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_dir::TestDir;
+
+    #[test]
+    fn signatures_name_their_key_and_cover_the_message() {
+        let key = SigningKey::from_bytes(&[3; 32]);
+        let signature = sign(&key, b"<package/>");
+        assert_eq!(verify(&signature, b"<package/>").unwrap(), to_hex(key.verifying_key().as_bytes()));
+        assert!(verify(&signature, b"<package></package>").is_err());
+        let other = to_hex(SigningKey::from_bytes(&[4; 32]).verifying_key().as_bytes());
+        let swapped = signature.replace(&to_hex(key.verifying_key().as_bytes()), &other);
+        assert!(verify(&swapped, b"<package/>").is_err());
+        assert!(verify("<signature>00</signature>", b"<package/>").is_err());
+    }
+
+    #[test]
+    fn keys_survive_saving_and_loading() {
+        let dir = TestDir::new("signing");
+        let public = generate_key(&dir.path().join("orbit.key")).unwrap();
+        assert!(generate_key(&dir.path().join("orbit.key")).is_err());
+        let key = load_signing_key(&dir.path().join("orbit.key")).unwrap();
+        assert_eq!(to_hex(key.verifying_key().as_bytes()), public);
+
+        let mut trusted = TrustedKeys::load(dir.path());
+        trusted.trust(dir.path(), "alice & bob", &public).unwrap();
+        assert_eq!(TrustedKeys::load(dir.path()).name_of(&public), Some("alice & bob"));
+        assert_eq!(from_hex("0aff"), Some(vec![0x0a, 0xff]));
+        assert_eq!(from_hex("abc"), None);
+    }
+}
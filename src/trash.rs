@@ -0,0 +1,261 @@
+use crate::manifest::{attribute, Manifest};
+use quick_xml::events::Event;
+use quick_xml::reader::Reader;
+use std::cmp::Reverse;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Days a deleted plugin is kept when `CONFIG/trash.xml` does not say otherwise.
+pub const DEFAULT_RETENTION_DAYS: u64 = 30;
+
+/// A deleted plugin, kept as `CONFIG/trash/<unix time>.<nanoseconds>-<name>/` with
+/// the plugin folder in `plugin/` and, if it was trashed with it, its save data in `data/`.
+pub struct TrashEntry {
+    pub name: String,
+    pub manifest: Manifest,
+    pub deleted_at: SystemTime,
+    pub path: PathBuf,
+    pub has_data: bool,
+}
+
+impl TrashEntry {
+    pub fn age(&self) -> Duration {
+        SystemTime::now().duration_since(self.deleted_at).unwrap_or_default()
+    }
+}
+
+fn trash_dir(config_dir: &Path) -> PathBuf {
+    config_dir.join("trash")
+}
+
+/// Moves a plugin into the trash. Its save data in `CONFIG/data/<name>` goes with it
+/// only when `include_data` is set; otherwise it stays where a reinstall will find it.
+/// On error the plugin is still installed.
+pub fn move_to_trash(config_dir: &Path, plugin_dir: &Path, name: &str, include_data: bool) -> Result<(), String> {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    fs::create_dir_all(trash_dir(config_dir))
+        .map_err(|e| format!("Failed to create {:?}: {}", trash_dir(config_dir), e))?;
+    // the same plugin can be trashed twice within a second; count up until the name is free
+    let mut nanos = now.subsec_nanos();
+    let entry = loop {
+        let entry = trash_dir(config_dir).join(format!("{}.{:09}-{}", now.as_secs(), nanos, name));
+        match fs::create_dir(&entry) {
+            Ok(()) => break entry,
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => nanos = nanos.wrapping_add(1),
+            Err(e) => return Err(format!("Failed to create {:?}: {}", entry, e)),
+        }
+    };
+    // the save data goes first, so an error always leaves the plugin installed
+    let data_dir = config_dir.join("data").join(name);
+    let moved_data = include_data && data_dir.is_dir();
+    if moved_data {
+        if let Err(e) = fs::rename(&data_dir, entry.join("data")) {
+            let _ = fs::remove_dir_all(&entry);
+            return Err(format!("Failed to move the save data of {} to the trash: {}", name, e));
+        }
+    }
+    if let Err(e) = fs::rename(plugin_dir, entry.join("plugin")) {
+        if moved_data && fs::rename(entry.join("data"), &data_dir).is_err() {
+            return Err(format!("Failed to move {} to the trash, its save data is left in {:?}: {}", name, entry, e));
+        }
+        let _ = fs::remove_dir_all(&entry);
+        return Err(format!("Failed to move {} to the trash: {}", name, e));
+    }
+    Ok(())
+}
+
+/// Everything in the trash, most recently deleted first.
+pub fn list(config_dir: &Path) -> Vec<TrashEntry> {
+    let mut entries = Vec::new();
+    let Ok(dir) = fs::read_dir(trash_dir(config_dir)) else {
+        return entries;
+    };
+    for entry in dir.flatten() {
+        let folder = entry.file_name().to_string_lossy().to_string();
+        let Some((time, name)) = folder.split_once('-') else {
+            continue;
+        };
+        // older entries have whole seconds only
+        let (secs, nanos) = time.split_once('.').unwrap_or((time, "0"));
+        let (Ok(secs), Ok(nanos)) = (secs.parse::<u64>(), nanos.parse::<u32>()) else {
+            continue;
+        };
+        let path = entry.path();
+        entries.push(TrashEntry {
+            name: name.to_string(),
+            manifest: Manifest::load(&path.join("plugin")).unwrap_or_default(),
+            deleted_at: UNIX_EPOCH + Duration::new(secs, nanos),
+            has_data: path.join("data").is_dir(),
+            path,
+        });
+    }
+    entries.sort_by_key(|entry| Reverse(entry.deleted_at));
+    entries
+}
+
+/// Puts a trashed plugin, and its save data if it was trashed too, back in place.
+pub fn restore(config_dir: &Path, entry: &TrashEntry) -> Result<PathBuf, String> {
+    let target = config_dir.join("plugins").join(&entry.name);
+    if target.exists() {
+        return Err(format!("{} is installed again; delete it before restoring the old copy", entry.name));
+    }
+    let data_dir = config_dir.join("data").join(&entry.name);
+    if entry.has_data && data_dir.exists() {
+        return Err(format!("{} already has new save data in {:?}", entry.name, data_dir));
+    }
+    fs::rename(entry.path.join("plugin"), &target)
+        .map_err(|e| format!("Failed to restore {}: {}", entry.name, e))?;
+    if entry.has_data {
+        if let Some(parent) = data_dir.parent() {
+            let _ = fs::create_dir_all(parent);
+        }
+        fs::rename(entry.path.join("data"), &data_dir)
+            .map_err(|e| format!("Restored {} but not its save data: {}", entry.name, e))?;
+    }
+    fs::remove_dir_all(&entry.path).map_err(|e| format!("Failed to clean up {:?}: {}", entry.path, e))?;
+    Ok(target)
+}
+
+pub fn delete(entry: &TrashEntry) -> Result<(), String> {
+    fs::remove_dir_all(&entry.path).map_err(|e| format!("Failed to delete {:?}: {}", entry.path, e))
+}
+
+pub fn empty(config_dir: &Path) -> Result<(), String> {
+    for entry in list(config_dir) {
+        delete(&entry)?;
+    }
+    Ok(())
+}
+
+/// Deletes entries older than the retention period; 0 days keeps everything.
+pub fn purge_expired(config_dir: &Path, retention_days: u64) -> Result<(), String> {
+    if retention_days == 0 {
+        return Ok(());
+    }
+    let retention = Duration::from_secs(retention_days * 24 * 60 * 60);
+    for entry in list(config_dir).iter().filter(|entry| entry.age() > retention) {
+        delete(entry)?;
+    }
+    Ok(())
+}
+
+/// Retention period from `CONFIG/trash.xml`: `<trash retention_days="30"/>`.
+pub fn load_retention(config_dir: &Path) -> u64 {
+    let Ok(xml_content) = fs::read_to_string(config_dir.join("trash.xml")) else {
+        return DEFAULT_RETENTION_DAYS;
+    };
+    let mut reader = Reader::from_str(&xml_content);
+    reader.trim_text(true);
+
+    let mut buf = Vec::new();
+    loop {
+        match reader.read_event_into(&mut buf) {
+            Ok(Event::Empty(ref e)) | Ok(Event::Start(ref e)) if e.name().as_ref() == b"trash" => {
+                return attribute(e, "retention_days")
+                    .and_then(|days| days.parse().ok())
+                    .unwrap_or(DEFAULT_RETENTION_DAYS);
+            },
+            Ok(Event::Eof) | Err(_) => return DEFAULT_RETENTION_DAYS,
+            _ => {}
+        }
+        buf.clear();
+    }
+}
+
+pub fn save_retention(config_dir: &Path, retention_days: u64) -> Result<(), String> {
+    let path = config_dir.join("trash.xml");
+    fs::write(&path, format!("<trash retention_days=\"{}\"/>\n", retention_days))
+        .map_err(|e| format!("Failed to save {:?}: {}", path, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_dir::TestDir;
+
+    fn install(config: &TestDir, name: &str) -> PathBuf {
+        config.write(&format!("plugins/{}/index.xml", name), &format!("<plugin><name>{}</name><version>1.0</version></plugin>", name));
+        config.write(&format!("data/{}/save.txt", name), "level 3");
+        config.path().join("plugins").join(name)
+    }
+
+    #[test]
+    fn restores_a_plugin_with_its_save_data() {
+        let config = TestDir::new("trash-restore");
+        let dir = install(&config, "snake");
+        move_to_trash(config.path(), &dir, "snake", true).unwrap();
+        assert!(!dir.exists());
+        assert!(!config.path().join("data/snake").exists());
+
+        let entries = list(config.path());
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].name, "snake");
+        assert_eq!(entries[0].manifest.version, "1.0");
+        assert!(entries[0].has_data);
+        assert_eq!(restore(config.path(), &entries[0]).unwrap(), dir);
+        assert!(dir.join("index.xml").is_file());
+        assert_eq!(fs::read_to_string(config.path().join("data/snake/save.txt")).unwrap(), "level 3");
+        assert!(list(config.path()).is_empty());
+    }
+
+    #[test]
+    fn save_data_stays_unless_asked_for() {
+        let config = TestDir::new("trash-keep-data");
+        let dir = install(&config, "snake");
+        move_to_trash(config.path(), &dir, "snake", false).unwrap();
+        assert!(config.path().join("data/snake/save.txt").is_file());
+        assert!(!list(config.path())[0].has_data);
+    }
+
+    #[test]
+    fn restoring_refuses_to_replace_a_reinstalled_plugin() {
+        let config = TestDir::new("trash-reinstalled");
+        let dir = install(&config, "snake");
+        move_to_trash(config.path(), &dir, "snake", false).unwrap();
+        install(&config, "snake");
+        let entries = list(config.path());
+        assert!(restore(config.path(), &entries[0]).is_err());
+        assert_eq!(list(config.path()).len(), 1);
+    }
+
+    #[test]
+    fn a_failed_move_leaves_the_save_data_in_place() {
+        let config = TestDir::new("trash-failed");
+        install(&config, "snake");
+        let missing = config.path().join("plugins/missing");
+        config.write("data/missing/save.txt", "level 1");
+        assert!(move_to_trash(config.path(), &missing, "missing", true).is_err());
+        assert!(config.path().join("data/missing/save.txt").is_file());
+        assert!(list(config.path()).is_empty());
+    }
+
+    #[test]
+    fn purges_entries_older_than_the_retention() {
+        let config = TestDir::new("trash-purge");
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+        let old = now - 40 * 24 * 60 * 60;
+        let recent = now - 10 * 24 * 60 * 60;
+        // older entries have whole seconds only
+        config.write(&format!("trash/{}-old/plugin/index.lua", old), "return {}");
+        config.write(&format!("trash/{}.000000000-recent/plugin/index.lua", recent), "return {}");
+
+        purge_expired(config.path(), 0).unwrap();
+        assert_eq!(list(config.path()).len(), 2);
+        purge_expired(config.path(), DEFAULT_RETENTION_DAYS).unwrap();
+        let names: Vec<_> = list(config.path()).into_iter().map(|entry| entry.name).collect();
+        assert_eq!(names, ["recent"]);
+        purge_expired(config.path(), 7).unwrap();
+        assert!(list(config.path()).is_empty());
+    }
+
+    #[test]
+    fn retention_survives_saving_and_loading() {
+        let config = TestDir::new("trash-retention");
+        assert_eq!(load_retention(config.path()), DEFAULT_RETENTION_DAYS);
+        save_retention(config.path(), 7).unwrap();
+        assert_eq!(load_retention(config.path()), 7);
+        config.write("trash.xml", "<trash retention_days=\"soon\"/>");
+        assert_eq!(load_retention(config.path()), DEFAULT_RETENTION_DAYS);
+    }
+}
@@ -0,0 +1,631 @@
+use crate::manifest::{Manifest, UpdateSource};
+use crate::package::glob_match;
+use git2::build::CheckoutBuilder;
+use git2::{AutotagOption, FetchOptions, Oid, RemoteCallbacks, Repository, StatusOptions};
+use quick_xml::escape::escape;
+use quick_xml::events::Event;
+use quick_xml::reader::Reader;
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::thread;
+
+const DEFAULT_BRANCH: &str = "main";
+const BRANCH_REFS: &str = "refs/remotes/orbit-update/";
+const TAG_REFS: &str = "refs/orbit-update/tags/";
+
+/// A plugin with an `<update>` source in its manifest.
+#[derive(Clone)]
+pub struct UpdateTarget {
+    pub name: String,
+    pub path: PathBuf,
+    pub source: UpdateSource,
+    pub preference: Preference,
+    /// Where the previous version is kept after a successful update.
+    pub backup: PathBuf,
+}
+
+#[derive(Debug)]
+pub enum UpdateOutcome {
+    UpToDate(String),
+    Updated { from: String, to: String },
+    Reinstalled(String),
+}
+
+/// What is installed next to what the update source currently offers.
+#[derive(Clone, Debug)]
+pub struct UpdateCheck {
+    pub channel: String,
+    pub installed_version: String,
+    pub installed_revision: Option<String>,
+    pub available_version: String,
+    pub available_revision: String,
+}
+
+impl UpdateCheck {
+    pub fn has_update(&self) -> bool {
+        self.installed_revision.as_deref() != Some(self.available_revision.as_str())
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Job {
+    Check,
+    Apply,
+}
+
+/// Sent from the update thread as it works through the plugins.
+pub enum UpdateEvent {
+    Started(String),
+    Progress { plugin: String, received: usize, total: usize },
+    Checked { plugin: String, result: Result<UpdateCheck, String> },
+    Finished { plugin: String, result: Result<UpdateOutcome, String> },
+    Done,
+}
+
+/// How the user wants a plugin updated, persisted in `CONFIG/updates.xml`.
+#[derive(Clone, Default, PartialEq)]
+pub struct Preference {
+    /// Branch, tag or commit to stay on instead of following a channel.
+    pub pin: Option<String>,
+    /// Follow the manifest's beta branch instead of the stable channel.
+    pub beta: bool,
+}
+
+#[derive(Default)]
+pub struct UpdatePrefs {
+    path: PathBuf,
+    plugins: BTreeMap<String, Preference>,
+}
+
+impl UpdatePrefs {
+    pub fn load(config_dir: &Path) -> UpdatePrefs {
+        let path = config_dir.join("updates.xml");
+        let mut plugins = BTreeMap::new();
+        if let Ok(xml_content) = fs::read_to_string(&path) {
+            let mut reader = Reader::from_str(&xml_content);
+            reader.trim_text(true);
+
+            let mut buf = Vec::new();
+            loop {
+                match reader.read_event_into(&mut buf) {
+                    Ok(Event::Empty(ref e)) | Ok(Event::Start(ref e)) if e.name().as_ref() == b"plugin" => {
+                        let attribute = |name: &str| e.try_get_attribute(name).ok().flatten()
+                            .and_then(|a| a.unescape_value().ok().map(|v| v.to_string()));
+                        if let Some(name) = attribute("name") {
+                            let preference = Preference {
+                                pin: attribute("pin").filter(|pin| !pin.is_empty()),
+                                beta: attribute("beta").as_deref() == Some("true"),
+                            };
+                            plugins.insert(name, preference);
+                        }
+                    },
+                    Ok(Event::Eof) => break,
+                    Err(_) => break,
+                    _ => {}
+                }
+                buf.clear();
+            }
+        }
+        UpdatePrefs { path, plugins }
+    }
+
+    pub fn get(&self, plugin: &str) -> Preference {
+        self.plugins.get(plugin).cloned().unwrap_or_default()
+    }
+
+    pub fn set(&mut self, plugin: &str, preference: Preference) -> Result<(), String> {
+        if preference == Preference::default() {
+            self.plugins.remove(plugin);
+        } else {
+            self.plugins.insert(plugin.to_string(), preference);
+        }
+        self.save()
+    }
+
+    fn save(&self) -> Result<(), String> {
+        let mut xml = String::from("<updates>\n");
+        for (plugin, preference) in &self.plugins {
+            xml.push_str(&format!("    <plugin name=\"{}\"", escape(plugin)));
+            if let Some(pin) = &preference.pin {
+                xml.push_str(&format!(" pin=\"{}\"", escape(pin)));
+            }
+            if preference.beta {
+                xml.push_str(" beta=\"true\"");
+            }
+            xml.push_str("/>\n");
+        }
+        xml.push_str("</updates>\n");
+        fs::write(&self.path, xml)
+            .map_err(|e| format!("Failed to save update settings to {:?}: {}", self.path, e))
+    }
+}
+
+fn short_id(oid: Oid) -> String {
+    oid.to_string()[..7].to_string()
+}
+
+/// Full id of the commit a plugin folder has checked out, if it is a git repository.
+pub fn installed_commit(path: &Path) -> Option<String> {
+    let repo = Repository::open(path).ok()?;
+    let commit = repo.head().and_then(|h| h.peel_to_commit()).ok()?;
+    Some(commit.id().to_string())
+}
+
+/// Like [`installed_commit`], shortened for display.
+pub fn installed_revision(path: &Path) -> Option<String> {
+    installed_commit(path).map(|id| id[..7].to_string())
+}
+
+fn fetch_options<'a>(progress: &'a mut dyn FnMut(usize, usize)) -> FetchOptions<'a> {
+    let mut callbacks = RemoteCallbacks::new();
+    callbacks.transfer_progress(move |stats| {
+        progress(stats.received_objects(), stats.total_objects());
+        true
+    });
+    let mut options = FetchOptions::new();
+    options.remote_callbacks(callbacks);
+    options.download_tags(AutotagOption::None);
+    options
+}
+
+/// Fetches every branch and tag of the update source into refs of our own, so the
+/// plugin's own remotes and tags are left alone.
+fn fetch(repo: &Repository, url: &str, progress: &mut dyn FnMut(usize, usize)) -> Result<(), String> {
+    let mut remote = repo.remote_anonymous(url)
+        .map_err(|e| format!("Invalid update source {}: {}", url, e.message()))?;
+    let refspecs = [
+        format!("+refs/heads/*:{}*", BRANCH_REFS),
+        format!("+refs/tags/*:{}*", TAG_REFS),
+    ];
+    remote.fetch(&refspecs, Some(&mut fetch_options(progress)), None)
+        .map_err(|e| format!("Failed to fetch {}: {}", url, e.message()))
+}
+
+/// Orders tags like `v1.10.0` after `v1.9.2`.
+pub fn compare_versions(a: &str, b: &str) -> Ordering {
+    let numbers = |tag: &str| -> Vec<u64> {
+        tag.split(|c: char| !c.is_ascii_digit())
+            .filter_map(|part| part.parse().ok())
+            .collect()
+    };
+    numbers(a).cmp(&numbers(b)).then_with(|| a.cmp(b))
+}
+
+/// The revision a target should be on after fetching, with a description of where it came from.
+fn resolve(repo: &Repository, target: &UpdateTarget) -> Result<(Oid, String), String> {
+    let peel = |refname: &str| repo.find_reference(refname)
+        .and_then(|r| r.peel_to_commit())
+        .map(|c| c.id());
+    if let Some(pin) = &target.preference.pin {
+        let oid = peel(&format!("{}{}", TAG_REFS, pin))
+            .or_else(|_| peel(&format!("{}{}", BRANCH_REFS, pin)))
+            .or_else(|_| repo.revparse_single(pin).and_then(|o| o.peel_to_commit()).map(|c| c.id()))
+            .map_err(|_| format!("Pinned revision '{}' was not found in {}", pin, target.source.url))?;
+        return Ok((oid, format!("pinned to {}", pin)));
+    }
+    if let (true, Some(beta)) = (target.preference.beta, &target.source.beta) {
+        let oid = peel(&format!("{}{}", BRANCH_REFS, beta))
+            .map_err(|_| format!("Beta branch '{}' was not found in {}", beta, target.source.url))?;
+        return Ok((oid, format!("beta branch {}", beta)));
+    }
+    if let Some(pattern) = &target.source.tags {
+        let references = repo.references_glob(&format!("{}*", TAG_REFS)).map_err(|e| e.message().to_string())?;
+        let newest = references
+            .flatten()
+            .filter_map(|r| Some(r.name()?.strip_prefix(TAG_REFS)?.to_string()))
+            .filter(|tag| glob_match(pattern, tag))
+            .max_by(|a, b| compare_versions(a, b))
+            .ok_or_else(|| format!("No tag matching '{}' in {}", pattern, target.source.url))?;
+        let oid = peel(&format!("{}{}", TAG_REFS, newest)).map_err(|e| e.message().to_string())?;
+        return Ok((oid, format!("tag {}", newest)));
+    }
+    let branch = target.source.branch.as_deref().unwrap_or(DEFAULT_BRANCH);
+    let oid = peel(&format!("{}{}", BRANCH_REFS, branch))
+        .map_err(|_| format!("Branch '{}' was not found in {}", branch, target.source.url))?;
+    Ok((oid, format!("branch {}", branch)))
+}
+
+/// Whether `oid` is on some branch or tag of the update source, i.e. it has no local-only commits.
+fn known_upstream(repo: &Repository, oid: Oid) -> bool {
+    let Ok(references) = repo.references() else {
+        return false;
+    };
+    references.flatten()
+        .filter(|r| r.name().is_some_and(|name| name.starts_with(BRANCH_REFS) || name.starts_with(TAG_REFS)))
+        .filter_map(|r| r.peel_to_commit().ok())
+        .any(|c| c.id() == oid || repo.graph_descendant_of(c.id(), oid).unwrap_or(false))
+}
+
+fn version_at(repo: &Repository, oid: Oid) -> String {
+    repo.find_commit(oid)
+        .and_then(|c| c.tree())
+        .and_then(|tree| tree.get_path(Path::new("index.xml")))
+        .and_then(|entry| entry.to_object(repo))
+        .ok()
+        .and_then(|object| object.as_blob().map(|blob| String::from_utf8_lossy(blob.content()).to_string()))
+        .and_then(|xml| Manifest::parse(&xml).ok())
+        .map(|manifest| manifest.version)
+        .unwrap_or_default()
+}
+
+/// Looks up what the update source offers without changing the installed plugin.
+pub fn check_plugin(target: &UpdateTarget, progress: &mut dyn FnMut(usize, usize)) -> Result<UpdateCheck, String> {
+    let installed_version = Manifest::load(&target.path).map(|m| m.version).unwrap_or_default();
+    if let Ok(repo) = Repository::open(&target.path) {
+        fetch(&repo, &target.source.url, progress)?;
+        let (latest, channel) = resolve(&repo, target)?;
+        let installed_revision = installed_revision(&target.path);
+        return Ok(UpdateCheck {
+            channel,
+            installed_version,
+            installed_revision,
+            available_version: version_at(&repo, latest),
+            available_revision: short_id(latest),
+        });
+    }
+    // plugins that were copied in by hand have nowhere to fetch into
+    let parent = target.path.parent().ok_or("Invalid plugin path")?;
+    let scratch = parent.join(format!(".check-{}", target.name));
+    let _ = fs::remove_dir_all(&scratch);
+    let result = Repository::init_bare(&scratch)
+        .map_err(|e| e.message().to_string())
+        .and_then(|repo| {
+            fetch(&repo, &target.source.url, progress)?;
+            let (latest, channel) = resolve(&repo, target)?;
+            Ok(UpdateCheck {
+                channel,
+                installed_version,
+                installed_revision: None,
+                available_version: version_at(&repo, latest),
+                available_revision: short_id(latest),
+            })
+        });
+    let _ = fs::remove_dir_all(&scratch);
+    result
+}
+
+fn copy_dir(from: &Path, to: &Path) -> Result<(), String> {
+    fs::create_dir_all(to).map_err(|e| format!("Failed to create {:?}: {}", to, e))?;
+    let entries = fs::read_dir(from).map_err(|e| format!("Failed to read {:?}: {}", from, e))?;
+    for entry in entries.flatten() {
+        let destination = to.join(entry.file_name());
+        if entry.path().is_dir() {
+            copy_dir(&entry.path(), &destination)?;
+        } else {
+            fs::copy(entry.path(), &destination)
+                .map_err(|e| format!("Failed to copy {:?}: {}", entry.path(), e))?;
+        }
+    }
+    Ok(())
+}
+
+/// Checks that a plugin folder still has a readable manifest and an entrypoint that compiles.
+pub fn verify_plugin(dir: &Path) -> Result<(), String> {
+    Manifest::load(dir)?;
+    let entry = dir.join("index.lua");
+    if entry.exists() {
+        let code = fs::read_to_string(&entry).map_err(|e| format!("Failed to read index.lua: {}", e))?;
+        mlua::Lua::new()
+            .load(&code)
+            .set_name("@index.lua")
+            .into_function()
+            .map_err(|e| format!("New version does not load: {}", e))?;
+    }
+    Ok(())
+}
+
+/// Moves the staged version into place, keeping the old one as the backup. Any
+/// failure puts the old version back. A plugin that is not installed yet has no backup.
+fn swap_in(target: &UpdateTarget, staging: &Path) -> Result<(), String> {
+    if let Err(e) = verify_plugin(staging) {
+        let _ = fs::remove_dir_all(staging);
+        return Err(e);
+    }
+    let installed = target.path.exists();
+    if installed {
+        if let Some(parent) = target.backup.parent() {
+            fs::create_dir_all(parent).map_err(|e| format!("Failed to create {:?}: {}", parent, e))?;
+        }
+        let _ = fs::remove_dir_all(&target.backup);
+        if let Err(e) = fs::rename(&target.path, &target.backup) {
+            let _ = fs::remove_dir_all(staging);
+            return Err(format!("Failed to back up the installed version: {}", e));
+        }
+    }
+    let swapped = fs::rename(staging, &target.path)
+        .map_err(|e| format!("Failed to move the new version into place: {}", e))
+        .and_then(|_| verify_plugin(&target.path));
+    if let Err(e) = swapped {
+        let moved_out = if target.path.exists() { fs::rename(&target.path, staging) } else { Ok(()) };
+        let _ = fs::remove_dir_all(staging);
+        if !installed {
+            if let Err(rename_error) = moved_out {
+                return Err(format!("{}; the broken version is left at {:?}: {}", e, target.path, rename_error));
+            }
+            return Err(e);
+        }
+        return match moved_out.and_then(|_| fs::rename(&target.backup, &target.path)) {
+            Ok(()) => Err(format!("{} (rolled back to the installed version)", e)),
+            Err(rollback_error) => Err(format!(
+                "{}; rollback failed ({}); previous version kept at {:?}",
+                e, rollback_error, target.backup
+            )),
+        };
+    }
+    Ok(())
+}
+
+/// Plugins that were copied in by hand have no repository yet; fetch a fresh copy
+/// next to the old one and swap it in. Plugins that are not installed at all are
+/// installed this way too.
+fn reinstall(target: &UpdateTarget, staging: &Path, progress: &mut dyn FnMut(usize, usize)) -> Result<UpdateOutcome, String> {
+    let staged = Repository::init(staging)
+        .map_err(|e| e.message().to_string())
+        .and_then(|repo| {
+            fetch(&repo, &target.source.url, progress)?;
+            let (latest, _) = resolve(&repo, target)?;
+            checkout(&repo, latest, true)?;
+            Ok(latest)
+        });
+    let latest = match staged {
+        Ok(latest) => latest,
+        Err(e) => {
+            let _ = fs::remove_dir_all(staging);
+            return Err(e);
+        },
+    };
+    swap_in(target, staging)?;
+    Ok(UpdateOutcome::Reinstalled(short_id(latest)))
+}
+
+/// Fetches the update source and, if the chosen channel or pin points somewhere new,
+/// moves a staged copy of the plugin there before swapping that copy in.
+pub fn update_plugin(target: &UpdateTarget, progress: &mut dyn FnMut(usize, usize)) -> Result<UpdateOutcome, String> {
+    let parent = target.path.parent().ok_or("Invalid plugin path")?;
+    let staging = parent.join(format!(".update-{}", target.name));
+    let _ = fs::remove_dir_all(&staging);
+    let repo = match Repository::open(&target.path) {
+        Ok(repo) => repo,
+        Err(_) => return reinstall(target, &staging, progress),
+    };
+    fetch(&repo, &target.source.url, progress)?;
+    let (latest, _) = resolve(&repo, target)?;
+    let current = repo.head()
+        .and_then(|h| h.peel_to_commit())
+        .map(|c| c.id())
+        .map_err(|e| format!("Plugin repository has no checked out commit: {}", e.message()))?;
+    if current == latest {
+        return Ok(UpdateOutcome::UpToDate(short_id(current)));
+    }
+    if !known_upstream(&repo, current) {
+        return Err("Local history has diverged from the update source".to_string());
+    }
+    let modified = repo.statuses(Some(StatusOptions::new().include_untracked(false)))
+        .map(|statuses| !statuses.is_empty())
+        .unwrap_or(true);
+    if modified {
+        return Err("Plugin has local changes; not updating".to_string());
+    }
+    drop(repo);
+
+    copy_dir(&target.path, &staging)?;
+    let moved = Repository::open(&staging)
+        .map_err(|e| e.message().to_string())
+        .and_then(|repo| checkout(&repo, latest, false));
+    if let Err(e) = moved {
+        let _ = fs::remove_dir_all(&staging);
+        return Err(e);
+    }
+    swap_in(target, &staging)?;
+    Ok(UpdateOutcome::Updated { from: short_id(current), to: short_id(latest) })
+}
+
+fn checkout(repo: &Repository, latest: Oid, fresh: bool) -> Result<(), String> {
+    let commit = repo.find_object(latest, None).map_err(|e| e.message().to_string())?;
+    let mut options = CheckoutBuilder::new();
+    if fresh {
+        options.force();
+    } else {
+        options.safe();
+    }
+    repo.checkout_tree(&commit, Some(&mut options))
+        .map_err(|e| format!("Failed to check out the update: {}", e.message()))?;
+    let moved = match repo.head() {
+        Ok(mut head) if head.is_branch() => head.set_target(latest, "orbit: update plugin").map(|_| ()),
+        _ => repo.set_head_detached(latest),
+    };
+    moved.map_err(|e| format!("Failed to move HEAD: {}", e.message()))
+}
+
+/// Swaps the installed version with the one kept from before the last update.
+pub fn revert(path: &Path, backup: &Path) -> Result<(), String> {
+    if !backup.is_dir() {
+        return Err("There is no previous version to go back to".to_string());
+    }
+    let parent = path.parent().ok_or("Invalid plugin path")?;
+    let name = path.file_name().ok_or("Invalid plugin path")?.to_string_lossy();
+    let aside = parent.join(format!(".revert-{}", name));
+    let _ = fs::remove_dir_all(&aside);
+    fs::rename(path, &aside).map_err(|e| format!("Failed to move the current version aside: {}", e))?;
+    if let Err(e) = fs::rename(backup, path) {
+        let _ = fs::rename(&aside, path);
+        return Err(format!("Failed to restore the previous version: {}", e));
+    }
+    // the version we just replaced becomes the backup, so a revert can be undone
+    fs::rename(&aside, backup).map_err(|e| format!("Failed to keep the replaced version: {}", e))
+}
+
+/// Checks or updates `targets` one after another on a background thread.
+pub fn spawn(targets: Vec<UpdateTarget>, job: Job) -> mpsc::Receiver<UpdateEvent> {
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        for target in targets {
+            let _ = sender.send(UpdateEvent::Started(target.name.clone()));
+            let mut last_reported = 0;
+            let mut progress = |received: usize, total: usize| {
+                if received == total || received > last_reported + total / 50 {
+                    last_reported = received;
+                    let _ = sender.send(UpdateEvent::Progress { plugin: target.name.clone(), received, total });
+                }
+            };
+            let event = match job {
+                Job::Check => UpdateEvent::Checked {
+                    plugin: target.name.clone(),
+                    result: check_plugin(&target, &mut progress),
+                },
+                Job::Apply => UpdateEvent::Finished {
+                    plugin: target.name.clone(),
+                    result: update_plugin(&target, &mut progress),
+                },
+            };
+            let _ = sender.send(event);
+        }
+        let _ = sender.send(UpdateEvent::Done);
+    });
+    receiver
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_dir::TestDir;
+    use git2::{Commit, Signature};
+
+    fn no_progress(_: usize, _: usize) {}
+
+    /// A bare repository to update from, and a plugin that follows it but is not installed yet.
+    fn setup() -> (TestDir, Repository, UpdateTarget) {
+        let dir = TestDir::new("updater");
+        let upstream_path = dir.path().join("upstream.git");
+        let upstream = Repository::init_bare(&upstream_path).unwrap();
+        fs::create_dir_all(dir.path().join("plugins")).unwrap();
+        let target = UpdateTarget {
+            name: "snake".to_string(),
+            path: dir.path().join("plugins/snake"),
+            source: UpdateSource { url: format!("file://{}", upstream_path.display()), ..Default::default() },
+            preference: Preference::default(),
+            backup: dir.path().join("backups/snake"),
+        };
+        (dir, upstream, target)
+    }
+
+    /// Commits a version of the plugin on top of `main`.
+    fn release(upstream: &Repository, version: &str, code: &str) -> Oid {
+        let manifest = format!("<plugin><name>Snake</name><version>{}</version></plugin>", version);
+        let mut tree = upstream.treebuilder(None).unwrap();
+        tree.insert("index.xml", upstream.blob(manifest.as_bytes()).unwrap(), 0o100644).unwrap();
+        tree.insert("index.lua", upstream.blob(code.as_bytes()).unwrap(), 0o100644).unwrap();
+        let tree = upstream.find_tree(tree.write().unwrap()).unwrap();
+        let parent = upstream.find_reference("refs/heads/main").and_then(|r| r.peel_to_commit()).ok();
+        let parents: Vec<&Commit> = parent.iter().collect();
+        let signature = Signature::now("Orbit", "orbit@example.com").unwrap();
+        upstream.commit(Some("refs/heads/main"), &signature, &signature, version, &tree, &parents).unwrap()
+    }
+
+    fn tag(upstream: &Repository, name: &str, oid: Oid) {
+        upstream.tag_lightweight(name, &upstream.find_object(oid, None).unwrap(), false).unwrap();
+    }
+
+    fn version(path: &Path) -> String {
+        Manifest::load(path).unwrap().version
+    }
+
+    #[test]
+    fn compare_versions_orders_numbers() {
+        assert_eq!(compare_versions("v1.10.0", "v1.9.2"), Ordering::Greater);
+        assert_eq!(compare_versions("1.2", "1.2.1"), Ordering::Less);
+        assert_eq!(compare_versions("v2", "release-2"), Ordering::Greater);
+        assert_eq!(compare_versions("v1.0", "v1.0"), Ordering::Equal);
+    }
+
+    #[test]
+    fn installs_and_updates_from_the_branch() {
+        let (_dir, upstream, target) = setup();
+        let first = release(&upstream, "1.0", "return 1");
+        let outcome = update_plugin(&target, &mut no_progress).unwrap();
+        assert!(matches!(outcome, UpdateOutcome::Reinstalled(ref id) if *id == short_id(first)));
+        assert_eq!(installed_commit(&target.path), Some(first.to_string()));
+
+        let second = release(&upstream, "1.1", "return 2");
+        let check = check_plugin(&target, &mut no_progress).unwrap();
+        assert!(check.has_update());
+        assert_eq!(check.channel, "branch main");
+        assert_eq!((check.installed_version.as_str(), check.available_version.as_str()), ("1.0", "1.1"));
+
+        let outcome = update_plugin(&target, &mut no_progress).unwrap();
+        assert!(matches!(outcome, UpdateOutcome::Updated { ref from, ref to } if *from == short_id(first) && *to == short_id(second)));
+        assert_eq!(installed_commit(&target.path), Some(second.to_string()));
+        assert_eq!(version(&target.path), "1.1");
+        assert_eq!(version(&target.backup), "1.0");
+        assert!(matches!(update_plugin(&target, &mut no_progress).unwrap(), UpdateOutcome::UpToDate(_)));
+    }
+
+    #[test]
+    fn pinned_revision_wins_over_the_branch() {
+        let (_dir, upstream, mut target) = setup();
+        let first = release(&upstream, "1.0", "return 1");
+        tag(&upstream, "v1.0", first);
+        let second = release(&upstream, "1.1", "return 2");
+
+        target.preference.pin = Some("v1.0".to_string());
+        assert_eq!(check_plugin(&target, &mut no_progress).unwrap().channel, "pinned to v1.0");
+        update_plugin(&target, &mut no_progress).unwrap();
+        assert_eq!(installed_commit(&target.path), Some(first.to_string()));
+
+        target.preference.pin = Some(second.to_string());
+        update_plugin(&target, &mut no_progress).unwrap();
+        assert_eq!(installed_commit(&target.path), Some(second.to_string()));
+
+        target.preference.pin = Some("v9".to_string());
+        assert!(update_plugin(&target, &mut no_progress).unwrap_err().contains("'v9' was not found"));
+    }
+
+    #[test]
+    fn tag_pattern_picks_the_newest_release() {
+        let (_dir, upstream, mut target) = setup();
+        let old = release(&upstream, "1.9.0", "return 1");
+        tag(&upstream, "v1.9.0", old);
+        let newest = release(&upstream, "1.10.0", "return 2");
+        tag(&upstream, "v1.10.0", newest);
+        let nightly = release(&upstream, "2.0-dev", "return 3");
+        tag(&upstream, "nightly", nightly);
+
+        target.source.tags = Some("v*".to_string());
+        let check = check_plugin(&target, &mut no_progress).unwrap();
+        assert_eq!(check.channel, "tag v1.10.0");
+        assert_eq!(check.available_version, "1.10.0");
+        assert_eq!(check.installed_revision, None);
+        update_plugin(&target, &mut no_progress).unwrap();
+        assert_eq!(installed_commit(&target.path), Some(newest.to_string()));
+
+        target.source.tags = Some("release-*".to_string());
+        assert!(check_plugin(&target, &mut no_progress).unwrap_err().contains("No tag matching"));
+    }
+
+    #[test]
+    fn broken_update_keeps_the_installed_version() {
+        let (dir, upstream, target) = setup();
+        let first = release(&upstream, "1.0", "return 1");
+        update_plugin(&target, &mut no_progress).unwrap();
+        release(&upstream, "1.1", "return function(");
+
+        let error = update_plugin(&target, &mut no_progress).unwrap_err();
+        assert!(error.contains("does not load"), "{}", error);
+        assert_eq!(installed_commit(&target.path), Some(first.to_string()));
+        assert_eq!(version(&target.path), "1.0");
+        assert!(!target.backup.exists());
+        assert!(!dir.path().join("plugins/.update-snake").exists());
+    }
+
+    #[test]
+    fn broken_install_is_not_left_behind() {
+        let (dir, upstream, target) = setup();
+        release(&upstream, "1.0", "return function(");
+        assert!(update_plugin(&target, &mut no_progress).unwrap_err().contains("does not load"));
+        assert!(!target.path.exists());
+        assert!(!dir.path().join("plugins/.update-snake").exists());
+    }
+}
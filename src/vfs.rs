@@ -0,0 +1,264 @@
+use crate::conflicts;
+use crate::load_order::LoadOrder;
+use crate::manifest::Manifest;
+use crate::package;
+use crate::plugin_state::PluginStates;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io::Read;
+use std::path::{Component, Path, PathBuf};
+
+/// Where a layer's files come from.
+#[derive(Clone, Debug)]
+pub enum Source {
+    Folder(PathBuf),
+    /// A zip archive in `CONFIG/plugins`, read without extracting it.
+    Archive(PathBuf),
+}
+
+impl Source {
+    pub fn path(&self) -> &Path {
+        match self {
+            Source::Folder(path) | Source::Archive(path) => path,
+        }
+    }
+}
+
+/// One plugin's files in the virtual filesystem.
+#[derive(Clone, Debug)]
+pub struct Layer {
+    pub plugin: String,
+    pub source: Source,
+}
+
+/// The files of the game and every enabled mod merged into one tree. The game plugin
+/// is the base layer and the other enabled plugins go on top in load order, so a mod's
+/// `sprites/player.png` replaces the game's. Paths are relative and use `/`; each
+/// plugin's own files (`index.xml`, `index.lua`, readme, logo...) are left out.
+#[derive(Default)]
+pub struct Vfs {
+    pub layers: Vec<Layer>,
+    /// Path -> every layer that has the file, bottom first, with where the file is.
+    files: BTreeMap<String, Vec<(usize, PathBuf)>>,
+}
+
+/// A plugin found in `CONFIG/plugins`, before layering.
+struct Mount {
+    dependencies: Vec<String>,
+    source: Source,
+    /// VFS path -> where the file is.
+    files: Vec<(String, PathBuf)>,
+}
+
+impl Vfs {
+    /// Layers the enabled plugins in `CONFIG/plugins`, folders and `.zip` archives
+    /// alike. A folder wins over an archive of the same plugin.
+    pub fn mount(config_dir: &Path) -> Vfs {
+        let plugins_dir = config_dir.join("plugins");
+        let states = PluginStates::load(config_dir);
+        let mut mounts: BTreeMap<String, Mount> = BTreeMap::new();
+        let mut archives = Vec::new();
+        if let Ok(entries) = fs::read_dir(&plugins_dir) {
+            for entry in entries.flatten() {
+                let name = entry.file_name().to_string_lossy().to_string();
+                let path = entry.path();
+                if name.starts_with('.') {
+                    continue;
+                }
+                if path.is_dir() {
+                    mounts.insert(name, mount_folder(&path));
+                } else if path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("zip")) {
+                    archives.push(path);
+                }
+            }
+        }
+        for path in archives {
+            if let Ok((name, mount)) = mount_archive(&path) {
+                mounts.entry(name).or_insert(mount);
+            }
+        }
+        mounts.retain(|name, _| states.is_enabled(name));
+
+        let plugins: Vec<(String, Vec<String>)> = mounts.iter()
+            .map(|(name, mount)| (name.clone(), mount.dependencies.clone()))
+            .collect();
+        let mut order = LoadOrder::load(config_dir).arrange(&plugins);
+        // the game is the base whatever the load order says
+        order.sort_by_key(|name| name != "game");
+
+        let mut vfs = Vfs::default();
+        for name in order {
+            let Some(mount) = mounts.remove(&name) else {
+                continue;
+            };
+            let layer = vfs.layers.len();
+            for (path, location) in mount.files {
+                vfs.files.entry(path).or_default().push((layer, location));
+            }
+            vfs.layers.push(Layer { plugin: name, source: mount.source });
+        }
+        vfs
+    }
+
+    /// Where the file behind `path` is: the top layer's copy.
+    pub fn locate(&self, path: &str) -> Option<&Path> {
+        self.files.get(&key(path)?)?.last().map(|(_, location)| location.as_path())
+    }
+
+    /// The layer `path` is read from.
+    pub fn layer_of(&self, path: &str) -> Option<&Layer> {
+        self.files.get(&key(path)?)?.last().map(|(layer, _)| &self.layers[*layer])
+    }
+
+    pub fn read(&self, path: &str) -> Result<Vec<u8>, String> {
+        let location = self.locate(path).ok_or_else(|| format!("'{}' is not in the game or any enabled mod", path))?;
+        read(location)
+    }
+
+    /// Names of the files and folders directly inside `dir` across all layers; an
+    /// empty `dir` is the root.
+    pub fn list(&self, dir: &str) -> Vec<String> {
+        let prefix = match key(dir) {
+            Some(dir) => format!("{}/", dir),
+            None if dir.split(['/', '\\']).all(|part| part.is_empty() || part == ".") => String::new(),
+            None => return Vec::new(),
+        };
+        let names: BTreeSet<&str> = self.files.range(prefix.clone()..)
+            .map(|(path, _)| path)
+            .take_while(|path| path.starts_with(&prefix))
+            .filter_map(|path| path[prefix.len()..].split('/').next())
+            .collect();
+        names.into_iter().map(str::to_string).collect()
+    }
+
+    /// Every path with the layers that have it, bottom first; the last one is used.
+    pub fn files(&self) -> impl Iterator<Item = (&str, Vec<&Layer>)> {
+        self.files.iter().map(|(path, providers)| {
+            (path.as_str(), providers.iter().map(|(layer, _)| &self.layers[*layer]).collect())
+        })
+    }
+}
+
+/// Turns a path from Lua into a VFS path: relative, `/`-separated, without `.` parts.
+/// Absolute paths and `..` are not in the VFS.
+fn key(path: &str) -> Option<String> {
+    let mut parts = Vec::new();
+    for component in Path::new(&path.replace('\\', "/")).components() {
+        match component {
+            Component::Normal(part) => parts.push(part.to_string_lossy().to_string()),
+            Component::CurDir => {},
+            _ => return None,
+        }
+    }
+    (!parts.is_empty()).then(|| parts.join("/"))
+}
+
+fn dependencies(manifest: &Manifest) -> Vec<String> {
+    manifest.dependencies.iter().map(|dependency| dependency.name.clone()).collect()
+}
+
+fn mount_folder(dir: &Path) -> Mount {
+    let manifest = Manifest::load(dir).unwrap_or_default();
+    let mut relative = Vec::new();
+    conflicts::collect(dir, dir, &mut relative);
+    let files = relative.into_iter()
+        .filter(|relative| !conflicts::is_plugin_file(relative, manifest.logo.as_deref()))
+        .map(|relative| (relative.to_string_lossy().replace('\\', "/"), dir.join(relative)))
+        .collect();
+    Mount { dependencies: dependencies(&manifest), source: Source::Folder(dir.to_path_buf()), files }
+}
+
+/// Reads an archive's file list and manifest. Like an installed package, the plugin
+/// may sit at the root of the archive or inside a single top-level folder.
+fn mount_archive(path: &Path) -> Result<(String, Mount), String> {
+    let mut archive = package::open_archive(path)?;
+    let mut entries = Vec::new();
+    for i in 0..archive.len() {
+        let file = archive.by_index(i).map_err(|e| format!("Failed to read archive entry: {}", e))?;
+        if let Some(entry) = file.enclosed_name().filter(|_| !file.is_dir()) {
+            entries.push(entry);
+        }
+    }
+    let index = entries.iter()
+        .filter(|entry| entry.file_name().is_some_and(|n| n == "index.xml") && entry.components().count() <= 2)
+        .min_by_key(|entry| entry.components().count())
+        .ok_or_else(|| format!("{:?} does not contain an index.xml", path))?;
+    let prefix = index.parent().map(Path::to_path_buf).unwrap_or_default();
+    let manifest = Manifest::parse(&String::from_utf8_lossy(&read(&path.join(index))?))?;
+    let name = match prefix.file_name() {
+        Some(folder) => folder.to_string_lossy().to_string(),
+        None => package::name_from_stem(&path.file_stem().unwrap_or_default().to_string_lossy()),
+    };
+    let files = entries.iter()
+        .filter_map(|entry| Some((entry.strip_prefix(&prefix).ok()?, entry)))
+        .filter(|(relative, _)| {
+            !relative.components().any(|c| c.as_os_str().to_string_lossy().starts_with('.'))
+                && !conflicts::is_plugin_file(relative, manifest.logo.as_deref())
+        })
+        .map(|(relative, entry)| (relative.to_string_lossy().replace('\\', "/"), path.join(entry)))
+        .collect();
+    Ok((name, Mount { dependencies: dependencies(&manifest), source: Source::Archive(path.to_path_buf()), files }))
+}
+
+/// Reads a file the VFS points at: a file on disk, or an entry of a zip archive given
+/// as the archive's path followed by the entry's path inside it.
+pub fn read(location: &Path) -> Result<Vec<u8>, String> {
+    if location.is_file() {
+        return fs::read(location).map_err(|e| format!("Failed to read {:?}: {}", location, e));
+    }
+    let archive_path = location.ancestors().skip(1).find(|ancestor| ancestor.is_file())
+        .ok_or_else(|| format!("Failed to read {:?}: it does not exist", location))?;
+    let entry = location.strip_prefix(archive_path).unwrap_or(location).to_string_lossy().replace('\\', "/");
+    let mut archive = package::open_archive(archive_path)?;
+    let mut file = archive.by_name(&entry)
+        .map_err(|e| format!("Failed to read {} from {:?}: {}", entry, archive_path, e))?;
+    let mut data = Vec::new();
+    file.read_to_end(&mut data)
+        .map_err(|e| format!("Failed to read {} from {:?}: {}", entry, archive_path, e))?;
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_dir::TestDir;
+
+    #[test]
+    fn key_normalizes_relative_paths() {
+        assert_eq!(key("sprites/player.png").as_deref(), Some("sprites/player.png"));
+        assert_eq!(key("./sprites//player.png").as_deref(), Some("sprites/player.png"));
+        assert_eq!(key("sprites\\player.png").as_deref(), Some("sprites/player.png"));
+        assert_eq!(key("sprites/"), Some("sprites".to_string()));
+        for outside in ["", ".", "../secret", "sprites/../../secret", "/etc/passwd"] {
+            assert_eq!(key(outside), None, "{:?}", outside);
+        }
+    }
+
+    #[test]
+    fn layers_enabled_plugins_over_the_game() {
+        let dir = TestDir::new("vfs");
+        dir.write("plugins/game/index.xml", "<plugin><name>Game</name></plugin>");
+        dir.write("plugins/game/index.lua", "");
+        dir.write("plugins/game/sprites/player.png", "game");
+        dir.write("plugins/game/music/theme.ogg", "game");
+        dir.write("plugins/mod/index.xml", "<plugin><name>Mod</name></plugin>");
+        dir.write("plugins/mod/sprites/player.png", "mod");
+        dir.write("plugins/mod/sprites/enemies/bat.png", "mod");
+        dir.write("plugins/off/sprites/ghost.png", "off");
+        dir.write("plugins.xml", r#"<plugins><plugin name="off" enabled="false"/></plugins>"#);
+        let vfs = Vfs::mount(dir.path());
+
+        assert_eq!(vfs.layers.iter().map(|layer| layer.plugin.as_str()).collect::<Vec<_>>(), ["game", "mod"]);
+        assert_eq!(vfs.read("sprites/player.png").unwrap(), b"mod");
+        assert_eq!(vfs.layer_of("music\\theme.ogg").unwrap().plugin, "game");
+        assert!(vfs.locate("sprites/ghost.png").is_none());
+        assert!(vfs.locate("index.lua").is_none());
+
+        assert_eq!(vfs.list(""), ["music", "sprites"]);
+        assert_eq!(vfs.list("./"), ["music", "sprites"]);
+        assert_eq!(vfs.list("sprites/"), ["enemies", "player.png"]);
+        assert_eq!(vfs.list("sprites/enemies"), ["bat.png"]);
+        assert!(vfs.list("sprites/player.png").is_empty());
+        assert!(vfs.list("../plugins").is_empty());
+    }
+}
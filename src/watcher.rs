@@ -0,0 +1,100 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+/// Polls a directory tree and reports files that were added, changed or removed.
+/// Hidden entries (`.git`, staging folders) are skipped.
+pub struct FileWatcher {
+    root: PathBuf,
+    interval: Duration,
+    last_scan: Instant,
+    files: HashMap<PathBuf, SystemTime>,
+}
+
+impl FileWatcher {
+    pub fn new(root: &Path, interval: Duration) -> FileWatcher {
+        let mut files = HashMap::new();
+        scan(root, &mut files);
+        FileWatcher { root: root.to_path_buf(), interval, last_scan: Instant::now(), files }
+    }
+
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    /// Rescans once the interval has passed; returns the paths that differ from the last scan.
+    pub fn poll(&mut self) -> Vec<PathBuf> {
+        if self.last_scan.elapsed() < self.interval {
+            return Vec::new();
+        }
+        self.last_scan = Instant::now();
+        let mut files = HashMap::new();
+        scan(&self.root, &mut files);
+        let mut changed: Vec<PathBuf> = files.iter()
+            .filter(|(path, modified)| self.files.get(*path) != Some(modified))
+            .map(|(path, _)| path.clone())
+            .collect();
+        changed.extend(self.files.keys().filter(|path| !files.contains_key(*path)).cloned());
+        self.files = files;
+        changed
+    }
+}
+
+fn scan(dir: &Path, files: &mut HashMap<PathBuf, SystemTime>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        if entry.file_name().to_string_lossy().starts_with('.') {
+            continue;
+        }
+        let path = entry.path();
+        if path.is_dir() {
+            scan(&path, files);
+        } else if let Ok(modified) = entry.metadata().and_then(|m| m.modified()) {
+            files.insert(path, modified);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_dir::TestDir;
+
+    #[test]
+    fn reports_added_changed_and_removed_files() {
+        let root = TestDir::new("watcher");
+        let kept = root.write("game/index.lua", "return {}");
+        let removed = root.write("game/old.lua", "return 1");
+        let changed = root.write("game/lib/util.lua", "return 2");
+        let mut watcher = FileWatcher::new(root.path(), Duration::ZERO);
+        assert!(watcher.poll().is_empty());
+
+        let added = root.write("mod/index.lua", "return {}");
+        fs::remove_file(&removed).unwrap();
+        let file = fs::File::options().write(true).open(&changed).unwrap();
+        file.set_modified(SystemTime::now() + Duration::from_secs(60)).unwrap();
+        let mut reported = watcher.poll();
+        reported.sort();
+        let mut expected = vec![added, removed, changed];
+        expected.sort();
+        assert_eq!(reported, expected);
+        assert!(!reported.contains(&kept));
+        assert!(watcher.poll().is_empty());
+    }
+
+    #[test]
+    fn skips_hidden_entries_and_waits_for_the_interval() {
+        let root = TestDir::new("watcher-hidden");
+        let mut watcher = FileWatcher::new(root.path(), Duration::ZERO);
+        root.write(".git/HEAD", "ref: refs/heads/main");
+        root.write("game/.staging/index.lua", "return {}");
+        assert!(watcher.poll().is_empty());
+
+        let mut slow = FileWatcher::new(root.path(), Duration::from_secs(3600));
+        root.write("game/index.lua", "return {}");
+        assert!(slow.poll().is_empty());
+    }
+}